@@ -3,17 +3,20 @@
 
 #![allow(clippy::ptr_arg)]
 
-use error::VmResult;
+use error::{RuntimeError, StatusCode, VmResult};
 use functional_tests::run_config::RunConfig;
 use halo2_proofs::pasta::{EqAffine, Fp};
 use halo2_proofs::poly::commitment::Params;
 use logger::prelude::*;
 use movelang::argument::{parse_transaction_argument, ScriptArgument, ScriptArguments};
 use movelang::compiler::compile_script;
-use movelang::state::StateStore;
 use std::path::PathBuf;
 use std::process::exit;
 use structopt::StructOpt;
+use vm::circuit::{MoveCircuit, FIRST_PUBLIC_ROW};
+use vm::keys::KeyInfo;
+use vm::package::ScriptPackage;
+use vm::proof::{script_hash, MoveProof};
 use vm::runtime::Runtime;
 
 #[derive(StructOpt)]
@@ -59,10 +62,296 @@ pub enum Command {
 
         #[structopt(long = "print-layout")]
         print_layout: bool,
+
+        #[structopt(
+            long = "loop-bound",
+            help = "how many times loops are unrolled in the circuit",
+            default_value = "16"
+        )]
+        loop_bound: usize,
+
+        #[structopt(
+            long = "vector-capacity",
+            help = "how many elements a vector can hold in the circuit",
+            default_value = "16"
+        )]
+        vector_capacity: usize,
+
+        #[structopt(
+            long = "lanes",
+            help = "how many lanes of advice columns the chips are laid out on, 1, 2 or 4, more lanes make the circuit wider and shorter",
+            default_value = "1"
+        )]
+        lanes: usize,
+
+        #[structopt(
+            long = "public-args",
+            help = "indices of the script arguments exposed as public inputs, separated with comma, overrides the public-args directive",
+            use_delimiter = true
+        )]
+        public_args: Option<Vec<usize>>,
+
+        #[structopt(
+            short = "o",
+            long = "proof-file",
+            help = "write the proof into this file"
+        )]
+        proof_file: Option<PathBuf>,
+
+        #[structopt(
+            long = "params-file",
+            help = "load the params from this file, or save them into it if it doesn't exist"
+        )]
+        params_file: Option<PathBuf>,
+
+        #[structopt(
+            long = "key-info-file",
+            help = "regenerate the keys recorded in this file, or record them into it if it doesn't exist"
+        )]
+        key_info_file: Option<PathBuf>,
+    },
+
+    #[structopt(
+        name = "compile",
+        about = "Compile a script and the modules it depends on into a package file."
+    )]
+    Compile {
+        #[structopt(
+            short = "s",
+            long = "script-file",
+            help = "path to .move file containing script"
+        )]
+        script: PathBuf,
+
+        #[structopt(
+            short = "m",
+            long = "module-dir",
+            help = "directory containing modules"
+        )]
+        modules: Option<PathBuf>,
+
+        #[structopt(
+            long = "public-args",
+            help = "indices of the script arguments exposed as public inputs, separated with comma, overrides the public-args directive",
+            use_delimiter = true
+        )]
+        public_args: Option<Vec<usize>>,
+
+        #[structopt(
+            short = "o",
+            long = "package-file",
+            help = "write the package into this file"
+        )]
+        package_file: PathBuf,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "setup",
+        about = "Generate the params of a package and record what its keys are generated for."
+    )]
+    Setup {
+        #[structopt(short = "p", long = "package-file", help = "path to the package file")]
+        package_file: PathBuf,
+
+        #[structopt(
+            long = "args",
+            help = "sample arguments the circuit is sized with, multiple args should separate with space",
+            parse(try_from_str = parse_transaction_argument),
+            required_unless = "k"
+        )]
+        args: Option<Vec<ScriptArgument>>,
+
+        #[structopt(
+            short = "k",
+            help = "the circuit has 2^k rows, found with the sample arguments if not given"
+        )]
+        k: Option<u32>,
+
+        #[structopt(
+            long = "loop-bound",
+            help = "how many times loops are unrolled in the circuit",
+            default_value = "16"
+        )]
+        loop_bound: usize,
+
+        #[structopt(
+            long = "vector-capacity",
+            help = "how many elements a vector can hold in the circuit",
+            default_value = "16"
+        )]
+        vector_capacity: usize,
+
+        #[structopt(
+            long = "lanes",
+            help = "how many lanes of advice columns the chips are laid out on, 1, 2 or 4, more lanes make the circuit wider and shorter",
+            default_value = "1"
+        )]
+        lanes: usize,
+
+        #[structopt(
+            long = "params-file",
+            help = "load the params from this file, or save them into it if it doesn't exist"
+        )]
+        params_file: PathBuf,
+
+        #[structopt(
+            long = "key-info-file",
+            help = "record what the keys are generated for into this file"
+        )]
+        key_info_file: PathBuf,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "prove",
+        about = "Prove an execution of a package with the keys recorded by setup."
+    )]
+    Prove {
+        #[structopt(short = "p", long = "package-file", help = "path to the package file")]
+        package_file: PathBuf,
+
+        #[structopt(
+            long = "args",
+            help = "arguments of the script, multiple args should separate with space",
+            parse(try_from_str = parse_transaction_argument)
+        )]
+        args: Vec<ScriptArgument>,
+
+        #[structopt(long = "params-file", help = "path to the params file")]
+        params_file: PathBuf,
+
+        #[structopt(long = "key-info-file", help = "path to the key info file")]
+        key_info_file: PathBuf,
+
+        #[structopt(
+            short = "o",
+            long = "proof-file",
+            help = "write the proof into this file"
+        )]
+        proof_file: PathBuf,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "verify",
+        about = "Verify a proof against the public inputs it records, without the script source or arguments."
+    )]
+    Verify {
+        #[structopt(short = "p", long = "package-file", help = "path to the package file")]
+        package_file: PathBuf,
+
+        #[structopt(long = "params-file", help = "path to the params file")]
+        params_file: PathBuf,
+
+        #[structopt(long = "key-info-file", help = "path to the key info file")]
+        key_info_file: PathBuf,
+
+        #[structopt(long = "proof-file", help = "path to the proof file")]
+        proof_file: PathBuf,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "execute",
+        about = "Execute a package with the mock prover, or without a prover with --dry-run."
+    )]
+    Execute {
+        #[structopt(
+            long = "dry-run",
+            help = "run the script on the native interpreter, only report the results of the script"
+        )]
+        dry_run: bool,
+
+        #[structopt(short = "p", long = "package-file", help = "path to the package file")]
+        package_file: PathBuf,
+
+        #[structopt(
+            long = "args",
+            help = "arguments of the script, multiple args should separate with space",
+            parse(try_from_str = parse_transaction_argument)
+        )]
+        args: Vec<ScriptArgument>,
+
+        #[structopt(
+            long = "loop-bound",
+            help = "how many times loops are unrolled in the circuit",
+            default_value = "16"
+        )]
+        loop_bound: usize,
+
+        #[structopt(
+            long = "vector-capacity",
+            help = "how many elements a vector can hold in the circuit",
+            default_value = "16"
+        )]
+        vector_capacity: usize,
+
+        #[structopt(
+            long = "lanes",
+            help = "how many lanes of advice columns the chips are laid out on, 1, 2 or 4, more lanes make the circuit wider and shorter",
+            default_value = "1"
+        )]
+        lanes: usize,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "profile",
+        about = "Report the rows, regions, advice cells and lookups of a package's circuit by opcode, function and code location."
+    )]
+    Profile {
+        #[structopt(short = "p", long = "package-file", help = "path to the package file")]
+        package_file: PathBuf,
+
+        #[structopt(
+            long = "args",
+            help = "arguments of the script, multiple args should separate with space, the circuit is profiled without witness if not given",
+            parse(try_from_str = parse_transaction_argument)
+        )]
+        args: Option<Vec<ScriptArgument>>,
+
+        #[structopt(
+            long = "loop-bound",
+            help = "how many times loops are unrolled in the circuit",
+            default_value = "16"
+        )]
+        loop_bound: usize,
+
+        #[structopt(
+            long = "vector-capacity",
+            help = "how many elements a vector can hold in the circuit",
+            default_value = "16"
+        )]
+        vector_capacity: usize,
+
+        #[structopt(
+            long = "lanes",
+            help = "how many lanes of advice columns the chips are laid out on, 1, 2 or 4, more lanes make the circuit wider and shorter",
+            default_value = "1"
+        )]
+        lanes: usize,
+
+        #[structopt(long = "json", help = "also write the report as JSON into this file")]
+        json_file: Option<PathBuf>,
+
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
     },
 }
 
 impl Arguments {
+    #[allow(clippy::too_many_arguments)]
     pub fn run(
         &self,
         script: &PathBuf,
@@ -71,46 +360,28 @@ impl Arguments {
         new_args: &Option<Vec<ScriptArgument>>,
         verbose: bool,
         print_layout: bool,
+        loop_bound: usize,
+        vector_capacity: usize,
+        lanes: usize,
+        public_args: &Option<Vec<usize>>,
+        proof_file: &Option<PathBuf>,
+        params_file: &Option<PathBuf>,
+        key_info_file: &Option<PathBuf>,
     ) -> VmResult<()> {
         logger::init_for_main(verbose);
 
-        let script_file = script.to_str().expect("path is None.");
-
-        // compile script and depended modules
-        let mut targets = vec![];
-        targets.push(script_file.to_string());
-        let config = RunConfig::new(script.as_path())?;
-        for module in config.modules.into_iter() {
-            let path = module_dir
-                .clone()
-                .expect("module_dir is missing")
-                .as_path()
-                .join(module)
-                .to_str()
-                .unwrap()
-                .to_string();
-            targets.push(path);
-        }
-        info!("compile script...");
-        let (compiled_script, compiled_modules) = compile_script(targets)?;
-
-        let script = compiled_script.expect("script is missing");
+        let (package, args) = compile_package(script, module_dir, public_args)?;
         let runtime = Runtime::<Fp>::new();
-        let mut state = StateStore::new();
-        for module in compiled_modules.clone().into_iter() {
-            state.add_module(module);
-        }
 
-        let move_circuit = runtime.create_move_circuit(
-            script.clone(),
-            compiled_modules.clone(),
-            config.args,
-            state.clone(),
-        );
-        let public_inputs = vec![Fp::zero()];
+        let mut move_circuit = package_circuit(&runtime, &package, args);
+        move_circuit.set_loop_bound(loop_bound);
+        move_circuit.set_vector_capacity(vector_capacity);
+        move_circuit.set_lanes(lanes);
         info!("find the best k...");
-        let k = runtime.find_best_k(&move_circuit, vec![public_inputs.clone()])?;
+        let k = runtime.find_best_k(&move_circuit)?;
         info!("k = {}", k);
+        let public_inputs = runtime.public_inputs(&move_circuit)?;
+        log_public_inputs(&public_inputs);
 
         if use_mock {
             info!("run with mock prover...");
@@ -119,41 +390,335 @@ impl Arguments {
 
         if print_layout {
             info!("print circuit layout into layout.svg ...");
-            runtime.print_circuit_layout(k, &move_circuit);
+            runtime.print_circuit_layout(k, &move_circuit)?;
         }
 
         info!("setup move circuit...");
-        let params: Params<EqAffine> = Params::new(k);
-        let pk = runtime.setup_move_circuit(&move_circuit, &params)?;
+        let params = load_or_create_params(&runtime, params_file.as_ref(), k)?;
+        let pk = match key_info_file {
+            Some(path) if path.exists() => {
+                info!("regenerate the keys recorded in {:?}", path);
+                runtime.regenerate_proving_key(&move_circuit, &params, path)?
+            }
+            _ => {
+                let pk = runtime.setup_move_circuit(&move_circuit, &params)?;
+                if let Some(path) = key_info_file {
+                    info!("save the key info into {:?}", path);
+                    runtime.save_key_info(&move_circuit, &params, pk.get_vk(), path)?;
+                }
+                pk
+            }
+        };
 
         info!("prove move circuit...");
-        runtime.prove_move_circuit(
-            move_circuit,
-            &[public_inputs.as_slice()],
-            &params,
-            pk.clone(),
-        )?;
+        let proof = runtime.prove_move_circuit(move_circuit, &public_inputs, &params, &pk)?;
+        info!("verify proof...");
+        runtime.verify_move_proof(&params, pk.get_vk(), &public_inputs, &proof)?;
+        if let Some(proof_file) = proof_file {
+            info!("write proof into {:?}", proof_file);
+            proof.save(proof_file)?;
+        }
 
         if let Some(new_args) = new_args {
             info!("execute script with new arguments");
             let arguments = Some(ScriptArguments::new(new_args.clone()));
 
-            let new_move_circuit =
-                runtime.create_move_circuit(script, compiled_modules, arguments, state);
+            let mut new_move_circuit = package_circuit(&runtime, &package, arguments);
+            new_move_circuit.set_loop_bound(loop_bound);
+            new_move_circuit.set_vector_capacity(vector_capacity);
+            new_move_circuit.set_lanes(lanes);
+            let public_inputs = runtime.public_inputs(&new_move_circuit)?;
 
             info!("prove the new execution with old proving key...");
-            runtime.prove_move_circuit(
-                new_move_circuit,
-                &[public_inputs.as_slice()],
-                &params,
-                pk,
-            )?;
+            let proof =
+                runtime.prove_move_circuit(new_move_circuit, &public_inputs, &params, &pk)?;
+            runtime.verify_move_proof(&params, pk.get_vk(), &public_inputs, &proof)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn compile(
+        &self,
+        script: &PathBuf,
+        module_dir: &Option<PathBuf>,
+        public_args: &Option<Vec<usize>>,
+        package_file: &PathBuf,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let (package, _) = compile_package(script, module_dir, public_args)?;
+        info!("write package into {:?}", package_file);
+        package.save(package_file)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup(
+        &self,
+        package_file: &PathBuf,
+        args: &Option<Vec<ScriptArgument>>,
+        k: Option<u32>,
+        loop_bound: usize,
+        vector_capacity: usize,
+        lanes: usize,
+        params_file: &PathBuf,
+        key_info_file: &PathBuf,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let package = ScriptPackage::load(package_file)?;
+        let runtime = Runtime::<Fp>::new();
+        let arguments = args.clone().map(ScriptArguments::new);
+        let mut move_circuit = package_circuit(&runtime, &package, arguments);
+        move_circuit.set_loop_bound(loop_bound);
+        move_circuit.set_vector_capacity(vector_capacity);
+        move_circuit.set_lanes(lanes);
+        let k = match k {
+            Some(k) => k,
+            None => {
+                info!("find the best k...");
+                runtime.find_best_k(&move_circuit)?
+            }
+        };
+        info!("k = {}", k);
+
+        let params = load_or_create_params(&runtime, Some(params_file), k)?;
+        info!("setup move circuit...");
+        let vk = runtime.setup_verifying_key(&move_circuit, &params)?;
+        info!("save the key info into {:?}", key_info_file);
+        runtime.save_key_info(&move_circuit, &params, &vk, key_info_file)
+    }
+
+    pub fn prove(
+        &self,
+        package_file: &PathBuf,
+        args: &[ScriptArgument],
+        params_file: &PathBuf,
+        key_info_file: &PathBuf,
+        proof_file: &PathBuf,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let package = ScriptPackage::load(package_file)?;
+        let info = KeyInfo::load(key_info_file)?;
+        let runtime = Runtime::<Fp>::new();
+        let arguments = Some(ScriptArguments::new(args.to_vec()));
+        let mut move_circuit = package_circuit(&runtime, &package, arguments);
+        info.configure(&mut move_circuit);
+
+        let params = runtime.load_params(params_file, info.k)?;
+        info!("regenerate the keys recorded in {:?}", key_info_file);
+        let pk = runtime.regenerate_proving_key(&move_circuit, &params, key_info_file)?;
+        let public_inputs = runtime.public_inputs(&move_circuit)?;
+        log_public_inputs(&public_inputs);
+
+        info!("prove move circuit...");
+        let proof = runtime.prove_move_circuit(move_circuit, &public_inputs, &params, &pk)?;
+        info!("write proof into {:?}", proof_file);
+        proof.save(proof_file)
+    }
+
+    pub fn verify(
+        &self,
+        package_file: &PathBuf,
+        params_file: &PathBuf,
+        key_info_file: &PathBuf,
+        proof_file: &PathBuf,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let package = ScriptPackage::load(package_file)?;
+        let proof = MoveProof::load(proof_file)?;
+        if proof.script_hash != script_hash(&package.script)? {
+            return Err(RuntimeError::new(StatusCode::IncompatibleKey)
+                .with_message("proof is made for another script".to_string()));
         }
+        let info = KeyInfo::load(key_info_file)?;
+        let runtime = Runtime::<Fp>::new();
+        // the verifying key only depends on the circuit shape, not on the arguments
+        let mut move_circuit = package_circuit(&runtime, &package, None);
+        info.configure(&mut move_circuit);
 
+        let params = runtime.load_params(params_file, info.k)?;
+        info!("regenerate the keys recorded in {:?}", key_info_file);
+        let vk = runtime.regenerate_verifying_key(&move_circuit, &params, key_info_file)?;
+        log_public_inputs(&proof.public_inputs);
+        runtime.verify_move_proof(&params, &vk, &proof.public_inputs, &proof)?;
+        info!("proof is valid");
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute(
+        &self,
+        dry_run: bool,
+        package_file: &PathBuf,
+        args: &[ScriptArgument],
+        loop_bound: usize,
+        vector_capacity: usize,
+        lanes: usize,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let package = ScriptPackage::load(package_file)?;
+        let runtime = Runtime::<Fp>::new();
+        let arguments = Some(ScriptArguments::new(args.to_vec()));
+        let mut move_circuit = package_circuit(&runtime, &package, arguments);
+        move_circuit.set_loop_bound(loop_bound);
+        move_circuit.set_vector_capacity(vector_capacity);
+        move_circuit.set_lanes(lanes);
+
+        if dry_run {
+            let result = runtime.dry_run(&move_circuit)?;
+            info!("{} steps", result.steps);
+            if let Some(abort) = result.abort {
+                return Err(
+                    RuntimeError::new(StatusCode::MoveAbort).with_message(format!(
+                        "aborted with error code {} at pc {} of {}",
+                        abort.code, abort.pc, abort.function
+                    )),
+                );
+            }
+            for (i, value) in result.return_values.iter().enumerate() {
+                info!("return value #{}: {:?}", i, value);
+            }
+            for (local, value) in package.public_outputs.iter().zip(result.outputs.iter()) {
+                info!("output local #{}: {:?}", local, value);
+            }
+            return Ok(());
+        }
+
+        let k = runtime.find_best_k(&move_circuit)?;
+        info!("k = {}", k);
+        let public_inputs = runtime.public_inputs(&move_circuit)?;
+        log_public_inputs(&public_inputs);
+        runtime.mock_prove_circuit(&move_circuit, vec![public_inputs], k)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn profile(
+        &self,
+        package_file: &PathBuf,
+        args: &Option<Vec<ScriptArgument>>,
+        loop_bound: usize,
+        vector_capacity: usize,
+        lanes: usize,
+        json_file: &Option<PathBuf>,
+        verbose: bool,
+    ) -> VmResult<()> {
+        logger::init_for_main(verbose);
+
+        let package = ScriptPackage::load(package_file)?;
+        let runtime = Runtime::<Fp>::new();
+        let arguments = args.clone().map(ScriptArguments::new);
+        let mut move_circuit = package_circuit(&runtime, &package, arguments);
+        move_circuit.set_loop_bound(loop_bound);
+        move_circuit.set_vector_capacity(vector_capacity);
+        move_circuit.set_lanes(lanes);
+
+        let size = runtime
+            .circuit_size(&move_circuit)?
+            .with_source_lines(package.source_lines.clone());
+        info!("{}", size);
+        if let Some(json_file) = json_file {
+            info!("save the report into {:?}", json_file);
+            size.save_json(json_file)?;
+        }
         Ok(())
     }
 }
 
+/// Compiles the script with the modules named by its directives, the package
+/// exposes the public args given here or else by the directive. The
+/// arguments of the args directive are returned too.
+fn compile_package(
+    script: &PathBuf,
+    module_dir: &Option<PathBuf>,
+    public_args: &Option<Vec<usize>>,
+) -> VmResult<(ScriptPackage, Option<ScriptArguments>)> {
+    let script_file = script.to_str().expect("path is None.");
+
+    // compile script and depended modules
+    let mut targets = vec![];
+    targets.push(script_file.to_string());
+    let config = RunConfig::new(script.as_path())?;
+    for module in config.modules.into_iter() {
+        let path = module_dir
+            .clone()
+            .expect("module_dir is missing")
+            .as_path()
+            .join(module)
+            .to_str()
+            .unwrap()
+            .to_string();
+        targets.push(path);
+    }
+    info!("compile script...");
+    let (compiled_script, compiled_modules, source_lines) = compile_script(targets)?;
+
+    let package = ScriptPackage {
+        script: compiled_script.expect("script is missing"),
+        modules: compiled_modules,
+        public_args: public_args.clone().unwrap_or(config.public_args),
+        public_outputs: config.outputs,
+        source_lines,
+    };
+    Ok((package, config.args))
+}
+
+fn package_circuit<'l>(
+    runtime: &'l Runtime<Fp>,
+    package: &ScriptPackage,
+    args: Option<ScriptArguments>,
+) -> MoveCircuit<'l, Fp> {
+    let mut move_circuit = runtime.create_move_circuit(
+        package.script.clone(),
+        package.modules.clone(),
+        args,
+        package.state(),
+    );
+    move_circuit.set_public_args(package.public_args.clone());
+    move_circuit.set_public_outputs(package.public_outputs.clone());
+    move_circuit
+}
+
+fn load_or_create_params(
+    runtime: &Runtime<Fp>,
+    params_file: Option<&PathBuf>,
+    k: u32,
+) -> VmResult<Params<EqAffine>> {
+    match params_file {
+        Some(path) if path.exists() => {
+            info!("load params from {:?}", path);
+            runtime.load_params(path, k)
+        }
+        _ => {
+            let params = Params::new(k);
+            if let Some(path) = params_file {
+                info!("save params into {:?}", path);
+                runtime.save_params(&params, path)?;
+            }
+            Ok(params)
+        }
+    }
+}
+
+fn log_public_inputs(public_inputs: &[Fp]) {
+    info!(
+        "state root {:?} -> {:?}",
+        public_inputs[0], public_inputs[1]
+    );
+    info!(
+        "public arguments and outputs {:?}",
+        &public_inputs[FIRST_PUBLIC_ROW..]
+    );
+}
+
 fn main() {
     let args = Arguments::from_args();
 
@@ -165,7 +730,118 @@ fn main() {
             ref new_args,
             verbose,
             print_layout,
-        } => args.run(script, modules, use_mock, new_args, verbose, print_layout),
+            loop_bound,
+            vector_capacity,
+            lanes,
+            ref public_args,
+            ref proof_file,
+            ref params_file,
+            ref key_info_file,
+        } => args.run(
+            script,
+            modules,
+            use_mock,
+            new_args,
+            verbose,
+            print_layout,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            public_args,
+            proof_file,
+            params_file,
+            key_info_file,
+        ),
+        Command::Compile {
+            ref script,
+            ref modules,
+            ref public_args,
+            ref package_file,
+            verbose,
+        } => args.compile(script, modules, public_args, package_file, verbose),
+        Command::Setup {
+            ref package_file,
+            args: ref script_args,
+            k,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            ref params_file,
+            ref key_info_file,
+            verbose,
+        } => args.setup(
+            package_file,
+            script_args,
+            k,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            params_file,
+            key_info_file,
+            verbose,
+        ),
+        Command::Prove {
+            ref package_file,
+            args: ref script_args,
+            ref params_file,
+            ref key_info_file,
+            ref proof_file,
+            verbose,
+        } => args.prove(
+            package_file,
+            script_args,
+            params_file,
+            key_info_file,
+            proof_file,
+            verbose,
+        ),
+        Command::Verify {
+            ref package_file,
+            ref params_file,
+            ref key_info_file,
+            ref proof_file,
+            verbose,
+        } => args.verify(
+            package_file,
+            params_file,
+            key_info_file,
+            proof_file,
+            verbose,
+        ),
+        Command::Execute {
+            dry_run,
+            ref package_file,
+            args: ref script_args,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            verbose,
+        } => args.execute(
+            dry_run,
+            package_file,
+            script_args,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            verbose,
+        ),
+        Command::Profile {
+            ref package_file,
+            args: ref script_args,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            ref json_file,
+            verbose,
+        } => args.profile(
+            package_file,
+            script_args,
+            loop_bound,
+            vector_capacity,
+            lanes,
+            json_file,
+            verbose,
+        ),
     };
 
     if let Err(error) = result {
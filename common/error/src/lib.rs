@@ -23,13 +23,20 @@ pub enum StatusCode {
     UnsupportedMoveType,
     TypeMissMatch,
     ArithmeticError,
+    VectorOperationError,
+    GlobalStateError,
     ModuleNotFound,
     ProgramBlockError,
+    LoopBoundExceeded,
     ShouldNotReachHere,
     InternalError,
 
     // Proof system error
     ProofSystemError(Error),
+    ProofFormatError,
+    IncompatibleKey,
+    CircuitTooLarge,
+    UnsupportedLanes,
 
     // error from OS
     OperatingSystemError(anyhow::Error),
@@ -9,13 +9,21 @@ use std::path::Path;
 
 /// directives can be added to move source files to tell vm how to run the test.
 ///
-/// //! mods: arith.move - import a module
-/// //! args: 0, 1       - pass arguments to the script, multiple args should separate with comma
+/// //! mods: arith.move   - import a module
+/// //! args: 0, 1         - pass arguments to the script, multiple args should separate with comma
+/// //! public-args: 0     - expose these arguments as public inputs, the others stay private
+/// //! outputs: 0, 2      - expose the final values of these locals as public outputs, arguments first
+/// //! error: UnsupportedBytecode - the script is rejected with this status code before it is proven
+/// //! lanes: 2              - lay the chips out on this many lanes of advice columns
 
 #[derive(Debug)]
 pub struct RunConfig {
     pub args: Option<ScriptArguments>,
     pub modules: Vec<String>,
+    pub public_args: Vec<usize>,
+    pub outputs: Vec<usize>,
+    pub error: Option<String>,
+    pub lanes: usize,
 }
 
 impl RunConfig {
@@ -23,6 +31,10 @@ impl RunConfig {
         let mut config = RunConfig {
             args: None,
             modules: vec![],
+            public_args: vec![],
+            outputs: vec![],
+            error: None,
+            lanes: 1,
         };
         let file_str = script_file.to_str().expect("path is None.");
 
@@ -39,6 +51,24 @@ impl RunConfig {
             if let Some(s) = s.strip_prefix("//!mods:") {
                 config.modules.push(s.to_string()); //todo: support multiple modules
             }
+            if let Some(s) = s.strip_prefix("//!public-args:") {
+                config.public_args = s
+                    .split(',')
+                    .map(|arg| arg.parse::<usize>())
+                    .collect::<Result<_, _>>()?;
+            }
+            if let Some(s) = s.strip_prefix("//!error:") {
+                config.error = Some(s.to_string());
+            }
+            if let Some(s) = s.strip_prefix("//!lanes:") {
+                config.lanes = s.parse::<usize>()?;
+            }
+            if let Some(s) = s.strip_prefix("//!outputs:") {
+                config.outputs = s
+                    .split(',')
+                    .map(|local| local.parse::<usize>())
+                    .collect::<Result<_, _>>()?;
+            }
         }
         Ok(config)
     }
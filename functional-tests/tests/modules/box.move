@@ -0,0 +1,10 @@
+address 0x1 {
+module Box {
+    struct Box<T: store> has key {
+        value: T,
+    }
+    public fun publish(account: &signer, value: u64) {
+        move_to(account, Box<u64> { value })
+    }
+}
+}
\ No newline at end of file
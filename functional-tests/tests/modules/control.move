@@ -0,0 +1,19 @@
+address 0x1 {
+module Control {
+    public fun classify(x: u8): u8 {
+        if (x > 10) return x - 10;
+        if (x > 5) {
+            return 1
+        };
+        2
+    }
+
+    public fun fail(code: u64) {
+        abort code
+    }
+
+    public fun fail_u8(code: u64): u8 {
+        abort code
+    }
+}
+}
\ No newline at end of file
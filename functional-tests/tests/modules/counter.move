@@ -0,0 +1,24 @@
+address 0x1 {
+module Counter {
+    struct Counter has key {
+        value: u64,
+    }
+    public fun publish(account: &signer, value: u64) {
+        move_to(account, Counter { value })
+    }
+    public fun exists_at(addr: address): bool {
+        exists<Counter>(addr)
+    }
+    public fun value(addr: address): u64 acquires Counter {
+        borrow_global<Counter>(addr).value
+    }
+    public fun increment(addr: address) acquires Counter {
+        let counter = borrow_global_mut<Counter>(addr);
+        counter.value = counter.value + 1;
+    }
+    public fun destroy(addr: address): u64 acquires Counter {
+        let Counter { value } = move_from<Counter>(addr);
+        value
+    }
+}
+}
@@ -0,0 +1,30 @@
+address 0x1 {
+module Point {
+    struct Point {
+        x: u64,
+        y: u64,
+    }
+    public fun new(x: u64, y: u64): Point {
+        Point { x, y }
+    }
+    public fun shift(p: Point, d: u64): Point {
+        let Point { x, y } = p;
+        Point { x: x + d, y }
+    }
+    public fun x(p: &Point): u64 {
+        p.x
+    }
+    public fun clamp_x(p: &mut Point, max: u64) {
+        if (p.x > max) {
+            p.x = max;
+        }
+    }
+    public fun equals(a: &Point, b: &Point): bool {
+        a == b
+    }
+    public fun coords(p: Point): (u64, u64) {
+        let Point { x, y } = p;
+        (x, y)
+    }
+}
+}
\ No newline at end of file
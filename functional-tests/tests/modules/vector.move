@@ -0,0 +1,10 @@
+address 0x1 {
+module Vector {
+    native public fun empty<Element>(): vector<Element>;
+    native public fun length<Element>(v: &vector<Element>): u64;
+    native public fun borrow<Element>(v: &vector<Element>, i: u64): &Element;
+    native public fun push_back<Element>(v: &mut vector<Element>, e: Element);
+    native public fun pop_back<Element>(v: &mut vector<Element>): Element;
+    native public fun swap<Element>(v: &mut vector<Element>, i: u64, j: u64);
+}
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+//! mods: control.move
+//! args: 3u8
+script {
+    use 0x1::Control;
+    fun main(x: u8) {
+        if (x > 5) Control::fail(101);
+        let y = if (x > 5) x + Control::fail_u8(102) else x;
+        assert!(y == 3u8, 103);
+    }
+}
\ No newline at end of file
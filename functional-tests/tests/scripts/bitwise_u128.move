@@ -0,0 +1,10 @@
+//! args: 340282366920938463463374607431768211455u128,1267650600228229401496703205376u128,127u8
+script {
+    fun main(a: u128, b: u128, s: u8) {
+        assert!(a & b == b, 101);
+        assert!(a | b == a, 102);
+        assert!(a ^ b == 340282365653287863235145205935065006079u128, 103);
+        assert!(a << s == 170141183460469231731687303715884105728u128, 104);
+        assert!(a >> s == 1u128, 105);
+    }
+}
\ No newline at end of file
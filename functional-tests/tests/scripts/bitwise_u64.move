@@ -0,0 +1,10 @@
+//! args: 16045690981097471999u64,18446462599038260856u64,36u8
+script {
+    fun main(a: u64, b: u64, s: u8) {
+        assert!(a & b == 16045481047390967416u64, 101);
+        assert!(a | b == 18446672532744765439u64, 102);
+        assert!(a ^ b == 2401191485353798023u64, 103);
+        assert!(a << s == 4503530907893760u64, 104);
+        assert!(a >> s == 233495534u64, 105);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+//! args: 202u8,166u8,3u8
+script {
+    fun main(a: u8, b: u8, s: u8) {
+        assert!(a & b == 130u8, 101);
+        assert!(a | b == 238u8, 102);
+        assert!(a ^ b == 108u8, 103);
+        assert!(a << s == 80u8, 104);
+        assert!(a >> s == 25u8, 105);
+    }
+}
\ No newline at end of file
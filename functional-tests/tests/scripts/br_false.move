@@ -0,0 +1,25 @@
+//! args: 4u8,false
+script {
+    fun main(n: u8, b: bool) {
+        // an if without else falls through to its body and jumps over it
+        // with BrFalse when the condition is false
+        let r = 0u8;
+        if (n > 2) {
+            r = r + 1;
+        };
+        if (b) {
+            r = r + 2;
+        };
+        if (!b) {
+            r = r + 4;
+        };
+        assert!(r == 5u8, 101);
+
+        // the loop header leaves the loop with BrFalse
+        let i = 0u8;
+        while (i < n) {
+            i = i + 1;
+        };
+        assert!(i == n, 102);
+    }
+}
\ No newline at end of file
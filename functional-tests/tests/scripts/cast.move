@@ -0,0 +1,12 @@
+//! args: 255u64,18446744073709551615u128
+script {
+    fun main(a: u64, b: u128) {
+        let x = (a as u8);
+        assert!(x == 255u8, 101);
+        let y = (x as u128);
+        assert!(y == 255u128, 102);
+        let z = (b as u64);
+        assert!(z == 18446744073709551615u64, 103);
+        assert!((z as u128) == b, 104);
+    }
+}
\ No newline at end of file
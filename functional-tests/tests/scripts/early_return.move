@@ -0,0 +1,10 @@
+//! mods: control.move
+//! args: 15u8,7u8,3u8
+script {
+    use 0x1::Control;
+    fun main(a: u8, b: u8, c: u8) {
+        assert!(Control::classify(a) == 5u8, 101);
+        assert!(Control::classify(b) == 1u8, 102);
+        assert!(Control::classify(c) == 2u8, 103);
+    }
+}
\ No newline at end of file
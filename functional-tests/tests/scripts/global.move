@@ -0,0 +1,14 @@
+//! mods: counter.move
+//! args: 0x2,0x2
+script {
+    use 0x1::Counter;
+    fun main(account: &signer, addr: address) {
+        if (!Counter::exists_at(addr)) {
+            Counter::publish(account, 3);
+        };
+        Counter::increment(addr);
+        assert!(Counter::value(addr) == 4, 101);
+        assert!(Counter::destroy(addr) == 4, 102);
+        assert!(!Counter::exists_at(addr), 103);
+    }
+}
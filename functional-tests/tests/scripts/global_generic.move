@@ -0,0 +1,9 @@
+//! mods: box.move
+//! args: 0x2
+//! error: UnsupportedBytecode
+script {
+    use 0x1::Box;
+    fun main(account: &signer) {
+        Box::publish(account, 7);
+    }
+}
\ No newline at end of file
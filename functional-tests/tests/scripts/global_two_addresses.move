@@ -0,0 +1,15 @@
+//! mods: counter.move
+//! args: 0x2, 0x2, 0x2, 0x3, true
+script {
+    use 0x1::Counter;
+    fun main(account: &signer, owner: address, a: address, b: address, flag: bool) {
+        Counter::publish(account, 3);
+        if (flag) {
+            Counter::increment(a);
+        } else {
+            Counter::increment(b);
+        };
+        assert!(Counter::value(owner) == 4, 101);
+        assert!(!Counter::exists_at(if (flag) b else a), 102);
+    }
+}
\ No newline at end of file
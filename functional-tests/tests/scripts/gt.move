@@ -0,0 +1,13 @@
+//! args: 1u64,2u64,0u8,255u8
+script {
+    fun main(a: u64, b: u64, c: u8, d: u8) {
+        let m = b > a;
+        assert!(m == true, 101);
+        let n = a > b;
+        assert!(n == false, 102);
+        let o = d > c;
+        assert!(o == true, 103);
+        let p = c > c;
+        assert!(p == false, 104);
+    }
+}
\ No newline at end of file
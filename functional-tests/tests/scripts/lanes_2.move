@@ -0,0 +1,13 @@
+//! args: 5u8
+//! lanes: 2
+script {
+    fun main(n: u8) {
+        let i = 0u8;
+        let sum = 0u8;
+        while (i < n) {
+            sum = sum + i * 2;
+            i = i + 1;
+        };
+        assert!(sum == 20u8, 101);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+//! mods: counter.move
+//! args: 0x2,0x2
+//! lanes: 4
+script {
+    use 0x1::Counter;
+    fun main(account: &signer, addr: address) {
+        if (!Counter::exists_at(addr)) {
+            Counter::publish(account, 3);
+        };
+        Counter::increment(addr);
+        assert!(Counter::value(addr) == 4, 101);
+    }
+}
\ No newline at end of file
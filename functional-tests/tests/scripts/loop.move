@@ -0,0 +1,24 @@
+//! args: 5u8
+script {
+    fun main(n: u8) {
+        let i = 0u8;
+        let sum = 0u8;
+        while (i < n) {
+            sum = sum + i;
+            i = i + 1;
+        };
+        assert!(sum == 10u8, 101);
+
+        let count = 0u8;
+        let j = 0u8;
+        while (j < n) {
+            let k = 0u8;
+            while (k < j) {
+                count = count + 1;
+                k = k + 1;
+            };
+            j = j + 1;
+        };
+        assert!(count == 10u8, 102);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+//! args: 7u8
+script {
+    fun main(n: u8) {
+        let r;
+        if (n > 2) {
+            if (n > 5) {
+                r = 1u8;
+            } else {
+                r = 2u8;
+            }
+        } else {
+            r = 3u8;
+        };
+        assert!(r == 1u8, 101);
+    }
+}
\ No newline at end of file
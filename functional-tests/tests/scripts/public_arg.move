@@ -0,0 +1,9 @@
+//! args: 7u64, 3u64
+//! public-args: 0
+//! outputs: 0
+script {
+    fun main(x: u64, w: u64) {
+        assert!(x > w, 101);
+        x = x - w;
+    }
+}
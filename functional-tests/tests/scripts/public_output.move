@@ -0,0 +1,11 @@
+//! args: 3u64
+//! outputs: 0
+script {
+    fun main(x: u64) {
+        if (x > 2) {
+            x = x * 2;
+        } else {
+            x = x + 1;
+        };
+    }
+}
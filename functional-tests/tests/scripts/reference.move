@@ -0,0 +1,20 @@
+//! mods: point.move
+//! args: 7u64,3u64
+script {
+    use 0x1::Point;
+    fun main(a: u64, b: u64) {
+        let p = Point::new(a, 1);
+        let q = Point::new(b, 1);
+        Point::clamp_x(&mut p, 5);
+        Point::clamp_x(&mut q, 5);
+        assert!(Point::x(&p) == 5, 101);
+        assert!(Point::x(&q) == 3, 102);
+        let (_, y) = Point::coords(p);
+        let r = &mut y;
+        if (a > b) {
+            *r = *r + 1;
+        };
+        assert!(y == 2, 103);
+        let (_, _) = Point::coords(q);
+    }
+}
\ No newline at end of file
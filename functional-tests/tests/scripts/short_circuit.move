@@ -0,0 +1,16 @@
+//! args: 3u8,true
+script {
+    fun main(n: u8, b: bool) {
+        let r = 0u8;
+        if (n > 2 && n < 5) {
+            r = r + 1;
+        };
+        if (n > 5 || b) {
+            r = r + 2;
+        };
+        if (n > 5 && b || n == 3) {
+            r = r + 4;
+        };
+        assert!(r == 7u8, 101);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+//! mods: point.move
+//! args: 7u64
+script {
+    use 0x1::Point;
+    fun main(n: u64) {
+        let p = Point::new(n, 1);
+        if (n > 2) {
+            p = Point::shift(p, 3);
+        };
+        let (x, y) = Point::coords(p);
+        assert!(x == 10, 101);
+        assert!(y == 1, 102);
+    }
+}
\ No newline at end of file
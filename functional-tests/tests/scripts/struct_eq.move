@@ -0,0 +1,15 @@
+//! mods: point.move
+//! args: 7u64
+script {
+    use 0x1::Point;
+    fun main(n: u64) {
+        let p = Point::new(n, 1);
+        let q = Point::new(7, 1);
+        assert!(Point::equals(&p, &q), 101);
+        let r = Point::shift(q, 1);
+        assert!(!Point::equals(&p, &r), 102);
+        let (x, _) = Point::coords(p);
+        let (y, _) = Point::coords(r);
+        assert!(x + 1 == y, 103);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+//! mods: vector.move
+//! args: 3u8
+script {
+    use 0x1::Vector;
+    fun main(x: u8) {
+        let v = Vector::empty<u8>();
+        Vector::push_back(&mut v, x);
+        Vector::push_back(&mut v, 5);
+        assert!(Vector::length(&v) == 2, 101);
+        Vector::swap(&mut v, 0, 1);
+        assert!(*Vector::borrow(&v, 0) == 5, 102);
+        assert!(Vector::pop_back(&mut v) == x, 103);
+        assert!(Vector::length(&v) == 1, 104);
+        let e = Vector::empty<vector<u8>>();
+        if (x > 10) {
+            let w = Vector::pop_back(&mut e);
+            assert!(Vector::length(&w) == 0, 105);
+        }
+    }
+}
\ No newline at end of file
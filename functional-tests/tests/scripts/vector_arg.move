@@ -0,0 +1,12 @@
+//! mods: vector.move
+//! args: x"030507"
+script {
+    use 0x1::Vector;
+    fun main(v: vector<u8>) {
+        assert!(Vector::length(&v) == 3, 101);
+        assert!(*Vector::borrow(&v, 0) == 3, 102);
+        assert!(*Vector::borrow(&v, 2) == 7, 103);
+        assert!(Vector::pop_back(&mut v) == 7, 104);
+        assert!(Vector::length(&v) == 2, 105);
+    }
+}
\ No newline at end of file
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::Result;
+use halo2_proofs::dev::MockProver;
 use halo2_proofs::pasta::{EqAffine, Fp};
 use halo2_proofs::poly::commitment::Params;
 use logger::prelude::*;
@@ -10,7 +11,9 @@ use movelang::{argument::ScriptArguments, compiler::compile_script};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use vm::runtime::Runtime;
+use std::time::Instant;
+use vm::proof::MoveProof;
+use vm::runtime::{Runtime, MIN_K};
 
 pub const TEST_MODULE_PATH: &str = "tests/modules";
 
@@ -18,12 +21,20 @@ pub const TEST_MODULE_PATH: &str = "tests/modules";
 struct RunConfig {
     args: Option<ScriptArguments>,
     modules: Vec<String>,
+    public_args: Vec<usize>,
+    outputs: Vec<usize>,
+    error: Option<String>,
+    lanes: usize,
 }
 
 fn parse_config(script_file: &Path) -> Result<RunConfig> {
     let mut config = RunConfig {
         args: None,
         modules: vec![],
+        public_args: vec![],
+        outputs: vec![],
+        error: None,
+        lanes: 1,
     };
     let file_str = script_file.to_str().expect("path is None.");
 
@@ -40,6 +51,24 @@ fn parse_config(script_file: &Path) -> Result<RunConfig> {
         if let Some(s) = s.strip_prefix("//!mods:") {
             config.modules.push(s.to_string()); //todo: support multiple modules
         }
+        if let Some(s) = s.strip_prefix("//!public-args:") {
+            config.public_args = s
+                .split(',')
+                .map(|arg| arg.parse::<usize>())
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(s) = s.strip_prefix("//!error:") {
+            config.error = Some(s.to_string());
+        }
+        if let Some(s) = s.strip_prefix("//!lanes:") {
+            config.lanes = s.parse::<usize>()?;
+        }
+        if let Some(s) = s.strip_prefix("//!outputs:") {
+            config.outputs = s
+                .split(',')
+                .map(|local| local.parse::<usize>())
+                .collect::<Result<_, _>>()?;
+        }
     }
     Ok(config)
 }
@@ -65,7 +94,7 @@ fn vm_test(path: &Path) -> datatest_stable::Result<()> {
         config.args, targets
     );
 
-    let (compiled_script, compiled_modules) = compile_script(targets)?;
+    let (compiled_script, compiled_modules, _) = compile_script(targets)?;
     let script = compiled_script.expect("script is missing");
     let runtime = Runtime::<Fp>::new();
     let mut state = StateStore::new();
@@ -74,18 +103,52 @@ fn vm_test(path: &Path) -> datatest_stable::Result<()> {
         state.add_module(module);
     }
 
-    let move_circuit =
+    let mut move_circuit =
         runtime.create_move_circuit(script, compiled_modules, config.args, state.clone());
-    let public_inputs = vec![Fp::zero()];
+    move_circuit.set_public_args(config.public_args);
+    move_circuit.set_public_outputs(config.outputs);
+    move_circuit.set_lanes(config.lanes);
+    // the dry run has the semantics of the circuit, it must not abort either
+    let dry_run_start = Instant::now();
+    let result = runtime.dry_run(&move_circuit);
+    let dry_run_time = dry_run_start.elapsed();
+    if let Some(error) = config.error {
+        let status = format!("{:?}", result.expect_err("the script runs").status_code());
+        assert_eq!(status, error);
+        return Ok(());
+    }
+    let result = result?;
+    debug!("dry run {:?}", result);
+    assert!(result.abort.is_none());
+
     debug!("Find the best suitable k for the circuit...");
-    let k = runtime.find_best_k(&move_circuit, vec![public_inputs.clone()])?;
+    let k = runtime.find_best_k(&move_circuit)?;
     info!("use move circuit, k = {}", k);
+    let public_inputs = runtime.public_inputs(&move_circuit)?;
 
     debug!(
         "Generate zk proof for script {:?} with mock prover",
         script_file
     );
+    let mock_start = Instant::now();
     runtime.mock_prove_circuit(&move_circuit, vec![public_inputs.clone()], k)?;
+    // the dry run only follows the path the script takes, nothing is laid
+    // out
+    assert!(dry_run_time < mock_start.elapsed());
+
+    // k is the smallest size that fits, the circuit does not fit in half the
+    // rows. The byte table alone needs k = 9, so this checks the row count of
+    // the instructions for the scripts which need more, like the ones with
+    // global resources.
+    if k > MIN_K {
+        let fits = vm::with_lanes!(move_circuit.lanes(), LANES => {
+            let circuit = move_circuit.on_lanes::<LANES>();
+            Ok(MockProver::run(k - 1, &circuit, vec![public_inputs.clone()])
+                .map(|prover| prover.verify().is_ok())
+                .unwrap_or(false))
+        })?;
+        assert!(!fits, "the circuit fits in k = {}", k - 1);
+    }
 
     let params: Params<EqAffine> = Params::new(k);
     let pk = runtime.setup_move_circuit(&move_circuit, &params)?;
@@ -94,7 +157,13 @@ fn vm_test(path: &Path) -> datatest_stable::Result<()> {
         "Generate zk proof for script {:?} with real prover",
         script_file
     );
-    runtime.prove_move_circuit(move_circuit, &[public_inputs.as_slice()], &params, pk)?;
+    let proof = runtime.prove_move_circuit(move_circuit, &public_inputs, &params, &pk)?;
+
+    // verify the proof as read back from its file format
+    let mut bytes = vec![];
+    proof.write(&mut bytes)?;
+    let proof = MoveProof::read(&mut bytes.as_slice())?;
+    runtime.verify_move_proof(&params, pk.get_vk(), &public_inputs, &proof)?;
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::value::address_to_field;
 use anyhow::{Error, Result};
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::arithmetic::FieldExt;
@@ -37,6 +38,15 @@ pub fn convert_from<F: FieldExt>(arg: ScriptArgument) -> VmResult<F> {
         ScriptArgument::U64(v) => Ok(F::from_u128(v as u128)),
         ScriptArgument::U128(v) => Ok(F::from_u128(v)),
         ScriptArgument::Bool(v) => Ok(if v { F::one() } else { F::zero() }),
+        ScriptArgument::Address(v) => Ok(address_to_field(v)),
+        _ => Err(RuntimeError::new(StatusCode::UnsupportedMoveType)),
+    }
+}
+
+/// The bytes of a `vector<u8>` argument.
+pub fn convert_vector_from<F: FieldExt>(arg: ScriptArgument) -> VmResult<Vec<F>> {
+    match arg {
+        ScriptArgument::U8Vector(v) => Ok(v.into_iter().map(|b| F::from_u128(b as u128)).collect()),
         _ => Err(RuntimeError::new(StatusCode::UnsupportedMoveType)),
     }
 }
@@ -2,34 +2,110 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{bail, Result};
-use move_binary_format::file_format::CompiledScript;
+use move_binary_format::access::ModuleAccess;
+use move_binary_format::file_format::{CompiledScript, FunctionDefinitionIndex};
 use move_binary_format::CompiledModule;
 use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule, NamedCompiledScript};
 use move_compiler::shared::NumericalAddress;
 use move_compiler::{self, Compiler, Flags};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+/// A line of a source file.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: u32,
+}
+
+/// The source line of every instruction, by function name and pc. The
+/// runtime only knows the name of a function, so functions of different
+/// modules sharing a name are left out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceLines {
+    pub functions: BTreeMap<String, Vec<Option<SourceLine>>>,
+}
+
+impl SourceLines {
+    pub fn get(&self, function: &str, pc: u16) -> Option<&SourceLine> {
+        self.functions.get(function)?.get(pc as usize)?.as_ref()
+    }
+}
 
 pub fn compile_script(
     targets: Vec<String>,
-) -> Result<(Option<CompiledScript>, Vec<CompiledModule>)> {
-    let (_, compiled_units) =
+) -> Result<(Option<CompiledScript>, Vec<CompiledModule>, SourceLines)> {
+    let (files, compiled_units) =
         Compiler::from_files(targets, vec![], BTreeMap::<String, NumericalAddress>::new())
             .set_flags(Flags::empty().set_sources_shadow_deps(false))
             .build_and_report()?;
 
     let mut compiled_script = None;
     let mut modules = vec![];
+    let mut functions = vec![];
     for c in compiled_units {
         match c.into_compiled_unit() {
-            CompiledUnit::Script(NamedCompiledScript { script, .. }) => {
+            CompiledUnit::Script(NamedCompiledScript {
+                script, source_map, ..
+            }) => {
                 if compiled_script.is_some() {
                     bail!("found more than one script.")
                 }
+                let lines = (0..script.code.code.len())
+                    .map(|pc| {
+                        let loc = source_map
+                            .get_code_location(FunctionDefinitionIndex(0), pc as u16)
+                            .ok()?;
+                        source_line(files.get(&loc.file_hash()), loc.start())
+                    })
+                    .collect();
+                // the runtime names the function of a script main
+                functions.push(("main".to_string(), lines));
                 compiled_script = Some(script)
             }
-            CompiledUnit::Module(NamedCompiledModule { module, .. }) => modules.push(module),
+            CompiledUnit::Module(NamedCompiledModule {
+                module, source_map, ..
+            }) => {
+                for (index, def) in module.function_defs().iter().enumerate() {
+                    let handle = module.function_handle_at(def.function);
+                    let name = module.identifier_at(handle.name).to_string();
+                    let len = def.code.as_ref().map_or(0, |code| code.code.len());
+                    let lines = (0..len)
+                        .map(|pc| {
+                            let loc = source_map
+                                .get_code_location(FunctionDefinitionIndex(index as u16), pc as u16)
+                                .ok()?;
+                            source_line(files.get(&loc.file_hash()), loc.start())
+                        })
+                        .collect();
+                    functions.push((name, lines));
+                }
+                modules.push(module)
+            }
+        }
+    }
+
+    let mut source_lines = SourceLines::default();
+    let mut shared = BTreeSet::new();
+    for (name, lines) in functions {
+        if source_lines.functions.insert(name.clone(), lines).is_some() {
+            shared.insert(name);
         }
     }
+    for name in shared {
+        source_lines.functions.remove(&name);
+    }
+
+    Ok((compiled_script, modules, source_lines))
+}
 
-    Ok((compiled_script, modules))
+/// The line of the byte at `start` of a source file, the file given by its
+/// name and text.
+fn source_line<N: Display>(file: Option<&(N, String)>, start: u32) -> Option<SourceLine> {
+    let (name, text) = file?;
+    let line = text.get(..start as usize)?.matches('\n').count() + 1;
+    Some(SourceLine {
+        file: name.to_string(),
+        line: line as u32,
+    })
 }
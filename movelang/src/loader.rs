@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: Apache-2.0
 
+use error::{RuntimeError, StatusCode, VmResult};
 use move_binary_format::errors::VMResult;
 use move_binary_format::file_format::FunctionHandleIndex;
-use move_vm_runtime::loader::{Function, Loader};
+use move_core_types::language_storage::{StructTag, TypeTag};
+use move_vm_runtime::loader::{Function, Loader, Resolver};
 use move_vm_runtime::native_functions::NativeFunctions;
 use move_vm_runtime::session::LoadedFunctionInstantiation;
 use move_vm_types::data_store::DataStore;
@@ -44,8 +46,45 @@ impl MoveLoader {
         caller: &Arc<Function>,
         callee_idx: FunctionHandleIndex,
     ) -> Arc<Function> {
-        let resolver = caller.get_resolver(&self.loader);
-        resolver.function_from_handle(callee_idx)
+        self.resolver(caller).function_from_handle(callee_idx)
+    }
+
+    pub fn resolver(&self, func: &Arc<Function>) -> Resolver<'_> {
+        func.get_resolver(&self.loader)
+    }
+
+    /// The tag a resource of struct type `ty` is stored under.
+    pub fn struct_tag(&self, ty: &Type) -> VmResult<StructTag> {
+        match self.loader.type_to_type_tag(ty) {
+            Ok(TypeTag::Struct(tag)) => Ok(tag),
+            _ => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a struct type, found {:?}", ty))),
+        }
+    }
+
+    /// The types of the fields of struct type `ty`, in declaration order.
+    pub fn struct_field_types(&self, ty: &Type) -> VmResult<Vec<Type>> {
+        let (index, type_args) = match ty {
+            Type::Struct(index) => (index, &[][..]),
+            Type::StructInstantiation(index, type_args) => (index, type_args.as_slice()),
+            _ => {
+                return Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                    .with_message(format!("expect a struct type, found {:?}", ty)))
+            }
+        };
+        let struct_type = self
+            .loader
+            .get_struct_type(*index)
+            .ok_or_else(|| RuntimeError::new(StatusCode::ScriptLoadingError))?;
+        struct_type
+            .fields
+            .iter()
+            .map(|field| {
+                field.subst(type_args).map_err(|e| {
+                    RuntimeError::new(StatusCode::TypeMissMatch).with_message(format!("{:?}", e))
+                })
+            })
+            .collect()
     }
 }
 
@@ -5,7 +5,10 @@ use move_binary_format::errors::{Location, PartialVMError};
 use move_binary_format::errors::{PartialVMResult, VMResult};
 use move_binary_format::CompiledModule;
 use move_core_types::{
-    account_address::AccountAddress, language_storage::ModuleId, vm_status::StatusCode,
+    account_address::AccountAddress,
+    language_storage::{ModuleId, StructTag},
+    value::MoveValue,
+    vm_status::StatusCode,
 };
 use move_vm_types::{
     loaded_data::runtime_types::Type,
@@ -21,6 +24,7 @@ use std::cell::RefCell;
 pub struct StateStore {
     modules: RefCell<HashMap<ModuleId, Vec<u8>>>,
     module_table: RefCell<Vec<ModuleId>>,
+    resources: RefCell<HashMap<(AccountAddress, StructTag), MoveValue>>,
 }
 
 impl StateStore {
@@ -28,6 +32,7 @@ impl StateStore {
         Self {
             modules: RefCell::new(HashMap::new()),
             module_table: RefCell::new(Vec::new()),
+            resources: RefCell::new(HashMap::new()),
         }
     }
 
@@ -50,6 +55,29 @@ impl StateStore {
         }
         module_index
     }
+
+    /// Publish a resource under `addr`, replacing the one already there.
+    pub fn add_resource(&mut self, addr: AccountAddress, tag: StructTag, value: MoveValue) {
+        self.resources.borrow_mut().insert((addr, tag), value);
+    }
+
+    /// Unpublish the resource under `addr`, returns it if there was one.
+    pub fn remove_resource(&mut self, addr: AccountAddress, tag: &StructTag) -> Option<MoveValue> {
+        self.resources.borrow_mut().remove(&(addr, tag.clone()))
+    }
+
+    pub fn resource(&self, addr: AccountAddress, tag: &StructTag) -> Option<MoveValue> {
+        self.resources.borrow().get(&(addr, tag.clone())).cloned()
+    }
+
+    /// All published resources, in no particular order.
+    pub fn resources(&self) -> Vec<(AccountAddress, StructTag, MoveValue)> {
+        self.resources
+            .borrow()
+            .iter()
+            .map(|((addr, tag), value)| (*addr, tag.clone(), value.clone()))
+            .collect()
+    }
 }
 
 impl Default for StateStore {
@@ -64,7 +92,10 @@ impl DataStore for StateStore {
         _addr: AccountAddress,
         _ty: &Type,
     ) -> PartialVMResult<&mut GlobalValue> {
-        unimplemented!()
+        // the circuit reads resources as move values through `resource`, they
+        // are never turned into runtime values
+        Err(PartialVMError::new(StatusCode::MISSING_DATA)
+            .with_message("resources are not loaded as global values".to_string()))
     }
 
     fn load_module(&self, module_id: &ModuleId) -> VMResult<Vec<u8>> {
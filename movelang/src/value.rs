@@ -3,9 +3,11 @@
 
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::arithmetic::FieldExt;
+use move_core_types::account_address::AccountAddress;
 pub use move_core_types::value::MoveValue;
-use move_core_types::value::MoveValue::{Bool, U128, U64, U8};
+use move_core_types::value::MoveValue::{Address, Bool, Signer, U128, U64, U8};
 pub use move_vm_types::loaded_data::runtime_types::Type as MoveValueType;
+use std::convert::TryFrom;
 
 pub fn convert_to_field<F: FieldExt>(value: MoveValue) -> F {
     match value {
@@ -19,10 +21,56 @@ pub fn convert_to_field<F: FieldExt>(value: MoveValue) -> F {
                 F::zero()
             }
         }
+        Address(a) | Signer(a) => address_to_field(a),
         _ => unimplemented!(),
     }
 }
 
+/// An address is a 16 bytes big endian number, it fits in a field element.
+pub fn address_to_field<F: FieldExt>(address: AccountAddress) -> F {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&address.to_vec());
+    F::from_u128(u128::from_be_bytes(bytes))
+}
+
+pub fn field_to_address<F: FieldExt>(field: F) -> VmResult<AccountAddress> {
+    AccountAddress::from_bytes(field.get_lower_128().to_be_bytes()).map_err(|e| {
+        RuntimeError::new(StatusCode::ValueConversionError).with_message(e.to_string())
+    })
+}
+
+macro_rules! overflowing_op {
+    ($name:ident, $method:ident, $verb:expr) => {
+        /// Returns the result wrapped around the bit width of the type, and
+        /// whether the operation overflows.
+        pub fn $name(left: MoveValue, right: MoveValue) -> VmResult<(MoveValue, bool)> {
+            let result = match (left, right) {
+                (U8(l), U8(r)) => {
+                    let (v, overflow) = u8::$method(l, r);
+                    (U8(v), overflow)
+                }
+                (U64(l), U64(r)) => {
+                    let (v, overflow) = u64::$method(l, r);
+                    (U64(v), overflow)
+                }
+                (U128(l), U128(r)) => {
+                    let (v, overflow) = u128::$method(l, r);
+                    (U128(v), overflow)
+                }
+                (l, r) => {
+                    let msg = format!("can not {} {:?} and {:?}", $verb, l, r);
+                    return Err(RuntimeError::new(StatusCode::TypeMissMatch).with_message(msg));
+                }
+            };
+            Ok(result)
+        }
+    };
+}
+
+overflowing_op!(move_overflowing_add, overflowing_add, "add");
+overflowing_op!(move_overflowing_sub, overflowing_sub, "sub");
+overflowing_op!(move_overflowing_mul, overflowing_mul, "mul");
+
 pub fn move_div(left: MoveValue, right: MoveValue) -> VmResult<MoveValue> {
     let result = match (left, right) {
         (U8(l), U8(r)) => u8::checked_div(l, r).map(U8),
@@ -49,12 +97,67 @@ pub fn move_rem(left: MoveValue, right: MoveValue) -> VmResult<MoveValue> {
     result.ok_or_else(|| RuntimeError::new(StatusCode::ArithmeticError))
 }
 
+pub fn move_shl(left: MoveValue, right: MoveValue) -> VmResult<MoveValue> {
+    let result = match (left, right) {
+        (U8(l), U8(r)) => u8::checked_shl(l, r as u32).map(U8),
+        (U64(l), U8(r)) => u64::checked_shl(l, r as u32).map(U64),
+        (U128(l), U8(r)) => u128::checked_shl(l, r as u32).map(U128),
+        (l, r) => {
+            let msg = format!("can not shift {:?} by {:?}", l, r);
+            return Err(RuntimeError::new(StatusCode::TypeMissMatch).with_message(msg));
+        }
+    };
+    result.ok_or_else(|| RuntimeError::new(StatusCode::ArithmeticError))
+}
+
+pub fn move_shr(left: MoveValue, right: MoveValue) -> VmResult<MoveValue> {
+    let result = match (left, right) {
+        (U8(l), U8(r)) => u8::checked_shr(l, r as u32).map(U8),
+        (U64(l), U8(r)) => u64::checked_shr(l, r as u32).map(U64),
+        (U128(l), U8(r)) => u128::checked_shr(l, r as u32).map(U128),
+        (l, r) => {
+            let msg = format!("can not shift {:?} by {:?}", l, r);
+            return Err(RuntimeError::new(StatusCode::TypeMissMatch).with_message(msg));
+        }
+    };
+    result.ok_or_else(|| RuntimeError::new(StatusCode::ArithmeticError))
+}
+
+pub fn move_cast(value: MoveValue, ty: MoveValueType) -> VmResult<MoveValue> {
+    let v = match value {
+        U8(v) => v as u128,
+        U64(v) => v as u128,
+        U128(v) => v,
+        v => {
+            let msg = format!("can not cast {:?} to {:?}", v, ty);
+            return Err(RuntimeError::new(StatusCode::TypeMissMatch).with_message(msg));
+        }
+    };
+    let result = match ty {
+        MoveValueType::U8 => u8::try_from(v).ok().map(U8),
+        MoveValueType::U64 => u64::try_from(v).ok().map(U64),
+        MoveValueType::U128 => Some(U128(v)),
+        ty => {
+            let msg = format!("can not cast {:?} to {:?}", v, ty);
+            return Err(RuntimeError::new(StatusCode::TypeMissMatch).with_message(msg));
+        }
+    };
+    result.ok_or_else(|| {
+        RuntimeError::new(StatusCode::ArithmeticError)
+            .with_message(format!("can not cast {} to {:?}", v, ty))
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::value::convert_to_field;
+    use crate::value::{
+        convert_to_field, field_to_address, move_cast, move_overflowing_add, move_overflowing_mul,
+        move_overflowing_sub, MoveValueType,
+    };
     use halo2_proofs::arithmetic::FieldExt;
     use halo2_proofs::pasta::Fp;
-    use move_core_types::value::MoveValue::{Bool, U128, U64, U8};
+    use move_core_types::account_address::AccountAddress;
+    use move_core_types::value::MoveValue::{Address, Bool, U128, U64, U8};
 
     #[test]
     fn test_conversion() {
@@ -81,5 +184,57 @@ mod tests {
             convert_to_field::<Fp>(U128(0x1111111111111111u128)),
             Fp::from_u128(0x1111111111111111u128)
         );
+
+        let mut bytes = [0u8; AccountAddress::LENGTH];
+        bytes[AccountAddress::LENGTH - 2..].copy_from_slice(&[0x12, 0x34]);
+        let address = AccountAddress::new(bytes);
+        assert_eq!(
+            convert_to_field::<Fp>(Address(address)),
+            Fp::from_u128(0x1234u128)
+        );
+        assert_eq!(
+            field_to_address(Fp::from_u128(0x1234u128)).unwrap(),
+            address
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(move_cast(U8(255), MoveValueType::U64).unwrap(), U64(255));
+        assert_eq!(move_cast(U8(255), MoveValueType::U128).unwrap(), U128(255));
+        assert_eq!(move_cast(U64(255), MoveValueType::U8).unwrap(), U8(255));
+        assert_eq!(
+            move_cast(U128(u64::MAX as u128), MoveValueType::U64).unwrap(),
+            U64(u64::MAX)
+        );
+        assert!(move_cast(U64(256), MoveValueType::U8).is_err());
+        assert!(move_cast(U128(u64::MAX as u128 + 1), MoveValueType::U64).is_err());
+        assert!(move_cast(Bool(true), MoveValueType::U8).is_err());
+    }
+
+    #[test]
+    fn test_overflowing() {
+        assert_eq!(
+            move_overflowing_add(U8(1), U8(254)).unwrap(),
+            (U8(255), false)
+        );
+        assert_eq!(move_overflowing_add(U8(1), U8(255)).unwrap(), (U8(0), true));
+        assert_eq!(
+            move_overflowing_sub(U64(2), U64(1)).unwrap(),
+            (U64(1), false)
+        );
+        assert_eq!(
+            move_overflowing_sub(U64(1), U64(2)).unwrap(),
+            (U64(u64::MAX), true)
+        );
+        assert_eq!(
+            move_overflowing_mul(U128(1 << 64), U128(1 << 63)).unwrap(),
+            (U128(1 << 127), false)
+        );
+        assert_eq!(
+            move_overflowing_mul(U128(1 << 64), U128(1 << 64)).unwrap(),
+            (U128(0), true)
+        );
+        assert!(move_overflowing_add(U8(1), U64(1)).is_err());
     }
 }
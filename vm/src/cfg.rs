@@ -0,0 +1,424 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Static control flow analysis of a function body.
+//!
+//! Conditional branches are executed by running both arms under their
+//! conditions and merging the locals where the arms join again, so the frame
+//! needs to know the join point of every branch before it is executed. The
+//! join point of a branch is the immediate post-dominator of its basic block.
+//! Paths ending with `Abort` never join, they are left out when computing
+//! post-dominators. Loops are found through back edges, an edge whose target
+//! dominates its source.
+
+use error::{RuntimeError, StatusCode, VmResult};
+use move_binary_format::file_format::Bytecode;
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    pub end: u16, // pc of the last instruction, inclusive
+    pub successors: Vec<usize>,
+}
+
+/// A loop entered through `header` and left through the conditional branch at
+/// `branch` only. Each iteration runs from `header` to `branch`, then from
+/// `body_start` back to `header`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loop {
+    pub header: u16,
+    pub branch: u16,
+    pub body_start: u16,
+    pub exit: u16,
+    pub continue_on_true: bool, // the loop continues if the branch condition is true
+}
+
+#[derive(Clone, Debug)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    block_of: Vec<usize>, // pc -> index of the basic block containing it
+    dominators: Vec<Option<usize>>, // immediate dominators, None if unreachable
+    post_dominators: Vec<Option<usize>>, // immediate post-dominators, `blocks.len()` is the exit
+    loops: Vec<Loop>,
+}
+
+/// The targets of a conditional branch, (if true, if false).
+pub fn branch_targets(code: &[Bytecode], pc: u16) -> Option<(u16, u16)> {
+    match &code[pc as usize] {
+        Bytecode::BrTrue(offset) => Some((*offset, pc + 1)),
+        Bytecode::BrFalse(offset) => Some((pc + 1, *offset)),
+        _ => None,
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn new(code: &[Bytecode]) -> VmResult<Self> {
+        let blocks = Self::basic_blocks(code);
+        let mut block_of = vec![0; code.len()];
+        for (index, block) in blocks.iter().enumerate() {
+            for pc in block.start..=block.end {
+                block_of[pc as usize] = index;
+            }
+        }
+
+        let exit = blocks.len();
+        let mut successors: Vec<Vec<usize>> = blocks.iter().map(|b| b.successors.clone()).collect();
+        successors.push(vec![]);
+        let mut predecessors = vec![vec![]; exit + 1];
+        for (index, block) in blocks.iter().enumerate() {
+            for s in block.successors.iter() {
+                predecessors[*s].push(index);
+            }
+            if let Bytecode::Ret = code[block.end as usize] {
+                successors[index].push(exit);
+                predecessors[exit].push(index);
+            }
+        }
+        let mut dominators = immediate_dominators(0, &successors, &predecessors);
+        dominators.truncate(exit);
+        let post_dominators = immediate_dominators(exit, &predecessors, &successors);
+
+        let mut cfg = ControlFlowGraph {
+            blocks,
+            block_of,
+            dominators,
+            post_dominators,
+            loops: vec![],
+        };
+        cfg.loops = cfg.find_loops(code)?;
+        Ok(cfg)
+    }
+
+    fn basic_blocks(code: &[Bytecode]) -> Vec<BasicBlock> {
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0u16);
+        for (pc, instruction) in code.iter().enumerate() {
+            let pc = pc as u16;
+            match instruction {
+                Bytecode::BrTrue(offset) | Bytecode::BrFalse(offset) | Bytecode::Branch(offset) => {
+                    leaders.insert(*offset);
+                    leaders.insert(pc + 1);
+                }
+                Bytecode::Ret | Bytecode::Abort => {
+                    leaders.insert(pc + 1);
+                }
+                _ => {}
+            }
+        }
+        let leaders: Vec<u16> = leaders
+            .into_iter()
+            .filter(|pc| (*pc as usize) < code.len())
+            .collect();
+        let index_of = |pc: u16| leaders.binary_search(&pc).expect("target is a leader");
+
+        let mut blocks = vec![];
+        for (index, start) in leaders.iter().enumerate() {
+            let end = leaders
+                .get(index + 1)
+                .map(|next| next - 1)
+                .unwrap_or(code.len() as u16 - 1);
+            let successors = match &code[end as usize] {
+                Bytecode::BrTrue(offset) | Bytecode::BrFalse(offset) => {
+                    vec![index_of(*offset), index_of(end + 1)]
+                }
+                Bytecode::Branch(offset) => vec![index_of(*offset)],
+                Bytecode::Ret | Bytecode::Abort => vec![],
+                _ => vec![index_of(end + 1)],
+            };
+            blocks.push(BasicBlock {
+                start: *start,
+                end,
+                successors,
+            });
+        }
+        blocks
+    }
+
+    fn find_loops(&self, code: &[Bytecode]) -> VmResult<Vec<Loop>> {
+        let mut loops = vec![];
+        for header in 0..self.blocks.len() {
+            let latches: Vec<usize> = (0..self.blocks.len())
+                .filter(|b| self.blocks[*b].successors.contains(&header))
+                .filter(|b| self.dominates(header, *b))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            let header_pc = self.blocks[header].start;
+            let error = |message: &str| {
+                Err(RuntimeError::new(StatusCode::ProgramBlockError)
+                    .with_message(format!("loop at {}: {}", header_pc, message)))
+            };
+
+            // the natural loop, blocks reaching a latch without passing the header
+            let mut body = BTreeSet::new();
+            body.insert(header);
+            let mut work = latches.clone();
+            while let Some(b) = work.pop() {
+                if body.insert(b) {
+                    work.extend(
+                        (0..self.blocks.len()).filter(|p| self.blocks[*p].successors.contains(&b)),
+                    );
+                }
+            }
+            // edges leaving the loop, except those which can only abort
+            let exits: Vec<(usize, usize)> = body
+                .iter()
+                .flat_map(|b| self.blocks[*b].successors.iter().map(move |s| (*b, *s)))
+                .filter(|(_, s)| !body.contains(s) && self.reaches_exit(*s))
+                .collect();
+            let (exiting, exit) = match exits.as_slice() {
+                [edge] => *edge,
+                [] => return error("never exits"),
+                _ => return error("more than one exit, break or return in loops is not supported"),
+            };
+            if latches.iter().any(|l| !self.dominates(exiting, *l)) {
+                return error("the exiting branch doesn't run in every iteration");
+            }
+            let branch = self.blocks[exiting].end;
+            let (true_target, false_target) = match branch_targets(code, branch) {
+                Some(targets) => targets,
+                None => return error("expect a conditional branch at the exit"),
+            };
+            let exit = self.blocks[exit].start;
+            loops.push(Loop {
+                header: header_pc,
+                branch,
+                body_start: if true_target == exit {
+                    false_target
+                } else {
+                    true_target
+                },
+                exit,
+                continue_on_true: true_target != exit,
+            });
+        }
+
+        // any other backward jump makes the flow irreducible
+        for (index, block) in self.blocks.iter().enumerate() {
+            for s in block.successors.iter() {
+                if *s <= index && !self.dominates(*s, index) {
+                    return Err(
+                        RuntimeError::new(StatusCode::ProgramBlockError).with_message(format!(
+                            "unsupported backward branch from {} to {}",
+                            block.end, self.blocks[*s].start
+                        )),
+                    );
+                }
+            }
+        }
+        Ok(loops)
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block_at(&self, pc: u16) -> usize {
+        self.block_of[pc as usize]
+    }
+
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.dominators[block].filter(|d| *d != block)
+    }
+
+    /// Whether every path from the entry to `b` passes `a`.
+    pub fn dominates(&self, a: usize, mut b: usize) -> bool {
+        if self.dominators[b].is_none() {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.immediate_dominator(b) {
+                Some(d) => b = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// Whether some path from `block` returns from the function.
+    pub fn reaches_exit(&self, block: usize) -> bool {
+        self.post_dominators[block].is_some()
+    }
+
+    /// The pc where the arms of the conditional branch at `pc` join again.
+    /// None if they don't join before leaving the function.
+    pub fn merge_point(&self, pc: u16) -> Option<u16> {
+        let block = self.block_at(pc);
+        self.post_dominators[block]
+            .and_then(|p| self.blocks.get(p))
+            .map(|b| b.start)
+    }
+
+    /// The loop left through the conditional branch at `pc`.
+    pub fn loop_at(&self, pc: u16) -> Option<&Loop> {
+        self.loops.iter().find(|l| l.branch == pc)
+    }
+}
+
+/// Immediate dominators of a graph, "A Simple, Fast Dominance Algorithm" by
+/// Cooper, Harvey and Kennedy. Nodes unreachable from the entry get None.
+fn immediate_dominators(
+    entry: usize,
+    successors: &[Vec<usize>],
+    predecessors: &[Vec<usize>],
+) -> Vec<Option<usize>> {
+    // reverse post order
+    let mut order = vec![];
+    let mut visited = vec![false; successors.len()];
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((node, next)) = stack.pop() {
+        if let Some(s) = successors[node].get(next) {
+            stack.push((node, next + 1));
+            if !visited[*s] {
+                visited[*s] = true;
+                stack.push((*s, 0));
+            }
+        } else {
+            order.push(node);
+        }
+    }
+    order.reverse();
+    let mut rank = vec![usize::MAX; successors.len()];
+    for (i, node) in order.iter().enumerate() {
+        rank[*node] = i;
+    }
+
+    let mut idom: Vec<Option<usize>> = vec![None; successors.len()];
+    idom[entry] = Some(entry);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in order.iter().skip(1) {
+            let mut new_idom: Option<usize> = None;
+            for p in predecessors[*node].iter() {
+                if idom[*p].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => *p,
+                    Some(mut a) => {
+                        let mut b = *p;
+                        while a != b {
+                            while rank[a] > rank[b] {
+                                a = idom[a].unwrap();
+                            }
+                            while rank[b] > rank[a] {
+                                b = idom[b].unwrap();
+                            }
+                        }
+                        a
+                    }
+                });
+            }
+            if new_idom.is_some() && idom[*node] != new_idom {
+                idom[*node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_binary_format::file_format::Bytecode::*;
+
+    #[test]
+    fn test_merge_point() {
+        // if (a) { if (b) x else y } else z; assert!(..)
+        let code = vec![
+            CopyLoc(0), // 0
+            BrTrue(3),  // 1
+            Branch(10), // 2
+            CopyLoc(1), // 3
+            BrTrue(6),  // 4
+            Branch(8),  // 5
+            LdU8(1),    // 6
+            Branch(11), // 7
+            LdU8(2),    // 8
+            Branch(11), // 9
+            LdU8(3),    // 10
+            StLoc(2),   // 11
+            CopyLoc(0), // 12
+            BrTrue(16), // 13
+            LdU64(101), // 14
+            Abort,      // 15
+            Ret,        // 16
+        ];
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        assert_eq!(cfg.merge_point(1), Some(11));
+        assert_eq!(cfg.merge_point(4), Some(11));
+        // the abort path is ignored
+        assert_eq!(cfg.merge_point(13), Some(16));
+        assert!(!cfg.reaches_exit(cfg.block_at(14)));
+        assert!(cfg.dominates(cfg.block_at(3), cfg.block_at(8)));
+        assert!(!cfg.dominates(cfg.block_at(3), cfg.block_at(11)));
+    }
+
+    #[test]
+    fn test_early_return() {
+        // if (a) return 1; 2
+        let code = vec![CopyLoc(0), BrFalse(4), LdU8(1), Ret, LdU8(2), Ret];
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        assert_eq!(cfg.merge_point(1), None);
+        assert!(cfg.loop_at(1).is_none());
+    }
+
+    #[test]
+    fn test_loops() {
+        // while (i < n) { while (j < i) { .. } }
+        let code = vec![
+            CopyLoc(1),  // 0
+            CopyLoc(0),  // 1
+            Lt,          // 2
+            BrTrue(5),   // 3
+            Branch(12),  // 4
+            CopyLoc(2),  // 5
+            CopyLoc(1),  // 6
+            Lt,          // 7
+            BrFalse(11), // 8
+            LdU8(1),     // 9
+            Branch(5),   // 10
+            Branch(0),   // 11
+            Ret,         // 12
+        ];
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        assert_eq!(
+            cfg.loop_at(3),
+            Some(&Loop {
+                header: 0,
+                branch: 3,
+                body_start: 5,
+                exit: 4,
+                continue_on_true: true,
+            })
+        );
+        assert_eq!(
+            cfg.loop_at(8),
+            Some(&Loop {
+                header: 5,
+                branch: 8,
+                body_start: 9,
+                exit: 11,
+                continue_on_true: true,
+            })
+        );
+
+        // while (c) { if (d) break; }
+        let code = vec![
+            CopyLoc(0), // 0
+            BrFalse(5), // 1
+            CopyLoc(1), // 2
+            BrTrue(5),  // 3
+            Branch(0),  // 4
+            Ret,        // 5
+        ];
+        assert!(ControlFlowGraph::new(&code).is_err());
+    }
+}
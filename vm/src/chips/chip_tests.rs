@@ -4,8 +4,11 @@
 use crate::chips::evaluation_chip::{EvaluationChip, EvaluationConfig, NUM_OF_ADVICE_COLUMNS};
 use crate::chips::instructions::Opcode;
 use crate::chips::utilities::{
-    RangeCheckChip, RangeCheckConfig, NUM_OF_BYTES_U128, NUM_OF_BYTES_U64, NUM_OF_BYTES_U8,
+    ByteTableConfig, RangeCheckChip, RangeCheckConfig, NUM_OF_BYTES_U128, NUM_OF_BYTES_U64,
+    NUM_OF_BYTES_U8,
 };
+use crate::circuit::MoveCircuit;
+use crate::state_tree::TREE_DEPTH;
 use crate::value::Value;
 use halo2_proofs::poly::Rotation;
 use halo2_proofs::{
@@ -16,6 +19,65 @@ use halo2_proofs::{
 use logger::prelude::*;
 use movelang::value::MoveValueType;
 
+/// A test running on the evaluation chip, `EvaluationTestCircuit` gives it
+/// the columns of a script circuit on `LANES` lanes and loads the byte table
+/// before it runs.
+trait EvaluationTest<F: FieldExt> {
+    const LANES: usize = 1;
+
+    fn without_witnesses(&self) -> Self;
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: impl Layouter<F>,
+    ) -> Result<(), Error>;
+}
+
+struct EvaluationTestCircuit<T>(T);
+
+/// Runs a test on `N` lanes.
+struct OnLanes<T, const N: usize>(T);
+
+impl<F: FieldExt, T: EvaluationTest<F>, const N: usize> EvaluationTest<F> for OnLanes<T, N> {
+    const LANES: usize = N;
+
+    fn without_witnesses(&self) -> Self {
+        Self(self.0.without_witnesses())
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        self.0.synthesize(evaluation_chip, layouter)
+    }
+}
+
+impl<F: FieldExt, T: EvaluationTest<F>> Circuit<F> for EvaluationTestCircuit<T> {
+    type Config = EvaluationConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self(self.0.without_witnesses())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        MoveCircuit::configure(meta, T::LANES)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let evaluation_chip = EvaluationChip::<F>::construct(config, ());
+        evaluation_chip.load_byte_table(layouter.namespace(|| "load byte table"))?;
+        self.0.synthesize(&evaluation_chip, layouter)
+    }
+}
+
 struct TestCircuit<F: FieldExt> {
     a: Option<F>,
     a_type: MoveValueType,
@@ -48,7 +110,7 @@ impl<F: FieldExt> Circuit<F> for TestCircuit<F> {
         let instance = meta.instance_column();
         let constant = meta.fixed_column();
 
-        EvaluationChip::configure(meta, advice, instance, constant)
+        EvaluationChip::configure(meta, &[advice], instance, constant)
     }
 
     fn synthesize(
@@ -57,6 +119,7 @@ impl<F: FieldExt> Circuit<F> for TestCircuit<F> {
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
         let evaluation_chip = EvaluationChip::<F>::construct(config, ());
+        evaluation_chip.load_byte_table(layouter.namespace(|| "load byte table"))?;
 
         let a = evaluation_chip.load_private(
             layouter.namespace(|| "load a"),
@@ -68,35 +131,35 @@ impl<F: FieldExt> Circuit<F> for TestCircuit<F> {
             self.b,
             self.b_type.clone(),
         )?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
         let c = evaluation_chip.binary_op(
             layouter.namespace(|| "a + b"),
             Opcode::Add,
             a.clone(),
             b.clone(),
-            self.cond,
+            cond.clone(),
         )?;
         let d = evaluation_chip.binary_op(
             layouter.namespace(|| "a - b"),
             Opcode::Sub,
             a.clone(),
             b.clone(),
-            self.cond,
+            cond.clone(),
         )?;
         let e = evaluation_chip.binary_op(
             layouter.namespace(|| "a * b"),
             Opcode::Mul,
             a.clone(),
             b.clone(),
-            self.cond,
+            cond.clone(),
         )?;
 
-        let f = evaluation_chip.binary_op(
-            layouter.namespace(|| "a == b"),
-            Opcode::Eq,
-            a,
-            b,
-            self.cond,
-        )?;
+        let f =
+            evaluation_chip.binary_op(layouter.namespace(|| "a == b"), Opcode::Eq, a, b, cond)?;
 
         evaluation_chip.expose_public(layouter.namespace(|| "expose c"), c, 0)?;
         evaluation_chip.expose_public(layouter.namespace(|| "expose d"), d, 1)?;
@@ -139,7 +202,7 @@ impl<F: FieldExt> Circuit<F> for TestBranchCircuit<F> {
         let instance = meta.instance_column();
         let constant = meta.fixed_column();
 
-        EvaluationChip::configure(meta, advice, instance, constant)
+        EvaluationChip::configure(meta, &[advice], instance, constant)
     }
 
     fn synthesize(
@@ -148,6 +211,7 @@ impl<F: FieldExt> Circuit<F> for TestBranchCircuit<F> {
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
         let evaluation_chip = EvaluationChip::<F>::construct(config, ());
+        evaluation_chip.load_byte_table(layouter.namespace(|| "load byte table"))?;
 
         let a = evaluation_chip.load_private(
             layouter.namespace(|| "load a"),
@@ -159,13 +223,24 @@ impl<F: FieldExt> Circuit<F> for TestBranchCircuit<F> {
             self.b,
             self.b_type.clone(),
         )?;
-        let not_cond = self.cond.map(|v| F::one() - v);
+        let one = evaluation_chip.load_constant(
+            layouter.namespace(|| "load one"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+        let (cond, not_cond) =
+            evaluation_chip.branch_condition(layouter.namespace(|| "branch"), one, cond)?;
         let c = evaluation_chip.binary_op(
             layouter.namespace(|| "a + b"),
             Opcode::Add,
             a.clone(),
             b.clone(),
-            self.cond,
+            cond.clone(),
         )?;
         let d = evaluation_chip.binary_op(
             layouter.namespace(|| "a * b"),
@@ -179,13 +254,428 @@ impl<F: FieldExt> Circuit<F> for TestBranchCircuit<F> {
             layouter.namespace(|| "conditional select"),
             c,
             d,
-            self.cond,
+            cond,
         )?;
         evaluation_chip.expose_public(layouter.namespace(|| "expose out"), out, 0)?;
         Ok(())
     }
 }
 
+struct CompareTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    b: Option<F>,
+    ty: MoveValueType,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for CompareTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            b: None,
+            ty: self.ty.clone(),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            self.ty.clone(),
+        )?;
+        let b = evaluation_chip.load_private(
+            layouter.namespace(|| "load b"),
+            self.b,
+            self.ty.clone(),
+        )?;
+        let cond = evaluation_chip.load_constant(
+            layouter.namespace(|| "load cond"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let opcodes = [Opcode::Lt, Opcode::Gt, Opcode::Le, Opcode::Ge];
+        for (row, opcode) in opcodes.iter().enumerate() {
+            let out = evaluation_chip.binary_op(
+                layouter.namespace(|| format!("{:?}", opcode)),
+                *opcode,
+                a.clone(),
+                b.clone(),
+                cond.clone(),
+            )?;
+            evaluation_chip.expose_public(
+                layouter.namespace(|| format!("expose {:?}", opcode)),
+                out,
+                row,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct BitwiseTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    b: Option<F>,
+    shift: Option<F>,
+    ty: MoveValueType,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for BitwiseTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            b: None,
+            shift: None,
+            ty: self.ty.clone(),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            self.ty.clone(),
+        )?;
+        let b = evaluation_chip.load_private(
+            layouter.namespace(|| "load b"),
+            self.b,
+            self.ty.clone(),
+        )?;
+        let shift = evaluation_chip.load_private(
+            layouter.namespace(|| "load shift"),
+            self.shift,
+            MoveValueType::U8,
+        )?;
+        let cond = evaluation_chip.load_constant(
+            layouter.namespace(|| "load cond"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let ops = vec![
+            (Opcode::BitAnd, b.clone()),
+            (Opcode::BitOr, b.clone()),
+            (Opcode::Xor, b),
+            (Opcode::Shl, shift.clone()),
+            (Opcode::Shr, shift),
+        ];
+        for (row, (opcode, rhs)) in ops.into_iter().enumerate() {
+            let out = evaluation_chip.binary_op(
+                layouter.namespace(|| format!("{:?}", opcode)),
+                opcode,
+                a.clone(),
+                rhs,
+                cond.clone(),
+            )?;
+            evaluation_chip.expose_public(
+                layouter.namespace(|| format!("expose {:?}", opcode)),
+                out,
+                row,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct CastTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    from: MoveValueType,
+    to: MoveValueType,
+    cond: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for CastTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            from: self.from.clone(),
+            to: self.to.clone(),
+            cond: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            self.from.clone(),
+        )?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+        let b = evaluation_chip.cast(layouter.namespace(|| "cast a"), a, self.to.clone(), cond)?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose b"), b, 0)
+    }
+}
+
+struct LoadPrivateTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    ty: MoveValueType,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for LoadPrivateTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            ty: self.ty.clone(),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            self.ty.clone(),
+        )?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose a"), a, 0)
+    }
+}
+
+struct ArithmeticTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    b: Option<F>,
+    ty: MoveValueType,
+    opcode: Opcode,
+    cond: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for ArithmeticTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            b: None,
+            ty: self.ty.clone(),
+            opcode: self.opcode,
+            cond: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            self.ty.clone(),
+        )?;
+        // the shift amount is always a u8
+        let b_ty = match self.opcode {
+            Opcode::Shl | Opcode::Shr => MoveValueType::U8,
+            _ => self.ty.clone(),
+        };
+        let b = evaluation_chip.load_private(layouter.namespace(|| "load b"), self.b, b_ty)?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+        let c = evaluation_chip.binary_op(
+            layouter.namespace(|| format!("{:?}", self.opcode)),
+            self.opcode,
+            a,
+            b,
+            cond,
+        )?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose c"), c, 0)
+    }
+}
+
+/// Aborts if cond is true. With `forged_abort` the prover claims another
+/// value for the condition of the aborting arm.
+struct AbortTestCircuit<F: FieldExt> {
+    cond: Option<F>,
+    forged_abort: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for AbortTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            cond: None,
+            forged_abort: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let one = evaluation_chip.load_constant(
+            layouter.namespace(|| "load one"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+        let (abort_cond, _) =
+            evaluation_chip.branch_condition(layouter.namespace(|| "branch"), one, cond)?;
+        let abort_cond = match self.forged_abort {
+            Some(forged) => {
+                Value::new_variable(Some(forged), abort_cond.cell(), MoveValueType::Bool)
+                    .map_err(|_| Error::Synthesis)?
+            }
+            None => abort_cond,
+        };
+        evaluation_chip.abort(layouter.namespace(|| "abort"), abort_cond)
+    }
+}
+
+/// Unrolls `while (i < n) i = i + 1` `bound` times like the frame does, then
+/// aborts if the loop would still continue. With `forged_continue` the
+/// prover claims another value for that condition.
+struct LoopBoundTestCircuit<F: FieldExt> {
+    n: Option<F>,
+    bound: usize,
+    forged_continue: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for LoopBoundTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            n: None,
+            bound: self.bound,
+            forged_continue: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let n = evaluation_chip.load_private(
+            layouter.namespace(|| "load n"),
+            self.n,
+            MoveValueType::U64,
+        )?;
+        let one = evaluation_chip.load_constant(
+            layouter.namespace(|| "load one"),
+            F::one(),
+            MoveValueType::U64,
+        )?;
+        let mut i = evaluation_chip.load_constant(
+            layouter.namespace(|| "load i"),
+            F::zero(),
+            MoveValueType::U64,
+        )?;
+        let mut running = evaluation_chip.load_constant(
+            layouter.namespace(|| "load running"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        for iteration in 0..=self.bound {
+            let header = evaluation_chip.binary_op(
+                layouter.namespace(|| format!("i < n #{}", iteration)),
+                Opcode::Lt,
+                i.clone(),
+                n.clone(),
+                running.clone(),
+            )?;
+            let (continue_cond, _) = evaluation_chip.branch_condition(
+                layouter.namespace(|| format!("loop condition #{}", iteration)),
+                running,
+                header,
+            )?;
+            if iteration == self.bound {
+                let continue_cond = match self.forged_continue {
+                    Some(forged) => {
+                        Value::new_variable(Some(forged), continue_cond.cell(), MoveValueType::Bool)
+                            .map_err(|_| Error::Synthesis)?
+                    }
+                    None => continue_cond,
+                };
+                evaluation_chip.abort(layouter.namespace(|| "loop bound"), continue_cond)?;
+                break;
+            }
+            let next = evaluation_chip.binary_op(
+                layouter.namespace(|| format!("i + 1 #{}", iteration)),
+                Opcode::Add,
+                i.clone(),
+                one.clone(),
+                continue_cond.clone(),
+            )?;
+            i = evaluation_chip.conditional_select(
+                layouter.namespace(|| format!("merge i #{}", iteration)),
+                next,
+                i,
+                continue_cond.clone(),
+            )?;
+            running = continue_cond;
+        }
+        evaluation_chip.expose_public(layouter.namespace(|| "expose i"), i, 0)
+    }
+}
+
+/// Runs a + b in a block which always runs, but the prover claims another
+/// value for the condition of the block.
+struct ForgedConditionTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    b: Option<F>,
+    forged_cond: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for ForgedConditionTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            b: None,
+            forged_cond: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            MoveValueType::U8,
+        )?;
+        let b = evaluation_chip.load_private(
+            layouter.namespace(|| "load b"),
+            self.b,
+            MoveValueType::U8,
+        )?;
+        let cond = evaluation_chip.load_constant(
+            layouter.namespace(|| "load cond"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let forged_cond = Value::new_variable(self.forged_cond, cond.cell(), MoveValueType::Bool)
+            .map_err(|_| Error::Synthesis)?;
+        let c = evaluation_chip.binary_op(
+            layouter.namespace(|| "a + b"),
+            Opcode::Add,
+            a,
+            b,
+            forged_cond,
+        )?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose c"), c, 0)
+    }
+}
+
 #[derive(Clone, Debug)]
 struct RangeCheckTestConfig<F: FieldExt> {
     advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
@@ -193,6 +683,7 @@ struct RangeCheckTestConfig<F: FieldExt> {
     range_check_u8: RangeCheckConfig<F, NUM_OF_BYTES_U8>,
     range_check_u64: RangeCheckConfig<F, NUM_OF_BYTES_U64>,
     range_check_u128: RangeCheckConfig<F, NUM_OF_BYTES_U128>,
+    byte_table: ByteTableConfig,
 }
 
 struct RangeCheckTestCircuit<F: FieldExt> {
@@ -200,124 +691,452 @@ struct RangeCheckTestCircuit<F: FieldExt> {
     cond: F,
 }
 
-impl<F: FieldExt> Circuit<F> for RangeCheckTestCircuit<F> {
-    type Config = RangeCheckTestConfig<F>;
-    type FloorPlanner = SimpleFloorPlanner;
+impl<F: FieldExt> Circuit<F> for RangeCheckTestCircuit<F> {
+    type Config = RangeCheckTestConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: Value::u8(0, None).unwrap(),
+            cond: F::zero(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advices = [(); NUM_OF_ADVICE_COLUMNS].map(|_| meta.advice_column());
+        for column in &advices {
+            meta.enable_equality(*column);
+        }
+
+        let s_a = meta.selector();
+        meta.create_gate("a", |meta| {
+            let s_a = meta.query_selector(s_a);
+            meta.query_advice(advices[0], Rotation::cur());
+            vec![s_a * Expression::Constant(F::zero())]
+        });
+
+        let table = meta.lookup_table_column();
+        let byte_table = ByteTableConfig::configure(meta, advices, table);
+        let range_check_u8 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+        let range_check_u64 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+        let range_check_u128 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+
+        RangeCheckTestConfig {
+            advices,
+            s_a,
+            range_check_u8,
+            range_check_u64,
+            range_check_u128,
+            byte_table,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.byte_table.load(&mut layouter)?;
+        let mut value = None;
+        let mut cond = None;
+        layouter.assign_region(
+            || "range check",
+            |mut region: Region<'_, F>| {
+                config.s_a.enable(&mut region, 0)?;
+                let a = region.assign_advice(
+                    || "a",
+                    config.advices[0],
+                    0,
+                    || {
+                        self.a.value().ok_or_else(|| {
+                            error!("a.value() is None");
+                            Error::Synthesis
+                        })
+                    },
+                )?;
+                value = Some(
+                    Value::new_variable(self.a.value(), Some(a.cell()), self.a.ty())
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                let c = region.assign_advice(|| "cond", config.advices[1], 0, || Ok(self.cond))?;
+                cond = Some(
+                    Value::new_variable(Some(self.cond), Some(c.cell()), MoveValueType::Bool)
+                        .map_err(|_| Error::Synthesis)?,
+                );
+
+                Ok(())
+            },
+        )?;
+
+        match self.a.ty() {
+            MoveValueType::U8 => {
+                RangeCheckChip::construct(config.range_check_u8).assign(
+                    &mut layouter,
+                    value.unwrap(),
+                    cond.unwrap(),
+                )?;
+            }
+            MoveValueType::U64 => {
+                RangeCheckChip::construct(config.range_check_u64).assign(
+                    &mut layouter,
+                    value.unwrap(),
+                    cond.unwrap(),
+                )?;
+            }
+            MoveValueType::U128 => {
+                RangeCheckChip::construct(config.range_check_u128).assign(
+                    &mut layouter,
+                    value.unwrap(),
+                    cond.unwrap(),
+                )?;
+            }
+            _ => unimplemented!(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Range checks a as a u8, with a itself in place of its only byte, the
+/// range check gate holds whatever a is.
+struct ForgedByteTestCircuit<F: FieldExt> {
+    a: Option<F>,
+}
+
+impl<F: FieldExt> Circuit<F> for ForgedByteTestCircuit<F> {
+    type Config = RangeCheckTestConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self { a: None }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        RangeCheckTestCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.byte_table.load(&mut layouter)?;
+        let range_check = config.range_check_u8;
+        layouter.assign_region(
+            || "forged range check",
+            |mut region: Region<'_, F>| {
+                range_check.s_range.enable(&mut region, 0)?;
+                range_check
+                    .cond_cell
+                    .assign(&mut region, 0, Some(F::one()))?;
+                range_check.value_cell.assign(&mut region, 0, self.a)?;
+                range_check.cells.0[0].assign(&mut region, 0, self.a)?;
+                range_check
+                    .byte_table
+                    .enable(&mut region, 0, &range_check.cells.0)
+            },
+        )
+    }
+}
+
+/// Selects between the structs {a, shared} and {b, shared}, only the first
+/// field differs.
+struct StructSelectTestCircuit<F: FieldExt> {
+    a: Option<F>,
+    b: Option<F>,
+    shared: Option<F>,
+    cond: Option<F>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for StructSelectTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: None,
+            b: None,
+            shared: None,
+            cond: None,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let a = evaluation_chip.load_private(
+            layouter.namespace(|| "load a"),
+            self.a,
+            MoveValueType::U64,
+        )?;
+        let b = evaluation_chip.load_private(
+            layouter.namespace(|| "load b"),
+            self.b,
+            MoveValueType::U64,
+        )?;
+        let shared = evaluation_chip.load_private(
+            layouter.namespace(|| "load shared"),
+            self.shared,
+            MoveValueType::U64,
+        )?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+
+        let out = evaluation_chip.conditional_select(
+            layouter.namespace(|| "conditional select"),
+            Value::new_struct(vec![a, shared.clone()]),
+            Value::new_struct(vec![b, shared.clone()]),
+            cond,
+        )?;
+        let fields = out.unpack().map_err(|_| Error::Synthesis)?;
+        // the shared field is passed through without a select
+        assert!(fields[1].equals(&shared));
+        evaluation_chip.expose_public(layouter.namespace(|| "expose x"), fields[0].clone(), 0)?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose y"), fields[1].clone(), 1)
+    }
+}
+
+/// Compares two values of struct { x: u64, inner: struct { v: vector<u8> } },
+/// `slots` fill the capacity of v, only the first `len` of them are elements.
+struct StructEqTestCircuit<F: FieldExt> {
+    x: [Option<F>; 2],
+    slots: [Option<Vec<F>>; 2],
+    len: [Option<F>; 2],
+    opcode: Opcode,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for StructEqTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        let capacity = self.slots[0].as_ref().map_or(0, |slots| slots.len());
+        Self {
+            x: [None; 2],
+            slots: [
+                Some(vec![F::zero(); capacity]),
+                Some(vec![F::zero(); capacity]),
+            ],
+            len: [None; 2],
+            opcode: self.opcode,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let mut values = vec![];
+        for i in 0..2 {
+            let x = evaluation_chip.load_private(
+                layouter.namespace(|| "load x"),
+                self.x[i],
+                MoveValueType::U64,
+            )?;
+            let mut slots = vec![];
+            for slot in self.slots[i].clone().unwrap_or_default() {
+                slots.push(evaluation_chip.load_private(
+                    layouter.namespace(|| "load slot"),
+                    Some(slot),
+                    MoveValueType::U8,
+                )?);
+            }
+            let len = evaluation_chip.load_private(
+                layouter.namespace(|| "load len"),
+                self.len[i],
+                MoveValueType::U64,
+            )?;
+            let v = Value::new_vector(slots, len, MoveValueType::U8);
+            values.push(Value::new_struct(vec![x, Value::new_struct(vec![v])]));
+        }
+        let cond = evaluation_chip.load_constant(
+            layouter.namespace(|| "load cond"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let b = values.pop().unwrap();
+        let a = values.pop().unwrap();
+        let out = evaluation_chip.binary_op(
+            layouter.namespace(|| format!("{:?}", self.opcode)),
+            self.opcode,
+            a,
+            b,
+            cond,
+        )?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose out"), out, 0)
+    }
+}
+
+/// Borrows the element at `index` of a private vector<u8>.
+struct VectorTestCircuit<F: FieldExt> {
+    values: Option<Vec<F>>,
+    index: Option<F>,
+    capacity: usize,
+}
 
+impl<F: FieldExt> EvaluationTest<F> for VectorTestCircuit<F> {
     fn without_witnesses(&self) -> Self {
         Self {
-            a: Value::u8(0, None).unwrap(),
-            cond: F::zero(),
+            values: None,
+            index: None,
+            capacity: self.capacity,
         }
     }
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let advices = [
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-        ];
-        for column in &advices {
-            meta.enable_equality(*column);
-        }
-
-        let s_a = meta.selector();
-        meta.create_gate("a", |meta| {
-            let s_a = meta.query_selector(s_a);
-            meta.query_advice(advices[0], Rotation::cur());
-            vec![s_a * Expression::Constant(F::zero())]
-        });
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let vector = evaluation_chip
+            .load_private_vector(
+                layouter.namespace(|| "load vector"),
+                self.values.clone(),
+                MoveValueType::U8,
+                self.capacity,
+            )?
+            .into_vector()
+            .map_err(|_| Error::Synthesis)?;
+        let index = evaluation_chip.load_private(
+            layouter.namespace(|| "load index"),
+            self.index,
+            MoveValueType::U64,
+        )?;
+        let cond = evaluation_chip.load_constant(
+            layouter.namespace(|| "load cond"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let elem =
+            evaluation_chip.vector_borrow(layouter.namespace(|| "borrow"), &vector, index, cond)?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose elem"), elem, 0)
+    }
+}
 
-        let range_check_u8 = RangeCheckChip::configure(meta, advices);
-        let range_check_u64 = RangeCheckChip::configure(meta, advices);
-        let range_check_u128 = RangeCheckChip::configure(meta, advices);
+/// Pops from and borrows in an empty vector<vector<u8>> under `cond`, no
+/// slot of the vector was ever written.
+struct EmptyVectorTestCircuit<F: FieldExt> {
+    cond: Option<F>,
+    capacity: usize,
+}
 
-        RangeCheckTestConfig {
-            advices,
-            s_a,
-            range_check_u8,
-            range_check_u64,
-            range_check_u128,
+impl<F: FieldExt> EvaluationTest<F> for EmptyVectorTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            cond: None,
+            capacity: self.capacity,
         }
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let mut value = None;
-        layouter.assign_region(
-            || "range check",
-            |mut region: Region<'_, F>| {
-                config.s_a.enable(&mut region, 0)?;
-                let a = region.assign_advice(
-                    || "a",
-                    config.advices[0],
-                    0,
-                    || {
-                        self.a.value().ok_or_else(|| {
-                            error!("a.value() is None");
-                            Error::Synthesis
-                        })
-                    },
-                )?;
-                value = Some(
-                    Value::new_variable(self.a.value(), Some(a.cell()), self.a.ty())
-                        .map_err(|_| Error::Synthesis)?,
-                );
-
-                Ok(())
-            },
+        let elem_ty = MoveValueType::Vector(Box::new(MoveValueType::U8));
+        let len = evaluation_chip.load_constant(
+            layouter.namespace(|| "load length"),
+            F::zero(),
+            MoveValueType::U64,
+        )?;
+        let vector = Value::new_vector(vec![Value::Invalid; self.capacity], len, elem_ty)
+            .into_vector()
+            .map_err(|_| Error::Synthesis)?;
+        let cond = evaluation_chip.load_private(
+            layouter.namespace(|| "load cond"),
+            self.cond,
+            MoveValueType::Bool,
+        )?;
+        let index = evaluation_chip.load_constant(
+            layouter.namespace(|| "load index"),
+            F::zero(),
+            MoveValueType::U64,
         )?;
+        let elem = evaluation_chip.vector_borrow(
+            layouter.namespace(|| "borrow"),
+            &vector,
+            index,
+            cond.clone(),
+        )?;
+        let (_, popped) =
+            evaluation_chip.vector_pop_back(layouter.namespace(|| "pop"), vector, cond)?;
+        for (i, elem) in vec![elem, popped].into_iter().enumerate() {
+            let len = *elem.into_vector().map_err(|_| Error::Synthesis)?.len;
+            evaluation_chip.expose_public(layouter.namespace(|| "expose length"), len, i)?;
+        }
+        Ok(())
+    }
+}
 
-        match self.a.ty() {
-            MoveValueType::U8 => {
-                RangeCheckChip::construct(config.range_check_u8).assign(
-                    &mut layouter,
-                    value.unwrap(),
-                    Some(self.cond),
-                )?;
-            }
-            MoveValueType::U64 => {
-                RangeCheckChip::construct(config.range_check_u64).assign(
-                    &mut layouter,
-                    value.unwrap(),
-                    Some(self.cond),
-                )?;
-            }
-            MoveValueType::U128 => {
-                RangeCheckChip::construct(config.range_check_u128).assign(
-                    &mut layouter,
-                    value.unwrap(),
-                    Some(self.cond),
-                )?;
-            }
-            _ => unimplemented!(),
+/// Computes the state root from a leaf of a resource with `key`.
+struct MerkleTestCircuit<F: FieldExt> {
+    key: Option<F>,
+    leaf: Option<F>,
+    siblings: Option<Vec<F>>,
+}
+
+impl<F: FieldExt> EvaluationTest<F> for MerkleTestCircuit<F> {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            key: None,
+            leaf: None,
+            siblings: None,
         }
+    }
 
-        Ok(())
+    fn synthesize(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let key = evaluation_chip.load_witness(layouter.namespace(|| "load key"), self.key)?;
+        let bits = evaluation_chip.key_bits(layouter.namespace(|| "key bits"), key)?;
+        let siblings = (0..TREE_DEPTH)
+            .map(|level| {
+                evaluation_chip.load_witness(
+                    layouter.namespace(|| format!("load sibling #{}", level)),
+                    self.siblings.as_ref().map(|siblings| siblings[level]),
+                )
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let leaf = evaluation_chip.load_witness(layouter.namespace(|| "load leaf"), self.leaf)?;
+        let root =
+            evaluation_chip.merkle_root(layouter.namespace(|| "root"), leaf, &bits, &siblings)?;
+        evaluation_chip.expose_public(layouter.namespace(|| "expose root"), root, 0)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::chips::chip_tests::TestCircuit;
-    use crate::chips::chip_tests::{RangeCheckTestCircuit, TestBranchCircuit};
+    use crate::chips::chip_tests::{AbortTestCircuit, ArithmeticTestCircuit, LoopBoundTestCircuit};
+    use crate::chips::chip_tests::{BitwiseTestCircuit, CastTestCircuit, CompareTestCircuit};
+    use crate::chips::chip_tests::{
+        EmptyVectorTestCircuit, ForgedByteTestCircuit, ForgedConditionTestCircuit,
+        LoadPrivateTestCircuit, MerkleTestCircuit, RangeCheckTestCircuit, StructEqTestCircuit,
+        StructSelectTestCircuit, TestBranchCircuit, VectorTestCircuit,
+    };
+    use crate::chips::chip_tests::{EvaluationTestCircuit, OnLanes, TestCircuit};
+    use crate::chips::instructions::Opcode;
+    use crate::state_tree::StateTree;
     use crate::value::Value;
+    use halo2_proofs::arithmetic::FieldExt;
     use halo2_proofs::dev::MockProver;
     use halo2_proofs::pasta::{EqAffine, Fp};
     use halo2_proofs::plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, SingleVerifier};
     use halo2_proofs::poly::commitment::Params;
     use halo2_proofs::transcript::{Blake2bRead, Blake2bWrite, Challenge255};
+    use move_core_types::account_address::AccountAddress;
+    use move_core_types::identifier::Identifier;
+    use move_core_types::language_storage::StructTag;
+    use move_core_types::value::MoveValue;
+    use movelang::state::StateStore;
     use movelang::value::MoveValueType;
     use rand_core::OsRng;
 
     #[test]
     fn test_evaluation() {
-        // Circuit is very small, we pick a small value here
-        let k = 5;
+        // Circuit is very small, the byte table takes 256 rows
+        let k = 9;
 
         // Prepare the private and public inputs to the circuit
         let a = Fp::from(3);
@@ -349,10 +1168,57 @@ mod tests {
         assert!(prover.verify().is_err());
     }
 
+    /// Proves the comparison test on `LANES` lanes, the chips take turns on
+    /// the lanes so consecutive instructions land on different columns.
+    fn prove_on_lanes<const LANES: usize>() {
+        let k = 9;
+        let circuit = EvaluationTestCircuit(OnLanes::<_, LANES>(CompareTestCircuit {
+            a: Some(Fp::from(1)),
+            b: Some(Fp::from(2)),
+            ty: MoveValueType::U64,
+        }));
+        let public_inputs = vec![Fp::one(), Fp::zero(), Fp::one(), Fp::zero()];
+
+        let prover = MockProver::run(k, &circuit, vec![public_inputs.clone()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let params: Params<EqAffine> = Params::new(k);
+        let vk = keygen_vk(&params, &circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &circuit).expect("keygen_pk should not fail");
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof(
+            &params,
+            &pk,
+            &[circuit],
+            &[&[public_inputs.as_slice()]],
+            OsRng,
+            &mut transcript,
+        )
+        .expect("proof generation should not fail");
+        let proof: Vec<u8> = transcript.finalize();
+
+        let strategy = SingleVerifier::new(&params);
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let result = verify_proof(
+            &params,
+            pk.get_vk(),
+            strategy,
+            &[&[public_inputs.as_slice()]],
+            &mut transcript,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_lanes() {
+        prove_on_lanes::<2>();
+        prove_on_lanes::<4>();
+    }
+
     #[test]
     fn test_branch() {
-        // Circuit is very small, we pick a small value here
-        let k = 5;
+        // Circuit is very small, the byte table takes 256 rows
+        let k = 9;
         let params: Params<EqAffine> = Params::new(k);
 
         let empty_circuit = TestBranchCircuit {
@@ -418,10 +1284,53 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_branch_condition() {
+        let k = 9;
+        let a = Fp::from(2);
+        let b = Fp::from(3);
+
+        // the false arm is selected
+        let circuit = TestBranchCircuit {
+            a: Some(a),
+            a_type: MoveValueType::U8,
+            b: Some(b),
+            b_type: MoveValueType::U8,
+            cond: Some(Fp::zero()),
+        };
+        let prover = MockProver::run(k, &circuit, vec![vec![a * b]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // a branch condition which is not a bool is rejected
+        let circuit = TestBranchCircuit {
+            a: Some(a),
+            a_type: MoveValueType::U8,
+            b: Some(b),
+            b_type: MoveValueType::U8,
+            cond: Some(Fp::from(2)),
+        };
+        let out = Fp::from(2) * (a + b) - (a * b);
+        let prover = MockProver::run(k, &circuit, vec![vec![out]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // 255 + 1 overflows u8 in a block which always runs, claiming the
+        // block doesn't run would skip the range check
+        let (a, b) = (Fp::from(255), Fp::from(1));
+        for forged_cond in [Fp::one(), Fp::zero()] {
+            let circuit = EvaluationTestCircuit(ForgedConditionTestCircuit {
+                a: Some(a),
+                b: Some(b),
+                forged_cond: Some(forged_cond),
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![a + b]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
     #[test]
     fn test_range_check_1() {
-        // Circuit is very small, we pick a small value here
-        let k = 5;
+        // Circuit is very small, the byte table takes 256 rows
+        let k = 9;
 
         // Prepare the private and public inputs to the circuit
         let a = Fp::from(2);
@@ -450,8 +1359,8 @@ mod tests {
 
     #[test]
     fn test_range_check_2() {
-        // Circuit is very small, we pick a small value here
-        let k = 5;
+        // Circuit is very small, the byte table takes 256 rows
+        let k = 9;
 
         // Prepare the private and public inputs to the circuit
         let a = Fp::from(255);
@@ -480,7 +1389,7 @@ mod tests {
 
     #[test]
     fn test_range_check() {
-        let k = 5;
+        let k = 9;
         let a = Value::u8(2, None).unwrap();
         let cond = Fp::from(1);
         let circuit = RangeCheckTestCircuit { a, cond };
@@ -502,4 +1411,493 @@ mod tests {
         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
         assert_eq!(prover.verify(), Ok(()));
     }
+
+    #[test]
+    fn test_forged_byte() {
+        let k = 9;
+        let circuit = ForgedByteTestCircuit {
+            a: Some(Fp::from(255)),
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // 256 is the only byte of itself, but it isn't in the byte table
+        let circuit = ForgedByteTestCircuit {
+            a: Some(Fp::from(256)),
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_comparison() {
+        let k = 9;
+        let one = Fp::one();
+        let zero = Fp::zero();
+
+        // (a, b, [a < b, a > b, a <= b, a >= b])
+        let cases = vec![
+            (1u64, 2u64, [one, zero, one, zero]),
+            (2u64, 1u64, [zero, one, zero, one]),
+            (7u64, 7u64, [zero, zero, one, one]),
+            (0u64, u64::MAX, [one, zero, one, zero]),
+        ];
+        for (a, b, expected) in cases {
+            let circuit = EvaluationTestCircuit(CompareTestCircuit {
+                a: Some(Fp::from(a)),
+                b: Some(Fp::from(b)),
+                ty: MoveValueType::U64,
+            });
+            let prover = MockProver::run(k, &circuit, vec![expected.to_vec()]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // flipping any of the outputs should be rejected
+            for i in 0..expected.len() {
+                let mut wrong = expected.to_vec();
+                wrong[i] = one - wrong[i];
+                let prover = MockProver::run(k, &circuit, vec![wrong]).unwrap();
+                assert!(prover.verify().is_err());
+            }
+        }
+
+        // keygen lays out the comparisons without a witness
+        let params: Params<EqAffine> = Params::new(k);
+        for ty in [MoveValueType::U8, MoveValueType::U64, MoveValueType::U128] {
+            let empty_circuit = EvaluationTestCircuit(CompareTestCircuit::<Fp> {
+                a: None,
+                b: None,
+                ty,
+            });
+            let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+            keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+        }
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let k = 9;
+
+        // (type, a, b, shift, [a & b, a | b, a ^ b, a << shift, a >> shift])
+        let cases: Vec<(MoveValueType, u128, u128, u128, [u128; 5])> = vec![
+            (
+                MoveValueType::U8,
+                0b1100_1010,
+                0b1010_0110,
+                3,
+                [
+                    0b1000_0010,
+                    0b1110_1110,
+                    0b0110_1100,
+                    0b0101_0000,
+                    0b0001_1001,
+                ],
+            ),
+            (
+                MoveValueType::U8,
+                u8::MAX as u128,
+                0,
+                7,
+                [0, 255, 255, 128, 1],
+            ),
+            (
+                MoveValueType::U64,
+                0xdead_beef_0000_ffff,
+                0xffff_0000_1234_5678,
+                36,
+                [
+                    0xdead_0000_0000_5678,
+                    0xffff_beef_1234_ffff,
+                    0x2152_beef_1234_a987,
+                    0x000f_fff0_0000_0000,
+                    0x0000_0000_0dea_dbee,
+                ],
+            ),
+            (
+                MoveValueType::U128,
+                u128::MAX,
+                1 << 100,
+                127,
+                [1 << 100, u128::MAX, u128::MAX ^ (1 << 100), 1 << 127, 1],
+            ),
+            (MoveValueType::U128, 5, 3, 0, [1, 7, 6, 5, 5]),
+        ];
+        for (ty, a, b, shift, expected) in cases {
+            let circuit = EvaluationTestCircuit(BitwiseTestCircuit {
+                a: Some(Fp::from_u128(a)),
+                b: Some(Fp::from_u128(b)),
+                shift: Some(Fp::from_u128(shift)),
+                ty,
+            });
+            let expected: Vec<Fp> = expected.iter().map(|v| Fp::from_u128(*v)).collect();
+            let prover = MockProver::run(k, &circuit, vec![expected.clone()]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // a wrong result of any operation should be rejected
+            for i in 0..expected.len() {
+                let mut wrong = expected.clone();
+                wrong[i] += Fp::one();
+                let prover = MockProver::run(k, &circuit, vec![wrong]).unwrap();
+                assert!(prover.verify().is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let k = 9;
+
+        // (value, from, to, cond, is_valid)
+        let cases = vec![
+            (
+                255u128,
+                MoveValueType::U64,
+                MoveValueType::U8,
+                Fp::one(),
+                true,
+            ),
+            (
+                255u128,
+                MoveValueType::U8,
+                MoveValueType::U128,
+                Fp::one(),
+                true,
+            ),
+            (
+                u64::MAX as u128,
+                MoveValueType::U128,
+                MoveValueType::U64,
+                Fp::one(),
+                true,
+            ),
+            (
+                256u128,
+                MoveValueType::U64,
+                MoveValueType::U8,
+                Fp::one(),
+                false,
+            ),
+            (
+                u64::MAX as u128 + 1,
+                MoveValueType::U128,
+                MoveValueType::U64,
+                Fp::one(),
+                false,
+            ),
+            // the range check is skipped in a branch which is not taken
+            (
+                256u128,
+                MoveValueType::U64,
+                MoveValueType::U8,
+                Fp::zero(),
+                true,
+            ),
+        ];
+        for (value, from, to, cond, is_valid) in cases {
+            let a = Fp::from_u128(value);
+            let circuit = EvaluationTestCircuit(CastTestCircuit {
+                a: Some(a),
+                from,
+                to,
+                cond: Some(cond),
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![a]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_load_private() {
+        let k = 9;
+
+        // (value, type, is_valid)
+        let cases = vec![
+            (Fp::zero(), MoveValueType::Bool, true),
+            (Fp::one(), MoveValueType::Bool, true),
+            (Fp::from(2), MoveValueType::Bool, false),
+            (Fp::from(255), MoveValueType::U8, true),
+            (Fp::from(256), MoveValueType::U8, false),
+            (-Fp::one(), MoveValueType::U8, false),
+            (Fp::from(u64::MAX), MoveValueType::U64, true),
+            (
+                Fp::from_u128(u64::MAX as u128 + 1),
+                MoveValueType::U64,
+                false,
+            ),
+            (Fp::from_u128(u128::MAX), MoveValueType::U128, true),
+            (
+                Fp::from_u128(u128::MAX) + Fp::one(),
+                MoveValueType::U128,
+                false,
+            ),
+        ];
+        for (a, ty, is_valid) in cases {
+            let circuit = EvaluationTestCircuit(LoadPrivateTestCircuit { a: Some(a), ty });
+            let prover = MockProver::run(k, &circuit, vec![vec![a]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_abort() {
+        let k = 9;
+
+        // the aborting arm isn't taken
+        let circuit = EvaluationTestCircuit(AbortTestCircuit {
+            cond: Some(Fp::zero()),
+            forged_abort: None,
+        });
+        let prover = MockProver::run(k, &circuit, vec![vec![]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = EvaluationTestCircuit(AbortTestCircuit {
+            cond: Some(Fp::one()),
+            forged_abort: None,
+        });
+        let prover = MockProver::run(k, &circuit, vec![vec![]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // skipping the abort by tampering with the condition of the arm
+        let circuit = EvaluationTestCircuit(AbortTestCircuit {
+            cond: Some(Fp::one()),
+            forged_abort: Some(Fp::zero()),
+        });
+        let prover = MockProver::run(k, &circuit, vec![vec![]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_loop_bound() {
+        let k = 10;
+        let bound = 4;
+
+        // (n, forged continue condition, i, is_valid)
+        let cases = vec![
+            (3u64, None, 3u64, true),
+            (4, None, 4, true),
+            // the loop still runs after the last iteration
+            (5, None, 4, false),
+            // stopping it by tampering with the condition to continue
+            (5, Some(Fp::zero()), 4, false),
+        ];
+        for (n, forged_continue, i, is_valid) in cases {
+            let circuit = EvaluationTestCircuit(LoopBoundTestCircuit {
+                n: Some(Fp::from(n)),
+                bound,
+                forged_continue,
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![Fp::from(i)]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_abort() {
+        let k = 9;
+        let (taken, skipped) = (Fp::one(), Fp::zero());
+
+        // (bits, a, b, opcode, cond, c, is_valid), the shift amount is a u8
+        let cases = vec![
+            (64, 7, 2, Opcode::Div, taken, 3, true),
+            (64, 7, 2, Opcode::Mod, taken, 1, true),
+            (64, 7, 0, Opcode::Div, taken, 0, false),
+            (64, 7, 0, Opcode::Mod, taken, 7, false),
+            (8, 200, 55, Opcode::Add, taken, 255, true),
+            (8, 200, 56, Opcode::Add, taken, 0, false),
+            (8, 2, 1, Opcode::Sub, taken, 1, true),
+            (8, 1, 2, Opcode::Sub, taken, 255, false),
+            (64, 1 << 32, 1 << 31, Opcode::Mul, taken, 1 << 63, true),
+            (64, 1 << 32, 1 << 32, Opcode::Mul, taken, 0, false),
+            (128, 1 << 64, 1 << 63, Opcode::Mul, taken, 1 << 127, true),
+            (128, 1 << 64, 1 << 64, Opcode::Mul, taken, 0, false),
+            (128, u128::MAX, u128::MAX, Opcode::Mul, taken, 1, false),
+            (8, 1, 7, Opcode::Shl, taken, 128, true),
+            (8, 1, 8, Opcode::Shl, taken, 0, false),
+            (8, 255, 255, Opcode::Shr, taken, 0, false),
+            (64, 1 << 63, 63, Opcode::Shr, taken, 1, true),
+            (64, 1 << 63, 64, Opcode::Shr, taken, 0, false),
+            (128, 1, 127, Opcode::Shl, taken, 1 << 127, true),
+            (128, 1, 128, Opcode::Shl, taken, 0, false),
+            // none of them aborts in a branch which is not taken
+            (64, 7, 0, Opcode::Div, skipped, 0, true),
+            (64, 7, 0, Opcode::Mod, skipped, 7, true),
+            (8, 200, 56, Opcode::Add, skipped, 0, true),
+            (8, 1, 2, Opcode::Sub, skipped, 255, true),
+            (64, 1 << 32, 1 << 32, Opcode::Mul, skipped, 0, true),
+            (128, u128::MAX, u128::MAX, Opcode::Mul, skipped, 1, true),
+            (8, 1, 8, Opcode::Shl, skipped, 0, true),
+            (128, 1, 200, Opcode::Shr, skipped, 0, true),
+        ];
+        for (bits, a, b, opcode, cond, c, is_valid) in cases {
+            let ty = match bits {
+                8 => MoveValueType::U8,
+                64 => MoveValueType::U64,
+                _ => MoveValueType::U128,
+            };
+            let circuit = EvaluationTestCircuit(ArithmeticTestCircuit {
+                a: Some(Fp::from_u128(a)),
+                b: Some(Fp::from_u128(b)),
+                ty,
+                opcode,
+                cond: Some(cond),
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![Fp::from_u128(c)]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_struct_eq() {
+        let k = 10;
+        let v = |slots: [u64; 3]| Some(slots.iter().map(|s| Fp::from(*s)).collect::<Vec<_>>());
+
+        // (x, slots, len, equal)
+        let cases = vec![
+            ([1, 1], [[3, 5, 7], [3, 5, 7]], [3, 3], true),
+            ([1, 2], [[3, 5, 7], [3, 5, 7]], [3, 3], false),
+            ([1, 1], [[3, 5, 7], [3, 6, 7]], [3, 3], false),
+            ([1, 1], [[3, 5, 7], [3, 5, 7]], [2, 3], false),
+            // slots past the end aren't elements
+            ([1, 1], [[3, 5, 7], [3, 5, 0]], [2, 2], true),
+            ([0, 0], [[0, 0, 0], [9, 9, 9]], [0, 0], true),
+        ];
+        for (x, slots, len, equal) in cases {
+            for opcode in [Opcode::Eq, Opcode::Neq] {
+                let circuit = EvaluationTestCircuit(StructEqTestCircuit {
+                    x: [Some(Fp::from(x[0])), Some(Fp::from(x[1]))],
+                    slots: [v(slots[0]), v(slots[1])],
+                    len: [Some(Fp::from(len[0])), Some(Fp::from(len[1]))],
+                    opcode,
+                });
+                let out = (opcode == Opcode::Eq) == equal;
+                let prover =
+                    MockProver::run(k, &circuit, vec![vec![Fp::from(out as u64)]]).unwrap();
+                assert_eq!(prover.verify(), Ok(()));
+
+                let prover =
+                    MockProver::run(k, &circuit, vec![vec![Fp::from(!out as u64)]]).unwrap();
+                assert!(prover.verify().is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_struct_select() {
+        let k = 9;
+        let (a, b, shared) = (Fp::from(3), Fp::from(4), Fp::from(5));
+        let cases = vec![
+            (Fp::one(), vec![a, shared], true),
+            (Fp::zero(), vec![b, shared], true),
+            (Fp::one(), vec![b, shared], false),
+        ];
+        for (cond, public, is_valid) in cases {
+            let circuit = EvaluationTestCircuit(StructSelectTestCircuit {
+                a: Some(a),
+                b: Some(b),
+                shared: Some(shared),
+                cond: Some(cond),
+            });
+            let prover = MockProver::run(k, &circuit, vec![public]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_vector_borrow() {
+        let k = 10;
+        let values: Vec<Fp> = vec![Fp::from(3), Fp::from(5), Fp::from(7)];
+        let cases = vec![
+            (values.clone(), 1u64, Fp::from(5), true),
+            (values.clone(), 2, Fp::from(7), true),
+            (values.clone(), 1, Fp::from(7), false),
+            // out of bounds, the slot past the end holds 0
+            (values.clone(), 3, Fp::zero(), false),
+            // longer than the capacity
+            (vec![Fp::one(); 5], 0, Fp::one(), false),
+        ];
+        for (values, index, elem, is_valid) in cases {
+            let circuit = EvaluationTestCircuit(VectorTestCircuit {
+                values: Some(values),
+                index: Some(Fp::from(index)),
+                capacity: 4,
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![elem]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+
+        // reading from an empty vector of vectors only aborts if the block runs
+        for (cond, is_valid) in [(Fp::zero(), true), (Fp::one(), false)] {
+            let circuit = EvaluationTestCircuit(EmptyVectorTestCircuit {
+                cond: Some(cond),
+                capacity: 4,
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![Fp::zero(); 2]]).unwrap();
+            assert_eq!(prover.verify().is_ok(), is_valid);
+        }
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let k = 14;
+        let mut tree = StateTree::<Fp>::new(&StateStore::new(), 4).unwrap();
+        let type_id = Fp::from(0xa5);
+        let key = tree.key(Fp::from(0x5_0000_1234), type_id);
+        // another leaf, so the path has a sibling which isn't empty
+        let other = tree.key(Fp::from(0x1235), type_id);
+        tree.update(StateTree::index(other).unwrap(), Fp::from(7));
+        let index = StateTree::index(key).unwrap();
+        let siblings = tree.siblings(index);
+        tree.update(index, Fp::from(42));
+        let root = tree.root();
+
+        let cases = vec![
+            (key, Fp::from(42), true),
+            (key, Fp::from(43), false),
+            // the bits of another key pick another leaf
+            (other, Fp::from(42), false),
+            // the bits are unique, a key can't be split with the modulus added
+            (key + Fp::from(2).pow(&[254, 0, 0, 0]), Fp::from(42), false),
+        ];
+        for (key, leaf, is_valid) in cases {
+            let circuit = EvaluationTestCircuit(MerkleTestCircuit {
+                key: Some(key),
+                leaf: Some(leaf),
+                siblings: Some(siblings.clone()),
+            });
+            let prover = MockProver::run(k, &circuit, vec![vec![root]]);
+            assert_eq!(
+                prover.map(|p| p.verify().is_ok()).unwrap_or(false),
+                is_valid
+            );
+        }
+    }
+
+    #[test]
+    fn test_state_tree_keys() {
+        let tag = StructTag {
+            address: AccountAddress::new([1u8; AccountAddress::LENGTH]),
+            module: Identifier::new("M").unwrap(),
+            name: Identifier::new("R").unwrap(),
+            type_params: vec![],
+        };
+        let address = |high: u8| {
+            let mut bytes = [0u8; AccountAddress::LENGTH];
+            bytes[0] = high;
+            bytes[AccountAddress::LENGTH - 1] = 1;
+            AccountAddress::new(bytes)
+        };
+        // addresses which agree in their low bits don't collide
+        let mut state = StateStore::new();
+        state.add_resource(address(1), tag.clone(), MoveValue::U64(1));
+        state.add_resource(address(2), tag.clone(), MoveValue::U64(2));
+        let tree = StateTree::<Fp>::new(&state, 4).unwrap();
+        assert_ne!(
+            tree.root(),
+            StateTree::<Fp>::new(&StateStore::new(), 4).unwrap().root()
+        );
+
+        let mut state = StateStore::new();
+        let nested = MoveValue::Vector(vec![MoveValue::Vector(vec![MoveValue::U8(1)])]);
+        state.add_resource(address(1), tag, nested);
+        assert!(StateTree::<Fp>::new(&state, 4).is_err());
+    }
 }
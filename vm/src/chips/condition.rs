@@ -0,0 +1,137 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::chips::utilities::Expr;
+use crate::value::Value;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Selector},
+    poly::Rotation,
+};
+use movelang::value::MoveValueType;
+use std::marker::PhantomData;
+
+/// Splits the condition of a block at a conditional branch into the
+/// conditions of its arms. The arms run if the block runs and the branch
+/// condition is true or false respectively:
+///
+/// | parent | cond | parent * cond | parent * (1 - cond) |
+///
+/// The parent condition and the branch condition are copied from the cells
+/// they were computed in, the branch condition is constrained to be boolean.
+/// The function body runs under the constant 1, so every block condition is
+/// boolean as well.
+#[derive(Clone, Debug)]
+pub struct ConditionConfig {
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    s_cond: Selector,
+}
+
+pub struct ConditionChip<F: FieldExt> {
+    config: ConditionConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> Chip<F> for ConditionChip<F> {
+    type Config = ConditionConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt> ConditionChip<F> {
+    pub fn construct(
+        config: <Self as Chip<F>>::Config,
+        _loaded: <Self as Chip<F>>::Loaded,
+    ) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    ) -> <Self as Chip<F>>::Config {
+        let s_cond = meta.selector();
+
+        meta.create_gate("branch condition", |meta| {
+            let parent = meta.query_advice(advices[0], Rotation::cur());
+            let cond = meta.query_advice(advices[1], Rotation::cur());
+            let t_cond = meta.query_advice(advices[2], Rotation::cur());
+            let f_cond = meta.query_advice(advices[3], Rotation::cur());
+            let s_cond = meta.query_selector(s_cond);
+
+            vec![
+                s_cond.clone() * (cond.clone() * (1.expr() - cond.clone())),
+                s_cond.clone() * (parent.clone() * cond - t_cond.clone()),
+                s_cond * (parent - t_cond - f_cond),
+            ]
+        });
+
+        ConditionConfig { advices, s_cond }
+    }
+
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        parent: Value<F>,
+        cond: Value<F>,
+    ) -> Result<(Value<F>, Value<F>), Error> {
+        let config = self.config();
+
+        let mut conditions = None;
+        layouter.assign_region(
+            || "branch condition",
+            |mut region: Region<'_, F>| {
+                config.s_cond.enable(&mut region, 0)?;
+
+                let inputs = [(&parent, "parent"), (&cond, "cond")];
+                for (column, (value, annotation)) in inputs.iter().enumerate() {
+                    let cell = region.assign_advice(
+                        || *annotation,
+                        config.advices[column],
+                        0,
+                        || value.value().ok_or(Error::Synthesis),
+                    )?;
+                    region.constrain_equal(value.cell().ok_or(Error::Synthesis)?, cell.cell())?;
+                }
+
+                let t_value = parent.value().zip(cond.value()).map(|(p, c)| p * c);
+                let f_value = parent.value().zip(t_value).map(|(p, t)| p - t);
+                let mut outputs = vec![];
+                for (column, (value, annotation)) in
+                    vec![(t_value, "true arm"), (f_value, "false arm")]
+                        .into_iter()
+                        .enumerate()
+                {
+                    let cell = region.assign_advice(
+                        || annotation,
+                        config.advices[column + 2],
+                        0,
+                        || value.ok_or(Error::Synthesis),
+                    )?;
+                    outputs.push(
+                        Value::new_variable(value, Some(cell.cell()), MoveValueType::Bool)
+                            .map_err(|_| Error::Synthesis)?,
+                    );
+                }
+                let f_cond = outputs.pop().unwrap();
+                let t_cond = outputs.pop().unwrap();
+                conditions = Some((t_cond, f_cond));
+                Ok(())
+            },
+        )?;
+
+        Ok(conditions.unwrap())
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::assign_cond;
 use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
 use crate::value::Value;
 use halo2_proofs::{
@@ -9,6 +10,7 @@ use halo2_proofs::{
     plonk::{Advice, Column, ConstraintSystem, Error, Selector},
     poly::Rotation,
 };
+use logger::prelude::*;
 use std::marker::PhantomData;
 
 #[derive(Clone, Debug)]
@@ -71,12 +73,97 @@ impl<F: FieldExt> ConditionalSelectChip<F> {
         ConditionalSelectConfig { advices, s_cs }
     }
 
+    /// Selects `a` if `cond` is 1 and `b` otherwise. Structs are selected
+    /// field by field and vectors slot by slot, fields equal in both are taken
+    /// as they are. A vector slot which is invalid on one side is past the end
+    /// there, so the other side is taken.
     pub fn conditional_select(
         &self,
         mut layouter: impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        match (a, b) {
+            (Value::Struct(a), Value::Struct(b)) => {
+                if a.len() != b.len() {
+                    return Err(Error::Synthesis);
+                }
+                let fields = a
+                    .into_iter()
+                    .zip(b)
+                    .enumerate()
+                    .map(|(i, (a, b))| {
+                        if a.equals(&b) {
+                            Ok(a)
+                        } else {
+                            self.conditional_select(
+                                layouter.namespace(|| format!("select field #{}", i)),
+                                a,
+                                b,
+                                cond.clone(),
+                            )
+                        }
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(Value::new_struct(fields))
+            }
+            (Value::Vector(a), Value::Vector(b)) => {
+                if a.capacity() != b.capacity() {
+                    return Err(Error::Synthesis);
+                }
+                let len = self.conditional_select(
+                    layouter.namespace(|| "select length"),
+                    *a.len,
+                    *b.len,
+                    cond.clone(),
+                )?;
+                let slots = a
+                    .slots
+                    .into_iter()
+                    .zip(b.slots)
+                    .enumerate()
+                    .map(|(i, (a, b))| match (&a, &b) {
+                        _ if a.equals(&b) => Ok(a),
+                        (Value::Invalid, _) => Ok(b),
+                        (_, Value::Invalid) => Ok(a),
+                        _ => self.conditional_select(
+                            layouter.namespace(|| format!("select slot #{}", i)),
+                            a,
+                            b,
+                            cond.clone(),
+                        ),
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(Value::new_vector(slots, len, a.elem_ty))
+            }
+            (Value::ImmRef(a), Value::ImmRef(b)) => {
+                let value = self.conditional_select(layouter, *a, *b, cond)?;
+                Ok(Value::ImmRef(Box::new(value)))
+            }
+            // a reference can't be selected, both sides must point to the same place
+            (Value::Reference(a), Value::Reference(b)) if a == b => Ok(Value::Reference(a)),
+            (a @ Value::Struct(_), _)
+            | (_, a @ Value::Struct(_))
+            | (a @ Value::Vector(_), _)
+            | (_, a @ Value::Vector(_))
+            | (a @ Value::Reference(_), _)
+            | (_, a @ Value::Reference(_))
+            | (a @ Value::ImmRef(_), _)
+            | (_, a @ Value::ImmRef(_)) => {
+                debug!("can not select between different shapes of {:?}", a);
+                Err(Error::Synthesis)
+            }
+            (a, b) => self.select(layouter, a, b, cond),
+        }
+    }
+
+    fn select(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let config = self.config();
 
@@ -101,7 +188,7 @@ impl<F: FieldExt> ConditionalSelectChip<F> {
                 region.constrain_equal(a.cell().unwrap(), lhs.cell())?;
                 region.constrain_equal(b.cell().unwrap(), rhs.cell())?;
 
-                let value = match (a.value(), b.value(), cond) {
+                let value = match (a.value(), b.value(), cond.value()) {
                     (Some(a), Some(b), Some(cond)) => {
                         let v = if cond == F::one() { a } else { b };
                         Some(v)
@@ -116,12 +203,7 @@ impl<F: FieldExt> ConditionalSelectChip<F> {
                     || value.ok_or(Error::Synthesis),
                 )?;
 
-                region.assign_advice(
-                    || "cond",
-                    config.advices[3],
-                    0,
-                    || cond.ok_or(Error::Synthesis),
-                )?;
+                assign_cond!(cond, region, config);
 
                 c = Some(
                     Value::new_variable(value, Some(cell.cell()), a.ty())
@@ -1,10 +1,13 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::assign_cond;
+use crate::chips::condition::{ConditionChip, ConditionConfig};
 use crate::chips::conditional_select::{ConditionalSelectChip, ConditionalSelectConfig};
 use crate::chips::instructions::_mod::{ModChip, ModConfig};
 use crate::chips::instructions::add::{AddChip, AddConfig};
 use crate::chips::instructions::and::{AndChip, AndConfig};
+use crate::chips::instructions::bitwise::{BitwiseChip, BitwiseConfig};
 use crate::chips::instructions::div::{DivChip, DivConfig};
 use crate::chips::instructions::eq::{EqChip, EqConfig};
 use crate::chips::instructions::lt::{LtChip, LtConfig};
@@ -12,26 +15,53 @@ use crate::chips::instructions::mul::{MulChip, MulConfig};
 use crate::chips::instructions::neq::{NeqChip, NeqConfig};
 use crate::chips::instructions::not::{NotChip, NotConfig};
 use crate::chips::instructions::or::{OrChip, OrConfig};
+use crate::chips::instructions::shift::{ShiftChip, ShiftConfig};
 use crate::chips::instructions::sub::{SubChip, SubConfig};
 use crate::chips::instructions::Opcode;
+use crate::chips::poseidon::{PoseidonChip, PoseidonConfig};
+use crate::chips::state_tree::KeyBitsConfig;
 use crate::chips::utilities::{
-    RangeCheckChip, RangeCheckConfig, NUM_OF_BYTES_U128, NUM_OF_BYTES_U64, NUM_OF_BYTES_U8,
+    ByteTableConfig, Expr, RangeCheckChip, RangeCheckConfig, NUM_OF_BITS_U128, NUM_OF_BITS_U64,
+    NUM_OF_BITS_U8, NUM_OF_BYTES_U128, NUM_OF_BYTES_U64, NUM_OF_BYTES_U8, NUM_OF_SHIFT_BITS_U128,
+    NUM_OF_SHIFT_BITS_U64, NUM_OF_SHIFT_BITS_U8,
 };
 use crate::value::Value;
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Chip, Layouter},
-    plonk::{Advice, Column, ConstraintSystem, Error, Fixed, Instance},
+    plonk::{Advice, Column, ConstraintSystem, Error, Fixed, Instance, Selector, TableColumn},
+    poly::Rotation,
 };
+use logger::prelude::*;
 use movelang::value::MoveValueType;
+use std::cell::Cell;
 
+/// Advice columns of a lane. The gates of the chips are written against
+/// these columns, so a circuit gets more columns by more lanes rather than
+/// wider ones.
 pub const NUM_OF_ADVICE_COLUMNS: usize = 4;
 
+/// The chips are configured on lanes, each with advice columns of its own.
+/// The evaluation chip lays out each chip it uses on the next lane, so the
+/// floor planner places consecutive instructions side by side, and a
+/// circuit with more lanes has more columns but fewer rows. Each chip still
+/// lays out regions of its own, the instructions of a block are not packed
+/// into a single region.
 #[derive(Clone, Debug)]
 pub struct EvaluationConfig<F: FieldExt> {
+    lanes: Vec<LaneConfig<F>>,
+    instance: Column<Instance>,         // Public inputs
+    pub(crate) constant: Column<Fixed>, // Fixed column to load constants
+    pub(crate) poseidon_config: PoseidonConfig<F>,
+    pub(crate) key_bits_config: KeyBitsConfig,
+}
+
+/// The chips laid out on the advice columns of a lane.
+#[derive(Clone, Debug)]
+pub struct LaneConfig<F: FieldExt> {
     advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
-    instance: Column<Instance>, // Public inputs
-    constant: Column<Fixed>,    // Fixed column to load constants
+    s_bool: Selector,  // Private bool inputs are 0 or 1
+    s_abort: Selector, // Aborting paths are never taken
     add_config: AddConfig<F>,
     sub_config: SubConfig<F>,
     mul_config: MulConfig<F>,
@@ -42,16 +72,25 @@ pub struct EvaluationConfig<F: FieldExt> {
     and_config: AndConfig<F>,
     or_config: OrConfig<F>,
     not_config: NotConfig<F>,
-    lt_config: LtConfig<F>,
+    lt: LtConfig<F>,
+    bitwise_u8: BitwiseConfig<F, NUM_OF_BITS_U8>,
+    bitwise_u64: BitwiseConfig<F, NUM_OF_BITS_U64>,
+    bitwise_u128: BitwiseConfig<F, NUM_OF_BITS_U128>,
+    shift_u8: ShiftConfig<F, NUM_OF_BYTES_U8, NUM_OF_SHIFT_BITS_U8>,
+    shift_u64: ShiftConfig<F, NUM_OF_BYTES_U64, NUM_OF_SHIFT_BITS_U64>,
+    shift_u128: ShiftConfig<F, NUM_OF_BYTES_U128, NUM_OF_SHIFT_BITS_U128>,
     conditional_select_config: ConditionalSelectConfig,
+    condition_config: ConditionConfig,
     range_check_u8: RangeCheckConfig<F, NUM_OF_BYTES_U8>,
     range_check_u64: RangeCheckConfig<F, NUM_OF_BYTES_U64>,
     range_check_u128: RangeCheckConfig<F, NUM_OF_BYTES_U128>,
+    byte_table: ByteTableConfig,
 }
 
 pub struct EvaluationChip<F: FieldExt> {
     config: EvaluationConfig<F>,
-    conditional_select_chip: ConditionalSelectChip<F>,
+    // the lane the next chip is laid out on
+    next_lane: Cell<usize>,
 }
 
 impl<F: FieldExt> Chip<F> for EvaluationChip<F> {
@@ -67,27 +106,23 @@ impl<F: FieldExt> Chip<F> for EvaluationChip<F> {
     }
 }
 
-impl<F: FieldExt> EvaluationChip<F> {
-    pub fn construct(
-        config: <Self as Chip<F>>::Config,
-        _loaded: <Self as Chip<F>>::Loaded,
-    ) -> Self {
-        let conditional_select_config = config.conditional_select_config.clone();
-        let conditional_select_chip =
-            ConditionalSelectChip::<F>::construct(conditional_select_config, ());
-
-        Self {
-            config,
-            conditional_select_chip,
-        }
+impl<F: FieldExt> EvaluationConfig<F> {
+    /// The selector of each lookup argument, a lookup is done on every row
+    /// the selector is enabled.
+    pub(crate) fn lookup_selectors(&self) -> Vec<Selector> {
+        self.lanes
+            .iter()
+            .flat_map(|lane| lane.byte_table.lookup_selectors())
+            .collect()
     }
+}
 
-    pub fn configure(
+impl<F: FieldExt> LaneConfig<F> {
+    fn configure(
         meta: &mut ConstraintSystem<F>,
         advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
-        instance: Column<Instance>,
-        constant: Column<Fixed>,
-    ) -> <Self as Chip<F>>::Config {
+        byte_table: TableColumn,
+    ) -> Self {
         let add_config = AddChip::configure(meta, advices);
         let sub_config = SubChip::configure(meta, advices);
         let mul_config = MulChip::configure(meta, advices);
@@ -98,22 +133,42 @@ impl<F: FieldExt> EvaluationChip<F> {
         let and_config = AndChip::configure(meta, advices);
         let or_config = OrChip::configure(meta, advices);
         let not_config = NotChip::configure(meta, advices);
-        let lt_config = LtChip::configure(meta, advices);
+        let byte_table = ByteTableConfig::configure(meta, advices, byte_table);
+        let lt = LtChip::configure(meta, advices, byte_table.clone());
+        let bitwise_u8 = BitwiseChip::configure(meta, advices);
+        let bitwise_u64 = BitwiseChip::configure(meta, advices);
+        let bitwise_u128 = BitwiseChip::configure(meta, advices);
         let conditional_select_config = ConditionalSelectChip::configure(meta, advices);
-        let range_check_u8 = RangeCheckChip::configure(meta, advices);
-        let range_check_u64 = RangeCheckChip::configure(meta, advices);
-        let range_check_u128 = RangeCheckChip::configure(meta, advices);
+        let condition_config = ConditionChip::configure(meta, advices);
+        let range_check_u8 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+        let range_check_u64 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+        let range_check_u128 = RangeCheckChip::configure(meta, advices, byte_table.clone());
+        let shift_u8 = ShiftChip::configure(meta, advices, range_check_u8.clone());
+        let shift_u64 = ShiftChip::configure(meta, advices, range_check_u64.clone());
+        let shift_u128 = ShiftChip::configure(meta, advices, range_check_u128.clone());
 
         for column in &advices {
             meta.enable_equality(*column);
         }
-        meta.enable_equality(instance);
-        meta.enable_constant(constant);
 
-        EvaluationConfig {
+        let s_bool = meta.selector();
+        meta.create_gate("bool", |meta| {
+            let s_bool = meta.query_selector(s_bool);
+            let value = meta.query_advice(advices[0], Rotation::cur());
+            vec![s_bool * value.clone() * (1.expr() - value)]
+        });
+
+        let s_abort = meta.selector();
+        meta.create_gate("abort", |meta| {
+            let s_abort = meta.query_selector(s_abort);
+            let cond = meta.query_advice(advices[0], Rotation::cur());
+            vec![s_abort * cond]
+        });
+
+        LaneConfig {
             advices,
-            instance,
-            constant,
+            s_bool,
+            s_abort,
             add_config,
             sub_config,
             mul_config,
@@ -124,43 +179,309 @@ impl<F: FieldExt> EvaluationChip<F> {
             and_config,
             or_config,
             not_config,
-            lt_config,
+            lt,
+            bitwise_u8,
+            bitwise_u64,
+            bitwise_u128,
+            shift_u8,
+            shift_u64,
+            shift_u128,
             conditional_select_config,
+            condition_config,
             range_check_u8,
             range_check_u64,
             range_check_u128,
-            //other config
+            byte_table,
+        }
+    }
+}
+
+impl<F: FieldExt> EvaluationChip<F> {
+    pub fn construct(
+        config: <Self as Chip<F>>::Config,
+        _loaded: <Self as Chip<F>>::Loaded,
+    ) -> Self {
+        Self {
+            config,
+            next_lane: Cell::new(0),
         }
     }
 
+    /// The lane the next chip is laid out on, the lanes take turns.
+    fn lane(&self) -> &LaneConfig<F> {
+        let lane = self.next_lane.get();
+        self.next_lane.set((lane + 1) % self.config.lanes.len());
+        &self.config.lanes[lane]
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: &[[Column<Advice>; NUM_OF_ADVICE_COLUMNS]],
+        instance: Column<Instance>,
+        constant: Column<Fixed>,
+    ) -> <Self as Chip<F>>::Config {
+        assert!(!advices.is_empty(), "the chips need at least one lane");
+        let byte_table = meta.lookup_table_column();
+        let lanes = advices
+            .iter()
+            .map(|advices| LaneConfig::configure(meta, *advices, byte_table))
+            .collect();
+        let round_constants = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let poseidon_config = PoseidonChip::configure(meta, advices[0], round_constants);
+        let key_bits_config = KeyBitsConfig::configure(meta, advices[0]);
+
+        meta.enable_equality(instance);
+        meta.enable_constant(constant);
+
+        EvaluationConfig {
+            lanes,
+            instance,
+            constant,
+            poseidon_config,
+            key_bits_config,
+        }
+    }
+
+    /// Loads the byte table the range checks look up, once per circuit.
+    pub fn load_byte_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.config.lanes[0].byte_table.load(&mut layouter)
+    }
+
     pub fn conditional_select(
         &self,
         layouter: impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
-        self.conditional_select_chip
+        ConditionalSelectChip::<F>::construct(self.lane().conditional_select_config.clone(), ())
             .conditional_select(layouter, a, b, cond)
     }
 
-    fn range_check(
+    /// The conditions of the true and false arms of a branch on `cond`, in a
+    /// block running under `parent`.
+    pub fn branch_condition(
+        &self,
+        layouter: impl Layouter<F>,
+        parent: Value<F>,
+        cond: Value<F>,
+    ) -> Result<(Value<F>, Value<F>), Error> {
+        ConditionChip::<F>::construct(self.lane().condition_config.clone(), ())
+            .assign(layouter, parent, cond)
+    }
+
+    /// Aborts the path running under `cond`. A valid proof requires the
+    /// condition to be 0, i.e. the path is never taken.
+    pub fn abort(&self, mut layouter: impl Layouter<F>, cond: Value<F>) -> Result<(), Error> {
+        let lane = self.lane();
+        layouter.assign_region(
+            || "abort",
+            |mut region| {
+                lane.s_abort.enable(&mut region, 0)?;
+                assign_cond!(cond, region, column = lane.advices[0]);
+                Ok(())
+            },
+        )
+    }
+
+    /// Overflowing aborts. Returns the condition under which the operation
+    /// doesn't overflow.
+    fn check_overflow(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        overflow: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let (abort_cond, no_overflow) =
+            self.branch_condition(layouter.namespace(|| "overflow"), cond, overflow)?;
+        self.abort(layouter.namespace(|| "abort"), abort_cond)?;
+        Ok(no_overflow)
+    }
+
+    /// A u128 product may wrap around the field modulus. If it doesn't
+    /// overflow, one of the factors is less than 2^64, and the product of
+    /// the factors is less than 2^192.
+    fn check_small_factor(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+        cond: Value<F>,
+    ) -> Result<(), Error> {
+        let swap = a.value().zip(b.value()).map(|(a, b)| {
+            if a.get_lower_128() <= b.get_lower_128() {
+                F::zero()
+            } else {
+                F::one()
+            }
+        });
+        let swap = self.load_private(
+            layouter.namespace(|| "load swap"),
+            swap,
+            MoveValueType::Bool,
+        )?;
+        let small = self.conditional_select(layouter.namespace(|| "small factor"), b, a, swap)?;
+        self.range_check(layouter, small.with_ty(MoveValueType::U64), cond)
+    }
+
+    /// Dividing by zero aborts.
+    fn check_divisor(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        divisor: Value<F>,
+        cond: Value<F>,
+    ) -> Result<(), Error> {
+        let zero =
+            self.load_constant(layouter.namespace(|| "load zero"), F::zero(), divisor.ty())?;
+        let is_zero = EqChip::<F>::construct(self.lane().eq_config.clone(), ()).assign(
+            layouter,
+            divisor,
+            zero,
+            cond.clone(),
+        )?;
+        let (abort_cond, _) =
+            self.branch_condition(layouter.namespace(|| "division by zero"), cond, is_zero)?;
+        self.abort(layouter.namespace(|| "abort"), abort_cond)
+    }
+
+    /// Shifting by the bit width of the value or more aborts. Returns the
+    /// condition under which the shift amount is in range.
+    fn check_shift_amount(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        ty: MoveValueType,
+        shift: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let num_of_bits = match ty {
+            MoveValueType::U8 => NUM_OF_BYTES_U8 * 8,
+            MoveValueType::U64 => NUM_OF_BYTES_U64 * 8,
+            MoveValueType::U128 => NUM_OF_BYTES_U128 * 8,
+            _ => {
+                error!("unsupported type {:?} for shift", ty);
+                return Err(Error::Synthesis);
+            }
+        };
+        let max = self.load_constant(
+            layouter.namespace(|| "load max shift"),
+            F::from(num_of_bits as u64 - 1),
+            MoveValueType::U8,
+        )?;
+        let too_large = LtChip::<F>::construct(self.lane().lt.clone(), ()).assign(
+            layouter,
+            max,
+            shift,
+            false,
+            cond.clone(),
+        )?;
+        let (abort_cond, in_range) = self.branch_condition(
+            layouter.namespace(|| "shift amount out of range"),
+            cond,
+            too_large,
+        )?;
+        self.abort(layouter.namespace(|| "abort"), abort_cond)?;
+        Ok(in_range)
+    }
+
+    /// Structs are equal if all their fields are, vectors if their lengths
+    /// and the elements before the end are.
+    fn equals(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        match (a, b) {
+            (Value::Struct(a), Value::Struct(b)) if a.len() == b.len() => {
+                let mut eq = self.load_constant(
+                    layouter.namespace(|| "load true"),
+                    F::one(),
+                    MoveValueType::Bool,
+                )?;
+                for (i, (a, b)) in a.into_iter().zip(b).enumerate() {
+                    let field_eq = self.equals(layouter, a, b, cond.clone())?;
+                    eq = self.binary_op(
+                        layouter.namespace(|| format!("field #{}", i)),
+                        Opcode::And,
+                        eq,
+                        field_eq,
+                        cond.clone(),
+                    )?;
+                }
+                Ok(eq)
+            }
+            (Value::Vector(a), Value::Vector(b)) if a.capacity() == b.capacity() => {
+                let len = *a.len.clone();
+                let mut eq = self.equals(layouter, len.clone(), *b.len, cond.clone())?;
+                for (i, (x, y)) in a.slots.into_iter().zip(b.slots).enumerate() {
+                    // slots never written are past the end
+                    if matches!(x, Value::Invalid) || matches!(y, Value::Invalid) {
+                        continue;
+                    }
+                    let index = self.load_constant(
+                        layouter.namespace(|| "load index"),
+                        F::from(i as u64),
+                        MoveValueType::U64,
+                    )?;
+                    let past_end = self.binary_op(
+                        layouter.namespace(|| format!("slot #{} past the end", i)),
+                        Opcode::Ge,
+                        index,
+                        len.clone(),
+                        cond.clone(),
+                    )?;
+                    let elem_eq = self.equals(layouter, x, y, cond.clone())?;
+                    let slot_eq = self.binary_op(
+                        layouter.namespace(|| format!("slot #{}", i)),
+                        Opcode::Or,
+                        past_end,
+                        elem_eq,
+                        cond.clone(),
+                    )?;
+                    eq = self.binary_op(
+                        layouter.namespace(|| format!("slot #{} and", i)),
+                        Opcode::And,
+                        eq,
+                        slot_eq,
+                        cond.clone(),
+                    )?;
+                }
+                Ok(eq)
+            }
+            (
+                a @ (Value::Constant(_) | Value::Variable(_)),
+                b @ (Value::Constant(_) | Value::Variable(_)),
+            ) => EqChip::<F>::construct(self.lane().eq_config.clone(), ())
+                .assign(layouter, a, b, cond),
+            (a, b) => {
+                error!("can not compare {:?} with {:?}", a, b);
+                Err(Error::Synthesis)
+            }
+        }
+    }
+
+    pub(crate) fn range_check(
         &self,
         layouter: &mut impl Layouter<F>,
         value: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<(), Error> {
         match value.ty() {
             MoveValueType::U8 => {
-                RangeCheckChip::construct(self.config.range_check_u8.clone())
+                RangeCheckChip::construct(self.lane().range_check_u8.clone())
                     .assign(layouter, value, cond)?;
             }
             MoveValueType::U64 => {
-                RangeCheckChip::construct(self.config.range_check_u64.clone())
+                RangeCheckChip::construct(self.lane().range_check_u64.clone())
                     .assign(layouter, value, cond)?;
             }
             MoveValueType::U128 => {
-                RangeCheckChip::construct(self.config.range_check_u128.clone())
+                RangeCheckChip::construct(self.lane().range_check_u128.clone())
                     .assign(layouter, value, cond)?;
             }
             _ => {}
@@ -174,48 +495,144 @@ impl<F: FieldExt> EvaluationChip<F> {
         opcode: Opcode,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let out = match opcode {
             Opcode::Add => {
-                let add_chip = AddChip::<F>::construct(self.config.add_config.clone(), ());
-                add_chip.assign(&mut layouter, a, b, cond)?
+                let add_chip = AddChip::<F>::construct(self.lane().add_config.clone(), ());
+                let (out, overflow) = add_chip.assign(&mut layouter, a, b, cond.clone())?;
+                self.check_overflow(&mut layouter, overflow, cond.clone())?;
+                out
             }
             Opcode::Sub => {
-                let sub_chip = SubChip::<F>::construct(self.config.sub_config.clone(), ());
-                sub_chip.assign(&mut layouter, a, b, cond)?
+                let sub_chip = SubChip::<F>::construct(self.lane().sub_config.clone(), ());
+                let (out, underflow) = sub_chip.assign(&mut layouter, a, b, cond.clone())?;
+                self.check_overflow(&mut layouter, underflow, cond.clone())?;
+                out
             }
             Opcode::Mul => {
-                let mul_chip = MulChip::<F>::construct(self.config.mul_config.clone(), ());
-                mul_chip.assign(&mut layouter, a, b, cond)?
+                let mul_chip = MulChip::<F>::construct(self.lane().mul_config.clone(), ());
+                let (out, overflow) =
+                    mul_chip.assign(&mut layouter, a.clone(), b.clone(), cond.clone())?;
+                let no_overflow = self.check_overflow(&mut layouter, overflow, cond.clone())?;
+                if matches!(a.ty(), MoveValueType::U128) {
+                    self.check_small_factor(&mut layouter, a, b, no_overflow)?;
+                }
+                out
             }
             Opcode::Div => {
-                let div_chip = DivChip::<F>::construct(self.config.div_config.clone(), ());
-                div_chip.assign(&mut layouter, a, b, cond)?
+                self.check_divisor(&mut layouter, b.clone(), cond.clone())?;
+                let div_chip = DivChip::<F>::construct(self.lane().div_config.clone(), ());
+                div_chip.assign(&mut layouter, a, b, cond.clone())?
             }
             Opcode::Mod => {
-                let mod_chip = ModChip::<F>::construct(self.config.mod_config.clone(), ());
-                mod_chip.assign(&mut layouter, a, b, cond)?
-            }
-            Opcode::Eq => {
-                let eq_chip = EqChip::<F>::construct(self.config.eq_config.clone(), ());
-                eq_chip.assign(&mut layouter, a, b, cond)?
-            }
-            Opcode::Neq => {
-                let neq_chip = NeqChip::<F>::construct(self.config.neq_config.clone(), ());
-                neq_chip.assign(&mut layouter, a, b, cond)?
+                self.check_divisor(&mut layouter, b.clone(), cond.clone())?;
+                let mod_chip = ModChip::<F>::construct(self.lane().mod_config.clone(), ());
+                mod_chip.assign(&mut layouter, a, b, cond.clone())?
             }
+            Opcode::Eq => self.equals(&mut layouter, a, b, cond.clone())?,
+            Opcode::Neq => match (&a, &b) {
+                (Value::Struct(_), _) | (Value::Vector(_), _) => {
+                    let eq = self.equals(&mut layouter, a, b, cond.clone())?;
+                    self.unary_op(layouter.namespace(|| "not"), Opcode::Not, eq, cond.clone())?
+                }
+                _ => {
+                    let neq_chip = NeqChip::<F>::construct(self.lane().neq_config.clone(), ());
+                    neq_chip.assign(&mut layouter, a, b, cond.clone())?
+                }
+            },
             Opcode::And => {
-                let and_chip = AndChip::<F>::construct(self.config.and_config.clone(), ());
-                and_chip.assign(&mut layouter, a, b, cond)?
+                let and_chip = AndChip::<F>::construct(self.lane().and_config.clone(), ());
+                and_chip.assign(&mut layouter, a, b, cond.clone())?
             }
             Opcode::Or => {
-                let or_chip = OrChip::<F>::construct(self.config.or_config.clone(), ());
-                or_chip.assign(&mut layouter, a, b, cond)?
+                let or_chip = OrChip::<F>::construct(self.lane().or_config.clone(), ());
+                or_chip.assign(&mut layouter, a, b, cond.clone())?
             }
-            Opcode::Lt => {
-                let lt_chip = LtChip::<F>::construct(self.config.lt_config.clone(), ());
-                lt_chip.assign(&mut layouter, a, b, cond)?
+            Opcode::Lt | Opcode::Gt | Opcode::Le | Opcode::Ge => {
+                // a > b is b < a, a <= b is !(b < a) and a >= b is !(a < b)
+                let (lhs, rhs) = match opcode {
+                    Opcode::Lt | Opcode::Ge => (a, b),
+                    _ => (b, a),
+                };
+                let negate = matches!(opcode, Opcode::Le | Opcode::Ge);
+                LtChip::<F>::construct(self.lane().lt.clone(), ()).assign(
+                    &mut layouter,
+                    lhs,
+                    rhs,
+                    negate,
+                    cond.clone(),
+                )?
+            }
+            Opcode::BitAnd | Opcode::BitOr | Opcode::Xor => match a.ty() {
+                MoveValueType::U8 => {
+                    BitwiseChip::<F, NUM_OF_BITS_U8>::construct(self.lane().bitwise_u8.clone(), ())
+                        .assign(&mut layouter, opcode, a, b, cond.clone())?
+                }
+                MoveValueType::U64 => BitwiseChip::<F, NUM_OF_BITS_U64>::construct(
+                    self.lane().bitwise_u64.clone(),
+                    (),
+                )
+                .assign(&mut layouter, opcode, a, b, cond.clone())?,
+                MoveValueType::U128 => BitwiseChip::<F, NUM_OF_BITS_U128>::construct(
+                    self.lane().bitwise_u128.clone(),
+                    (),
+                )
+                .assign(&mut layouter, opcode, a, b, cond.clone())?,
+                _ => {
+                    error!("unsupported type {:?} for {:?}", a.ty(), opcode);
+                    return Err(Error::Synthesis);
+                }
+            },
+            Opcode::Shl | Opcode::Shr => {
+                // the shift itself runs only if the amount is in range
+                let in_range =
+                    self.check_shift_amount(&mut layouter, a.ty(), b.clone(), cond.clone())?;
+                match a.ty() {
+                    MoveValueType::U8 => {
+                        ShiftChip::<F, NUM_OF_BYTES_U8, NUM_OF_SHIFT_BITS_U8>::construct(
+                            self.lane().shift_u8.clone(),
+                            (),
+                        )
+                        .assign(
+                            &mut layouter,
+                            opcode,
+                            a,
+                            b,
+                            in_range.clone(),
+                        )?
+                    }
+                    MoveValueType::U64 => {
+                        ShiftChip::<F, NUM_OF_BYTES_U64, NUM_OF_SHIFT_BITS_U64>::construct(
+                            self.lane().shift_u64.clone(),
+                            (),
+                        )
+                        .assign(
+                            &mut layouter,
+                            opcode,
+                            a,
+                            b,
+                            in_range.clone(),
+                        )?
+                    }
+                    MoveValueType::U128 => {
+                        ShiftChip::<F, NUM_OF_BYTES_U128, NUM_OF_SHIFT_BITS_U128>::construct(
+                            self.lane().shift_u128.clone(),
+                            (),
+                        )
+                        .assign(
+                            &mut layouter,
+                            opcode,
+                            a,
+                            b,
+                            in_range.clone(),
+                        )?
+                    }
+                    _ => {
+                        error!("unsupported type {:?} for {:?}", a.ty(), opcode);
+                        return Err(Error::Synthesis);
+                    }
+                }
             }
             _ => unreachable!(),
         };
@@ -228,32 +645,59 @@ impl<F: FieldExt> EvaluationChip<F> {
         mut layouter: impl Layouter<F>,
         opcode: Opcode,
         a: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         match opcode {
             Opcode::Not => {
-                let not_chip = NotChip::<F>::construct(self.config.not_config.clone(), ());
+                let not_chip = NotChip::<F>::construct(self.lane().not_config.clone(), ());
                 not_chip.assign(&mut layouter, a, cond)
             }
             _ => unreachable!(),
         }
     }
 
+    /// Casts keep the cell of the operand. Narrowing casts range check the
+    /// operand against the target type.
+    pub fn cast(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Value<F>,
+        ty: MoveValueType,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let narrowing = matches!(
+            (a.ty(), &ty),
+            (MoveValueType::U64, MoveValueType::U8)
+                | (MoveValueType::U128, MoveValueType::U8)
+                | (MoveValueType::U128, MoveValueType::U64)
+        );
+        let out = a.with_ty(ty);
+        if narrowing {
+            self.range_check(&mut layouter, out.clone(), cond)?;
+        }
+        Ok(out)
+    }
+
+    /// Private inputs are range checked against their type, a bool input
+    /// must be 0 or 1 and an address must fit in 128 bits.
     pub fn load_private(
         &self,
         mut layouter: impl Layouter<F>,
         value: Option<F>,
         ty: MoveValueType,
     ) -> Result<Value<F>, Error> {
-        let config = self.config();
+        let lane = self.lane();
 
         let mut alloc = None;
         layouter.assign_region(
             || "load private",
             |mut region| {
+                if matches!(ty, MoveValueType::Bool) {
+                    lane.s_bool.enable(&mut region, 0)?;
+                }
                 let cell = region.assign_advice(
                     || "private input",
-                    config.advices[0],
+                    lane.advices[0],
                     0,
                     || value.ok_or(Error::Synthesis),
                 )?;
@@ -264,6 +708,60 @@ impl<F: FieldExt> EvaluationChip<F> {
                 Ok(())
             },
         )?;
+        let value = alloc.unwrap();
+
+        if matches!(
+            ty,
+            MoveValueType::U8
+                | MoveValueType::U64
+                | MoveValueType::U128
+                | MoveValueType::Address
+                | MoveValueType::Signer
+        ) {
+            // inputs are loaded before any block runs
+            let cond = self.load_constant(
+                layouter.namespace(|| "load range check condition"),
+                F::one(),
+                MoveValueType::Bool,
+            )?;
+            // an address is a 128 bits number
+            let checked = match ty {
+                MoveValueType::Address | MoveValueType::Signer => {
+                    value.clone().with_ty(MoveValueType::U128)
+                }
+                _ => value.clone(),
+            };
+            self.range_check(&mut layouter, checked, cond)?;
+        }
+        Ok(value)
+    }
+
+    /// Loads a field element which isn't range checked, e.g. a node of the
+    /// state tree.
+    pub fn load_witness(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Option<F>,
+    ) -> Result<Value<F>, Error> {
+        let lane = self.lane();
+
+        let mut alloc = None;
+        layouter.assign_region(
+            || "load witness",
+            |mut region| {
+                let cell = region.assign_advice(
+                    || "witness",
+                    lane.advices[0],
+                    0,
+                    || value.ok_or(Error::Synthesis),
+                )?;
+                alloc = Some(
+                    Value::new_variable(value, Some(cell.cell()), MoveValueType::U128)
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                Ok(())
+            },
+        )?;
         Ok(alloc.unwrap())
     }
 
@@ -4,6 +4,7 @@
 pub mod _mod;
 pub mod add;
 pub mod and;
+pub mod bitwise;
 pub mod common;
 pub mod div;
 pub mod eq;
@@ -12,8 +13,10 @@ pub mod mul;
 pub mod neq;
 pub mod not;
 pub mod or;
+pub mod shift;
 pub mod sub;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Opcode {
     LdU8,
     LdU64,
@@ -41,4 +44,12 @@ pub enum Opcode {
     Call,
     Abort,
     Lt,
+    Gt,
+    Le,
+    Ge,
+    BitAnd,
+    BitOr,
+    Xor,
+    Shl,
+    Shr,
 }
@@ -78,7 +78,7 @@ impl<F: FieldExt> ModChip<F> {
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let config = self.config();
 
@@ -2,14 +2,17 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::chips::utilities::type_modulus;
 use crate::value::Value;
-use crate::{assign_cond, assign_operands};
+use crate::{assign_cond, assign_operands, assign_overflow, overflowing};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Chip, Layouter, Region},
     plonk::{Advice, Column, ConstraintSystem, Error, Selector},
     poly::Rotation,
 };
+use logger::prelude::*;
+use movelang::value::{convert_to_field, move_overflowing_add, MoveValue, MoveValueType};
 use std::marker::PhantomData;
 
 #[derive(Clone, Debug)]
@@ -54,9 +57,12 @@ impl<F: FieldExt> AddChip<F> {
             let rhs = meta.query_advice(advices[1], Rotation::cur());
             let out = meta.query_advice(advices[2], Rotation::cur());
             let cond = meta.query_advice(advices[3], Rotation::cur());
+            let overflow = meta.query_advice(advices[0], Rotation::next());
+            let modulus = meta.query_advice(advices[1], Rotation::next());
             let s_add = meta.query_selector(s_add) * cond;
 
-            vec![s_add * (lhs + rhs - out)]
+            // out wraps around 2^bits of the type, overflow is 1 if it does
+            vec![s_add * (lhs + rhs - out - overflow * modulus)]
         });
 
         AddConfig {
@@ -71,8 +77,8 @@ impl<F: FieldExt> AddChip<F> {
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
-    ) -> Result<Value<F>, Error> {
+        cond: Value<F>,
+    ) -> Result<(Value<F>, Value<F>), Error> {
         let config = self.config();
 
         let mut c = None;
@@ -84,17 +90,19 @@ impl<F: FieldExt> AddChip<F> {
                 assign_operands!(a, b, region, config);
                 assign_cond!(cond, region, config);
 
-                let value = a.value().and_then(|a| b.value().map(|b| a + b));
+                let (value, overflow) = overflowing!(a, b, move_overflowing_add);
                 let cell = region.assign_advice(
                     || "lhs + rhs",
                     config.advices[2],
                     0,
                     || value.ok_or(Error::Synthesis),
                 )?;
-                c = Some(
+                let overflow = assign_overflow!(overflow, a.ty(), region, config);
+                c = Some((
                     Value::new_variable(value, Some(cell.cell()), a.ty())
                         .map_err(|_| Error::Synthesis)?,
-                );
+                    overflow,
+                ));
                 Ok(())
             },
         )?;
@@ -81,7 +81,7 @@ impl<F: FieldExt> AndChip<F> {
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let config = self.config();
 
@@ -0,0 +1,220 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::chips::instructions::Opcode;
+use crate::chips::utilities::Expr;
+use crate::value::Value;
+use crate::{assign_cond, assign_operands};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, VirtualCells},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// BitAnd, BitOr and Xor walk through the operands bit by bit. Row i holds
+/// lhs >> i, rhs >> i, out >> i and cond, so the i-th bits are
+/// acc_cur - 2 * acc_next. Row NUM_OF_BITS holds zeros, which also bounds
+/// the operands and the result to NUM_OF_BITS bits.
+///
+/// s_bits and one of the operation selectors are enabled on rows
+/// 0..NUM_OF_BITS, s_end is enabled on row NUM_OF_BITS.
+#[derive(Clone, Debug)]
+pub struct BitwiseConfig<F: FieldExt, const NUM_OF_BITS: usize> {
+    s_bits: Selector,
+    s_end: Selector,
+    s_bit_and: Selector,
+    s_bit_or: Selector,
+    s_xor: Selector,
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    _marker: PhantomData<F>,
+}
+
+pub struct BitwiseChip<F: FieldExt, const NUM_OF_BITS: usize> {
+    config: BitwiseConfig<F, NUM_OF_BITS>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const NUM_OF_BITS: usize> Chip<F> for BitwiseChip<F, NUM_OF_BITS> {
+    type Config = BitwiseConfig<F, NUM_OF_BITS>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt, const NUM_OF_BITS: usize> BitwiseChip<F, NUM_OF_BITS> {
+    pub(crate) fn construct(
+        config: <Self as Chip<F>>::Config,
+        _loaded: <Self as Chip<F>>::Loaded,
+    ) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    ) -> <Self as Chip<F>>::Config {
+        let s_bits = meta.selector();
+        let s_end = meta.selector();
+        let s_bit_and = meta.selector();
+        let s_bit_or = meta.selector();
+        let s_xor = meta.selector();
+
+        // the bits of lhs, rhs and out in the current row
+        let query_bits = |meta: &mut VirtualCells<'_, F>| {
+            let mut bits = vec![];
+            for column in advices.iter().take(3) {
+                let cur = meta.query_advice(*column, Rotation::cur());
+                let next = meta.query_advice(*column, Rotation::next());
+                bits.push(cur - next * F::from(2));
+            }
+            (bits[0].clone(), bits[1].clone(), bits[2].clone())
+        };
+
+        meta.create_gate("bitwise bits", |meta| {
+            let cond = meta.query_advice(advices[3], Rotation::cur());
+            let cond_next = meta.query_advice(advices[3], Rotation::next());
+            let (lhs, rhs, _) = query_bits(meta);
+            let s_bits = meta.query_selector(s_bits);
+
+            vec![
+                // cond is the same in all rows
+                s_bits.clone() * (cond_next - cond.clone()),
+                // bits of lhs and rhs are 0 or 1, then so are the bits of out
+                s_bits.clone() * cond.clone() * (lhs.clone() * (1.expr() - lhs)),
+                s_bits * cond * (rhs.clone() * (1.expr() - rhs)),
+            ]
+        });
+
+        meta.create_gate("bitwise end", |meta| {
+            let cond = meta.query_advice(advices[3], Rotation::cur());
+            let s_end = meta.query_selector(s_end) * cond;
+            advices
+                .iter()
+                .take(3)
+                .map(|column| s_end.clone() * meta.query_advice(*column, Rotation::cur()))
+                .collect::<Vec<_>>()
+        });
+
+        let bitwise_gate =
+            |meta: &mut ConstraintSystem<F>,
+             name: &'static str,
+             selector: Selector,
+             op: fn(Expression<F>, Expression<F>) -> Expression<F>| {
+                meta.create_gate(name, |meta| {
+                    let cond = meta.query_advice(advices[3], Rotation::cur());
+                    let (lhs, rhs, out) = query_bits(meta);
+                    let s = meta.query_selector(selector) * cond;
+                    vec![s * (op(lhs, rhs) - out)]
+                });
+            };
+        // c = a * b
+        bitwise_gate(meta, "bit_and", s_bit_and, |a, b| a * b);
+        // c = a + b - a * b
+        bitwise_gate(meta, "bit_or", s_bit_or, |a, b| {
+            a.clone() + b.clone() - a * b
+        });
+        // c = a + b - 2 * a * b
+        bitwise_gate(meta, "xor", s_xor, |a, b| {
+            a.clone() + b.clone() - a * b * F::from(2)
+        });
+
+        BitwiseConfig {
+            s_bits,
+            s_end,
+            s_bit_and,
+            s_bit_or,
+            s_xor,
+            advices,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn assign(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        opcode: Opcode,
+        a: Value<F>,
+        b: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let config = self.config();
+
+        let (selector, name) = match opcode {
+            Opcode::BitAnd => (config.s_bit_and, "bit_and"),
+            Opcode::BitOr => (config.s_bit_or, "bit_or"),
+            Opcode::Xor => (config.s_xor, "xor"),
+            _ => unreachable!(),
+        };
+
+        let mut c = None;
+        layouter.assign_region(
+            || name,
+            |mut region: Region<'_, F>| {
+                assign_operands!(a, b, region, config);
+                assign_cond!(cond, region, config);
+
+                let operands = match (a.value(), b.value()) {
+                    (Some(a), Some(b)) => Some((a.get_lower_128(), b.get_lower_128())),
+                    _ => None,
+                };
+                let out = operands.map(|(a, b)| match opcode {
+                    Opcode::BitAnd => a & b,
+                    Opcode::BitOr => a | b,
+                    Opcode::Xor => a ^ b,
+                    _ => unreachable!(),
+                });
+                let value = out.map(F::from_u128);
+
+                let cell = region.assign_advice(
+                    || name,
+                    config.advices[2],
+                    0,
+                    || value.ok_or(Error::Synthesis),
+                )?;
+
+                for offset in 0..NUM_OF_BITS {
+                    config.s_bits.enable(&mut region, offset)?;
+                    selector.enable(&mut region, offset)?;
+
+                    let row = offset + 1;
+                    let shift = |v: u128| F::from_u128(v.checked_shr(row as u32).unwrap_or(0));
+                    let accumulators = [
+                        operands.map(|(a, _)| shift(a)),
+                        operands.map(|(_, b)| shift(b)),
+                        out.map(shift),
+                        cond.value(),
+                    ];
+                    for (column, v) in accumulators.iter().enumerate() {
+                        region.assign_advice(
+                            || format!("{} row {}", name, row),
+                            config.advices[column],
+                            row,
+                            || v.ok_or(Error::Synthesis),
+                        )?;
+                    }
+                }
+                config.s_end.enable(&mut region, NUM_OF_BITS)?;
+
+                c = Some(
+                    Value::new_variable(value, Some(cell.cell()), a.ty())
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                Ok(())
+            },
+        )?;
+
+        Ok(c.unwrap())
+    }
+}
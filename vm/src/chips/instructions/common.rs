@@ -23,13 +23,61 @@ macro_rules! assign_operands {
 
 #[macro_export]
 macro_rules! assign_cond {
-    ($cond:expr, $region:expr, $config:expr) => {{
-        $region.assign_advice(
+    ($cond:expr, $region:expr, column = $column:expr) => {{
+        let cond = $region.assign_advice(
             || "cond",
-            $config.advices[3],
+            $column,
             0,
-            || $cond.ok_or(Error::Synthesis),
+            || $cond.value().ok_or(Error::Synthesis),
+        )?;
+        // cond is the condition of the block, not a free witness
+        $region.constrain_equal($cond.cell().ok_or(Error::Synthesis)?, cond.cell())?;
+    }};
+    ($cond:expr, $region:expr, $config:expr) => {{
+        $crate::assign_cond!($cond, $region, column = $config.advices[3]);
+    }};
+}
+
+/// The result wrapped around the bit width of the type, and whether the
+/// operation overflows.
+#[macro_export]
+macro_rules! overflowing {
+    ($a:expr, $b:expr, $op:ident) => {{
+        let l_move: Option<MoveValue> = $a.clone().into();
+        let r_move: Option<MoveValue> = $b.clone().into();
+        match (l_move, r_move) {
+            (Some(l), Some(r)) => {
+                let (value, overflow) = $op(l, r).map_err(|e| {
+                    error!("{} failed: {:?}", stringify!($op), e);
+                    Error::Synthesis
+                })?;
+                let overflow = if overflow { F::one() } else { F::zero() };
+                (Some(convert_to_field::<F>(value)), Some(overflow))
+            }
+            _ => (None, None),
+        }
+    }};
+}
+
+/// Assigns the overflow flag and 2^bits of the type in the row below the
+/// operands.
+#[macro_export]
+macro_rules! assign_overflow {
+    ($overflow:expr, $ty:expr, $region:expr, $config:expr) => {{
+        let overflow_cell = $region.assign_advice(
+            || "overflow",
+            $config.advices[0],
+            1,
+            || $overflow.ok_or(Error::Synthesis),
+        )?;
+        $region.assign_advice_from_constant(
+            || "modulus",
+            $config.advices[1],
+            1,
+            type_modulus::<F>(&$ty),
         )?;
+        Value::new_variable($overflow, Some(overflow_cell.cell()), MoveValueType::Bool)
+            .map_err(|_| Error::Synthesis)?
     }};
 }
 
@@ -39,6 +87,8 @@ macro_rules! div_rem {
         let l_move: Option<MoveValue> = $a.clone().into();
         let r_move: Option<MoveValue> = $b.clone().into();
         match (l_move, r_move) {
+            // dividing by zero aborts, a = 0 * 0 + a satisfies the gate
+            (Some(_), Some(_)) if $b.value() == Some(F::zero()) => (Some(F::zero()), $a.value()),
             (Some(l), Some(r)) => {
                 let quo = move_div(l.clone(), r.clone()).map_err(|e| {
                     error!("move div failed: {:?}", e);
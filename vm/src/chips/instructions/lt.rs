@@ -2,13 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
-use crate::chips::utilities::{ByteRepresentation, Expr, NUM_OF_BYTES_U128};
+use crate::chips::utilities::{
+    construct_cells, ByteTableConfig, Cell, Expr, NUM_OF_BYTES_U128, NUM_OF_BYTES_U64,
+    NUM_OF_BYTES_U8,
+};
 use crate::value::Value;
 use crate::{assign_cond, assign_operands};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Chip, Layouter, Region},
-    plonk::{Advice, Column, ConstraintSystem, Error, Selector},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
     poly::Rotation,
 };
 use logger::prelude::*;
@@ -16,14 +19,42 @@ use movelang::value::MoveValueType;
 use std::convert::TryInto;
 use std::marker::PhantomData;
 
+/// The comparison of operands of `num_of_bytes` bytes, `s_not` in place of
+/// `s_lt` negates the output.
 #[derive(Clone, Debug)]
-pub struct LtConfig<F: FieldExt> {
+struct LtWidth<F: FieldExt> {
+    num_of_bytes: usize,
     s_lt: Selector,
+    s_not: Selector,
+    diff_cells: Vec<Cell<F>>,
+}
+
+/// Compares lhs < rhs. The other comparisons are laid out on the same cells,
+/// lhs > rhs is rhs < lhs, lhs <= rhs is !(rhs < lhs) and lhs >= rhs is
+/// !(lhs < rhs). The operands of every width share the columns, each width
+/// has a gate of its own that queries as many diff cells as it has bytes.
+#[derive(Clone, Debug)]
+pub struct LtConfig<F: FieldExt> {
+    widths: Vec<LtWidth<F>>,
     advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
-    lt_diff_cells: ByteRepresentation<F, NUM_OF_BYTES_U128>,
+    byte_table: ByteTableConfig,
     _marker: PhantomData<F>,
 }
 
+impl<F: FieldExt> LtConfig<F> {
+    fn width(&self, ty: &MoveValueType) -> Option<&LtWidth<F>> {
+        let num_of_bytes = match ty {
+            MoveValueType::U8 => NUM_OF_BYTES_U8,
+            MoveValueType::U64 => NUM_OF_BYTES_U64,
+            MoveValueType::U128 => NUM_OF_BYTES_U128,
+            _ => return None,
+        };
+        self.widths
+            .iter()
+            .find(|width| width.num_of_bytes == num_of_bytes)
+    }
+}
+
 pub struct LtChip<F: FieldExt> {
     config: LtConfig<F>,
     _marker: PhantomData<F>,
@@ -56,72 +87,114 @@ impl<F: FieldExt> LtChip<F> {
     pub(crate) fn configure(
         meta: &mut ConstraintSystem<F>,
         advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        byte_table: ByteTableConfig,
     ) -> <Self as Chip<F>>::Config {
+        let widths = [NUM_OF_BYTES_U8, NUM_OF_BYTES_U64, NUM_OF_BYTES_U128]
+            .iter()
+            .map(|&num_of_bytes| Self::configure_width(meta, advices, num_of_bytes))
+            .collect();
+
+        LtConfig {
+            widths,
+            advices,
+            byte_table,
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure_width(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        num_of_bytes: usize,
+    ) -> LtWidth<F> {
         let s_lt = meta.selector();
+        let s_not = meta.selector();
         let mut lt_diff_cells = None;
         meta.create_gate("lt", |meta| {
             let lhs = meta.query_advice(advices[0], Rotation::cur());
             let rhs = meta.query_advice(advices[1], Rotation::cur());
             let out = meta.query_advice(advices[2], Rotation::cur());
             let cond = meta.query_advice(advices[3], Rotation::cur());
-            let range = F::from(2).pow(&[(NUM_OF_BYTES_U128 * 8) as u64, 0, 0, 0]);
+            let range = F::from(2).pow(&[(num_of_bytes * 8) as u64, 0, 0, 0]);
             let diff_cells =
-                ByteRepresentation::construct(meta, advices, Rotation::next().0 as usize);
-            lt_diff_cells = Some(diff_cells.clone());
-            let s_lt = meta.query_selector(s_lt) * cond;
+                construct_cells(meta, advices, Rotation::next().0 as usize, num_of_bytes);
+            let diff = bytes_expr(&diff_cells);
+            lt_diff_cells = Some(diff_cells);
+            let s_lt = meta.query_selector(s_lt) * cond.clone();
+            let s_not = meta.query_selector(s_not) * cond;
 
             vec![
                 // out is 0 or 1
                 s_lt.clone() * (out.clone() * (1.expr() - out.clone())),
+                s_not.clone() * (out.clone() * (1.expr() - out.clone())),
                 // let diff = if lhs >= rhs {lhs - rhs} else {lhs - rhs + range};
                 // to constrain: lhs - rhs = diff - out * range
                 // if lhs >= rhs, then diff = lhs - rhs, out must be 0.
-                // if lhs < rhs, then diff = lhs - rhs + range, diff is in range 2^128, out can only be 1.
+                // if lhs < rhs, then diff = lhs - rhs + range, diff is in range 2^(8 * num_of_bytes), out can only be 1.
                 //
-                // diff is reconstructed from the lower 16 bytes of the original value,
-                // it will always be in range 2^128.
+                // the bytes of diff are looked up in the byte table, so diff is always in
+                // range 2^(8 * num_of_bytes), the operands are range checked against their
+                // type of num_of_bytes bytes.
                 //
-                s_lt * ((lhs - rhs) + out * range - diff_cells.expr()),
+                s_lt * ((lhs.clone() - rhs.clone()) + out.clone() * range - diff.clone()),
+                // negated, out is 1 - (lhs < rhs)
+                s_not * ((lhs - rhs) + (1.expr() - out) * range - diff),
             ]
         });
 
-        LtConfig {
+        LtWidth {
+            num_of_bytes,
             s_lt,
-            advices,
-            lt_diff_cells: lt_diff_cells.expect("lt_diff_cells is None."),
-            _marker: PhantomData,
+            s_not,
+            diff_cells: lt_diff_cells.expect("lt_diff_cells is None."),
         }
     }
 
+    /// Returns a < b, or !(a < b) if `negate` is set.
     pub(crate) fn assign(
         &self,
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
+        negate: bool,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let config = self.config();
+        let width = config.width(&a.ty()).ok_or_else(|| {
+            error!("unsupported type {:?} for comparison", a.ty());
+            Error::Synthesis
+        })?;
+        let diff_cells = &width.diff_cells;
 
         let mut c = None;
         layouter.assign_region(
             || "lt",
             |mut region: Region<'_, F>| {
-                config.s_lt.enable(&mut region, 0)?;
+                if negate {
+                    width.s_not.enable(&mut region, 0)?;
+                } else {
+                    width.s_lt.enable(&mut region, 0)?;
+                }
 
                 assign_operands!(a, b, region, config);
                 assign_cond!(cond, region, config);
-                self.assign_diff_bytes(&mut region, &config.lt_diff_cells, a.clone(), b.clone())?;
+                assign_diff_bytes(&mut region, diff_cells, a.clone(), b.clone())?;
+                config.byte_table.enable(&mut region, 0, diff_cells)?;
 
                 let value = match (a.value(), b.value()) {
                     (Some(a), Some(b)) => {
-                        let v = if a < b { F::one() } else { F::zero() };
+                        let v = if (a < b) != negate {
+                            F::one()
+                        } else {
+                            F::zero()
+                        };
                         Some(v)
                     }
                     _ => None,
                 };
 
                 let cell = region.assign_advice(
-                    || "lhs < rhs",
+                    || "compare",
                     config.advices[2],
                     0,
                     || value.ok_or(Error::Synthesis),
@@ -137,39 +210,46 @@ impl<F: FieldExt> LtChip<F> {
 
         Ok(c.unwrap())
     }
+}
 
-    /// if a >= b then diff = a - b; if a < b then diff = a - b + range;
-    /// Move doesn't support u256, range 0~2^128 is workable for u8, u64, u128
-    /// convert diff into the byte representation, assign them into specified cells.
-    fn assign_diff_bytes(
-        &self,
-        region: &mut Region<'_, F>,
-        cells: &ByteRepresentation<F, NUM_OF_BYTES_U128>,
-        a: Value<F>,
-        b: Value<F>,
-    ) -> Result<(), Error> {
-        let lhs = a.value().ok_or_else(|| {
-            error!("a.value is None");
-            Error::Synthesis
-        })?;
-        let rhs = b.value().ok_or_else(|| {
-            error!("b.value is None");
-            Error::Synthesis
-        })?;
-        let range = F::from(2).pow(&[(NUM_OF_BYTES_U128 * 8) as u64, 0, 0, 0]);
+/// The little-endian value of `bytes`.
+fn bytes_expr<F: FieldExt>(bytes: &[Cell<F>]) -> Expression<F> {
+    let mut value = 0.expr();
+    let mut multiplier = F::one();
+    for byte in bytes {
+        value = value + byte.expression.clone() * multiplier;
+        multiplier *= F::from(256);
+    }
+    value
+}
+
+/// if a >= b then diff = a - b; if a < b then diff = a - b + range;
+/// range is 2^(8 * cells.len()), the operands are less than it.
+/// convert diff into the byte representation, assign them into specified cells.
+pub(crate) fn assign_diff_bytes<F: FieldExt>(
+    region: &mut Region<'_, F>,
+    cells: &[Cell<F>],
+    a: Value<F>,
+    b: Value<F>,
+) -> Result<(), Error> {
+    let range = F::from(2).pow(&[(cells.len() * 8) as u64, 0, 0, 0]);
+    // the diff is unknown without a witness, e.g. during keygen
+    let diff_bytes: Option<[u8; 32]> = a.value().zip(b.value()).map(|(lhs, rhs)| {
         let range_or_zero = if lhs < rhs { range } else { F::zero() };
         let diff = (lhs - rhs) + range_or_zero;
-
-        let diff_bytes: [u8; 32] = diff
-            .to_repr()
+        diff.to_repr()
             .as_ref()
             .try_into()
-            .expect("Field fits into 256 bits");
-
-        for (index, byte) in cells.0.iter().enumerate() {
-            byte.assign(region, 0, Some(F::from(diff_bytes[index] as u64)))?;
-        }
-
-        Ok(())
+            .expect("Field fits into 256 bits")
+    });
+
+    for (index, byte) in cells.iter().enumerate() {
+        byte.assign(
+            region,
+            0,
+            diff_bytes.map(|bytes| F::from(bytes[index] as u64)),
+        )?;
     }
+
+    Ok(())
 }
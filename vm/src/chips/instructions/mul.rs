@@ -2,14 +2,17 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::chips::utilities::{type_modulus, Expr};
 use crate::value::Value;
-use crate::{assign_cond, assign_operands};
+use crate::{assign_cond, assign_operands, assign_overflow, overflowing};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Chip, Layouter, Region},
     plonk::{Advice, Column, ConstraintSystem, Error, Selector},
     poly::Rotation,
 };
+use logger::prelude::*;
+use movelang::value::{convert_to_field, move_overflowing_mul, MoveValue, MoveValueType};
 use std::marker::PhantomData;
 
 #[derive(Clone, Debug)]
@@ -58,9 +61,16 @@ impl<F: FieldExt> MulChip<F> {
             let rhs = meta.query_advice(advices[1], Rotation::cur());
             let out = meta.query_advice(advices[2], Rotation::cur());
             let cond = meta.query_advice(advices[3], Rotation::cur());
+            let overflow = meta.query_advice(advices[0], Rotation::next());
+            let modulus = meta.query_advice(advices[1], Rotation::next());
+            let high = meta.query_advice(advices[2], Rotation::next());
             let s_mul = meta.query_selector(s_mul) * cond;
 
-            vec![s_mul * (lhs * rhs - out)]
+            vec![
+                s_mul.clone() * (lhs * rhs - out - high.clone() * modulus),
+                // the high part is zero unless the product overflows
+                s_mul * high * (1.expr() - overflow),
+            ]
         });
 
         MulConfig {
@@ -75,8 +85,8 @@ impl<F: FieldExt> MulChip<F> {
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
         b: Value<F>,
-        cond: Option<F>,
-    ) -> Result<Value<F>, Error> {
+        cond: Value<F>,
+    ) -> Result<(Value<F>, Value<F>), Error> {
         let config = self.config();
 
         let mut c = None;
@@ -88,17 +98,31 @@ impl<F: FieldExt> MulChip<F> {
                 assign_operands!(a, b, region, config);
                 assign_cond!(cond, region, config);
 
-                let value = a.value().and_then(|a| b.value().map(|b| a * b));
+                let (value, overflow) = overflowing!(a, b, move_overflowing_mul);
                 let cell = region.assign_advice(
                     || "lhs * rhs",
                     config.advices[2],
                     0,
                     || value.ok_or(Error::Synthesis),
                 )?;
-                c = Some(
+                let overflow = assign_overflow!(overflow, a.ty(), region, config);
+                let modulus_invert = type_modulus::<F>(&a.ty()).invert().unwrap();
+                let high = a
+                    .value()
+                    .zip(b.value())
+                    .zip(value)
+                    .map(|((lhs, rhs), out)| (lhs * rhs - out) * modulus_invert);
+                region.assign_advice(
+                    || "high",
+                    config.advices[2],
+                    1,
+                    || high.ok_or(Error::Synthesis),
+                )?;
+                c = Some((
                     Value::new_variable(value, Some(cell.cell()), a.ty())
                         .map_err(|_| Error::Synthesis)?,
-                );
+                    overflow,
+                ));
                 Ok(())
             },
         )?;
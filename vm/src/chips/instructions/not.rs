@@ -1,6 +1,7 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::assign_cond;
 use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
 use crate::chips::utilities::Expr;
 use crate::value::Value;
@@ -79,7 +80,7 @@ impl<F: FieldExt> NotChip<F> {
         &self,
         layouter: &mut impl Layouter<F>,
         a: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<Value<F>, Error> {
         let config = self.config();
 
@@ -98,13 +99,7 @@ impl<F: FieldExt> NotChip<F> {
                 )?;
                 region.constrain_equal(a.cell().unwrap(), x.cell())?;
 
-                // assign cond
-                region.assign_advice(
-                    || "cond",
-                    config.advices[2],
-                    0,
-                    || cond.ok_or(Error::Synthesis),
-                )?;
+                assign_cond!(cond, region, column = config.advices[2]);
 
                 let value = match a.value() {
                     Some(a) => {
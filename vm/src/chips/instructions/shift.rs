@@ -0,0 +1,341 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::chips::instructions::Opcode;
+use crate::chips::utilities::{
+    construct_cells, num_of_rows, BitRepresentation, Cell, Expr, RangeCheckChip, RangeCheckConfig,
+};
+use crate::value::Value;
+use crate::{assign_cond, assign_operands};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use logger::prelude::*;
+use movelang::value::{convert_to_field, move_shl, move_shr, MoveValue};
+use std::marker::PhantomData;
+
+/// Shl and Shr split the shifted value into a high part and a low part,
+/// value = hi * x + lo, where x is 2^(n-s) for Shl and 2^s for Shr, and
+/// n is the bit width of the value, s is the shift amount.
+/// Then Shl gives lo * 2^s and Shr gives hi.
+///
+/// 2^s is computed from the bits of s, so s < n holds, a larger s aborts
+/// before the shift and leaves its gate disabled. To make the split
+/// unique, hi, lo and the gaps below are range checked to n bits, which
+/// gives lo < x and hi < 2^n / x, so hi * x + lo < 2^n never wraps around.
+///
+/// layout:
+/// | value  | shift  | out    | cond   |
+/// | hi     | lo     | hi_gap | lo_gap |
+/// | shift bits ...                    |
+/// | partial products of 2^s, 2^(n-s)  |
+#[derive(Clone, Debug)]
+pub struct ShiftConfig<F: FieldExt, const NUM_OF_BYTES: usize, const NUM_OF_SHIFT_BITS: usize> {
+    s_shl: Selector,
+    s_shr: Selector,
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    shift_bits: BitRepresentation<F, NUM_OF_SHIFT_BITS>,
+    products: Vec<Cell<F>>,
+    co_pow: Cell<F>,
+    range_check: RangeCheckConfig<F, NUM_OF_BYTES>,
+    _marker: PhantomData<F>,
+}
+
+pub struct ShiftChip<F: FieldExt, const NUM_OF_BYTES: usize, const NUM_OF_SHIFT_BITS: usize> {
+    config: ShiftConfig<F, NUM_OF_BYTES, NUM_OF_SHIFT_BITS>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const NUM_OF_BYTES: usize, const NUM_OF_SHIFT_BITS: usize> Chip<F>
+    for ShiftChip<F, NUM_OF_BYTES, NUM_OF_SHIFT_BITS>
+{
+    type Config = ShiftConfig<F, NUM_OF_BYTES, NUM_OF_SHIFT_BITS>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+struct ShiftWitness<F: FieldExt> {
+    out: F,
+    hi: F,
+    lo: F,
+    hi_gap: F,
+    lo_gap: F,
+    co_pow: F,
+    products: Vec<F>,
+}
+
+impl<F: FieldExt, const NUM_OF_BYTES: usize, const NUM_OF_SHIFT_BITS: usize>
+    ShiftChip<F, NUM_OF_BYTES, NUM_OF_SHIFT_BITS>
+{
+    const NUM_OF_BITS: usize = NUM_OF_BYTES * 8;
+
+    pub(crate) fn construct(
+        config: <Self as Chip<F>>::Config,
+        _loaded: <Self as Chip<F>>::Loaded,
+    ) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    fn two_pow(exp: usize) -> F {
+        F::from(2).pow(&[exp as u64, 0, 0, 0])
+    }
+
+    pub(crate) fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        range_check: RangeCheckConfig<F, NUM_OF_BYTES>,
+    ) -> <Self as Chip<F>>::Config {
+        let s_shl = meta.selector();
+        let s_shr = meta.selector();
+
+        let bits_offset = 2;
+        let products_offset = bits_offset + num_of_rows(NUM_OF_SHIFT_BITS);
+
+        let mut cells = None;
+        for (name, selector) in [("shl", s_shl), ("shr", s_shr)] {
+            meta.create_gate(name, |meta| {
+                let value = meta.query_advice(advices[0], Rotation::cur());
+                let shift = meta.query_advice(advices[1], Rotation::cur());
+                let out = meta.query_advice(advices[2], Rotation::cur());
+                let cond = meta.query_advice(advices[3], Rotation::cur());
+                let hi = meta.query_advice(advices[0], Rotation::next());
+                let lo = meta.query_advice(advices[1], Rotation::next());
+                let hi_gap = meta.query_advice(advices[2], Rotation::next());
+                let lo_gap = meta.query_advice(advices[3], Rotation::next());
+
+                let shift_bits = BitRepresentation::<F, NUM_OF_SHIFT_BITS>::construct(
+                    meta,
+                    advices,
+                    bits_offset,
+                );
+                let mut products =
+                    construct_cells(meta, advices, products_offset, NUM_OF_SHIFT_BITS + 1);
+                let co_pow = products.pop().expect("products is empty.");
+                let pow = products[NUM_OF_SHIFT_BITS - 1].expression.clone();
+                let s = meta.query_selector(selector) * cond;
+
+                let mut constraints: Vec<Expression<F>> = shift_bits
+                    .bool_constraints()
+                    .into_iter()
+                    .map(|c| s.clone() * c)
+                    .collect();
+                // shift < n, since it is reconstructed from log2(n) bits
+                constraints.push(s.clone() * (shift - shift_bits.expr()));
+                // products[i] = products[i-1] * (bit_i ? 2^(2^i) : 1), the last is 2^shift
+                let mut previous = 1.expr();
+                for (i, product) in products.iter().enumerate() {
+                    let factor = 1.expr() + shift_bits.bit(i) * (Self::two_pow(1 << i) - F::one());
+                    constraints.push(s.clone() * (previous * factor - product.expression.clone()));
+                    previous = product.expression.clone();
+                }
+                // 2^shift * 2^(n-shift) = 2^n
+                constraints.push(
+                    s.clone()
+                        * (pow.clone() * co_pow.expression.clone()
+                            - Expression::Constant(Self::two_pow(Self::NUM_OF_BITS))),
+                );
+
+                let (x, y) = if name == "shl" {
+                    (co_pow.expression.clone(), pow.clone())
+                } else {
+                    (pow.clone(), co_pow.expression.clone())
+                };
+                constraints.extend(vec![
+                    s.clone() * (value - (hi.clone() * x.clone() + lo.clone())),
+                    // lo < x
+                    s.clone() * (lo.clone() + lo_gap + 1.expr() - x),
+                    // hi < y
+                    s.clone() * (hi.clone() + hi_gap + 1.expr() - y),
+                ]);
+                if name == "shl" {
+                    constraints.push(s * (out - lo * pow));
+                } else {
+                    constraints.push(s * (out - hi));
+                }
+
+                cells = Some((shift_bits, products, co_pow));
+                constraints
+            });
+        }
+        let (shift_bits, products, co_pow) = cells.expect("cells is None.");
+
+        ShiftConfig {
+            s_shl,
+            s_shr,
+            advices,
+            shift_bits,
+            products,
+            co_pow,
+            range_check,
+            _marker: PhantomData,
+        }
+    }
+
+    fn witness(
+        opcode: Opcode,
+        a: &Value<F>,
+        b: &Value<F>,
+    ) -> Result<Option<ShiftWitness<F>>, Error> {
+        let l_move: Option<MoveValue> = a.clone().into();
+        let r_move: Option<MoveValue> = b.clone().into();
+        let (l, r) = match (l_move, r_move) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return Ok(None),
+        };
+        let value = a.value().unwrap().get_lower_128();
+        let shift = b.value().unwrap().get_lower_128() as usize;
+        let n = Self::NUM_OF_BITS;
+        // shifting by n or more bits aborts, the shift runs under a false
+        // condition and any witness satisfies the disabled gate
+        if shift >= n {
+            return Ok(Some(ShiftWitness {
+                out: F::zero(),
+                hi: F::zero(),
+                lo: F::zero(),
+                hi_gap: F::zero(),
+                lo_gap: F::zero(),
+                co_pow: F::zero(),
+                products: vec![F::zero(); NUM_OF_SHIFT_BITS],
+            }));
+        }
+        let out = match opcode {
+            Opcode::Shl => move_shl(l, r),
+            Opcode::Shr => move_shr(l, r),
+            _ => unreachable!(),
+        }
+        .map_err(|e| {
+            error!("move shift failed: {:?}", e);
+            Error::Synthesis
+        })?;
+
+        let low_bits = |v: u128, k: usize| if k >= 128 { v } else { v & ((1u128 << k) - 1) };
+        let (hi, lo) = match opcode {
+            Opcode::Shl => (
+                value.checked_shr((n - shift) as u32).unwrap_or(0),
+                low_bits(value, n - shift),
+            ),
+            _ => (value >> shift, low_bits(value, shift)),
+        };
+        let pow = Self::two_pow(shift);
+        let co_pow = Self::two_pow(n - shift);
+        let (x, y) = match opcode {
+            Opcode::Shl => (co_pow, pow),
+            _ => (pow, co_pow),
+        };
+        let hi = F::from_u128(hi);
+        let lo = F::from_u128(lo);
+
+        let mut products = vec![];
+        let mut product = F::one();
+        for i in 0..NUM_OF_SHIFT_BITS {
+            if (shift >> i) & 1 == 1 {
+                product *= Self::two_pow(1 << i);
+            }
+            products.push(product);
+        }
+
+        Ok(Some(ShiftWitness {
+            out: convert_to_field(out),
+            hi,
+            lo,
+            hi_gap: y - hi - F::one(),
+            lo_gap: x - lo - F::one(),
+            co_pow,
+            products,
+        }))
+    }
+
+    pub(crate) fn assign(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        opcode: Opcode,
+        a: Value<F>,
+        b: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let config = self.config();
+
+        let (selector, name) = match opcode {
+            Opcode::Shl => (config.s_shl, "shl"),
+            Opcode::Shr => (config.s_shr, "shr"),
+            _ => unreachable!(),
+        };
+
+        let mut c = None;
+        let mut parts = vec![];
+        layouter.assign_region(
+            || name,
+            |mut region: Region<'_, F>| {
+                selector.enable(&mut region, 0)?;
+
+                assign_operands!(a, b, region, config);
+                assign_cond!(cond, region, config);
+
+                let witness = Self::witness(opcode, &a, &b)?;
+                let value = witness.as_ref().map(|w| w.out);
+                let cell = region.assign_advice(
+                    || name,
+                    config.advices[2],
+                    0,
+                    || value.ok_or(Error::Synthesis),
+                )?;
+
+                parts.clear();
+                let next_row = [
+                    ("hi", witness.as_ref().map(|w| w.hi)),
+                    ("lo", witness.as_ref().map(|w| w.lo)),
+                    ("hi_gap", witness.as_ref().map(|w| w.hi_gap)),
+                    ("lo_gap", witness.as_ref().map(|w| w.lo_gap)),
+                ];
+                for (column, (annotation, v)) in next_row.iter().enumerate() {
+                    let cell = region.assign_advice(
+                        || *annotation,
+                        config.advices[column],
+                        1,
+                        || v.ok_or(Error::Synthesis),
+                    )?;
+                    parts.push(
+                        Value::new_variable(*v, Some(cell.cell()), a.ty())
+                            .map_err(|_| Error::Synthesis)?,
+                    );
+                }
+
+                config.shift_bits.assign(&mut region, 0, b.value())?;
+                for (i, product) in config.products.iter().enumerate() {
+                    product.assign(&mut region, 0, witness.as_ref().map(|w| w.products[i]))?;
+                }
+                config
+                    .co_pow
+                    .assign(&mut region, 0, witness.as_ref().map(|w| w.co_pow))?;
+
+                c = Some(
+                    Value::new_variable(value, Some(cell.cell()), a.ty())
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                Ok(())
+            },
+        )?;
+
+        // hi, lo and the gaps are all in range 2^n
+        let range_check_chip = RangeCheckChip::construct(config.range_check.clone());
+        for part in parts {
+            range_check_chip.assign(layouter, part, cond.clone())?;
+        }
+
+        Ok(c.unwrap())
+    }
+}
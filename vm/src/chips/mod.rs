@@ -2,7 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod chip_tests;
+pub mod condition;
 pub mod conditional_select;
 pub mod evaluation_chip;
 pub mod instructions;
+pub mod poseidon;
+pub mod state_tree;
 pub mod utilities;
+pub mod vector;
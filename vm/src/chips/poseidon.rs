@@ -0,0 +1,431 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::NUM_OF_ADVICE_COLUMNS;
+use crate::value::Value;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use movelang::value::MoveValueType;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+pub const WIDTH: usize = 3;
+pub const FULL_ROUNDS: usize = 8;
+pub const PARTIAL_ROUNDS: usize = 56;
+const ROUNDS: usize = FULL_ROUNDS + PARTIAL_ROUNDS;
+
+/// The Poseidon permutation over a state of 3 elements with the x^5 S-box,
+/// 8 full rounds and 56 partial rounds. The round constants and then the MDS
+/// matrix are generated with the Grain LFSR of the Poseidon paper, like the
+/// reference implementation does, so over the Pasta fields this is the
+/// P128Pow5T3 permutation of `halo2_gadgets`.
+#[derive(Clone, Debug)]
+pub struct Poseidon<F: FieldExt> {
+    round_constants: Vec<[F; WIDTH]>,
+    mds: [[F; WIDTH]; WIDTH],
+}
+
+impl<F: FieldExt> Poseidon<F> {
+    pub fn new() -> Self {
+        let mut grain = Grain::new();
+        let round_constants = (0..ROUNDS)
+            .map(|_| {
+                let mut constants = [F::zero(); WIDTH];
+                for c in constants.iter_mut() {
+                    *c = grain.next_field_element();
+                }
+                constants
+            })
+            .collect();
+        // the Cauchy matrix `1 / (x_i + y_j)` of the first distinct x and y,
+        // the first matrix sampled is secure for these parameters
+        let mds = loop {
+            let values: Vec<F> = (0..2 * WIDTH)
+                .map(|_| grain.next_field_element_without_rejection())
+                .collect();
+            if values
+                .iter()
+                .enumerate()
+                .any(|(i, value)| values[..i].contains(value))
+            {
+                continue;
+            }
+            let (xs, ys) = values.split_at(WIDTH);
+            let mut mds = [[F::zero(); WIDTH]; WIDTH];
+            for (row, x) in mds.iter_mut().zip(xs) {
+                for (m, y) in row.iter_mut().zip(ys) {
+                    *m = (*x + y).invert().unwrap();
+                }
+            }
+            break mds;
+        };
+        Self {
+            round_constants,
+            mds,
+        }
+    }
+
+    fn is_full_round(round: usize) -> bool {
+        !(FULL_ROUNDS / 2..FULL_ROUNDS / 2 + PARTIAL_ROUNDS).contains(&round)
+    }
+
+    fn round(&self, round: usize, state: [F; WIDTH]) -> [F; WIDTH] {
+        let mut sboxed = [F::zero(); WIDTH];
+        for (i, s) in sboxed.iter_mut().enumerate() {
+            let x = state[i] + self.round_constants[round][i];
+            *s = if i == 0 || Self::is_full_round(round) {
+                x.square().square() * x
+            } else {
+                x
+            };
+        }
+        let mut next = [F::zero(); WIDTH];
+        for (i, n) in next.iter_mut().enumerate() {
+            *n = (0..WIDTH).fold(F::zero(), |acc, j| acc + self.mds[i][j] * sboxed[j]);
+        }
+        next
+    }
+
+    pub fn permute(&self, mut state: [F; WIDTH]) -> [F; WIDTH] {
+        for round in 0..ROUNDS {
+            state = self.round(round, state);
+        }
+        state
+    }
+
+    /// The capacity element separates hashes of two elements from other
+    /// uses of the permutation.
+    pub fn capacity() -> F {
+        F::from_u128(2 << 64)
+    }
+
+    pub fn hash2(&self, a: F, b: F) -> F {
+        self.permute([a, b, Self::capacity()])[0]
+    }
+}
+
+impl<F: FieldExt> Default for Poseidon<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The LFSR generating the round constants, seeded with the field size and the
+/// number of rounds.
+struct Grain {
+    state: VecDeque<bool>,
+}
+
+impl Grain {
+    fn new() -> Self {
+        let mut state = VecDeque::with_capacity(80);
+        let mut push = |len: usize, value: u128| {
+            for i in (0..len).rev() {
+                state.push_back((value >> i) & 1 == 1);
+            }
+        };
+        push(2, 1); // prime field
+        push(4, 0); // x^alpha S-box
+        push(12, 255); // field size, rounded up for every field we use
+        push(12, WIDTH as u128);
+        push(10, FULL_ROUNDS as u128);
+        push(10, PARTIAL_ROUNDS as u128);
+        push(30, (1 << 30) - 1);
+        let mut grain = Grain { state };
+        for _ in 0..160 {
+            grain.next_bit();
+        }
+        grain
+    }
+
+    fn next_bit(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.pop_front();
+        self.state.push_back(bit);
+        bit
+    }
+
+    /// A bit is output only if the bit before it is 1.
+    fn next_output_bit(&mut self) -> bool {
+        loop {
+            let keep = self.next_bit();
+            let bit = self.next_bit();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    /// Big endian bits are sampled until they form an element of the field.
+    fn next_field_element<F: FieldExt>(&mut self) -> F {
+        let num_bits = F::NUM_BITS as usize;
+        loop {
+            let mut repr = F::Repr::default();
+            for i in (0..num_bits).rev() {
+                if self.next_output_bit() {
+                    repr.as_mut()[i / 8] |= 1 << (i % 8);
+                }
+            }
+            if let Some(value) = Option::from(F::from_repr(repr)) {
+                return value;
+            }
+        }
+    }
+
+    /// Big endian bits reduced modulo the field, where the reference
+    /// implementation doesn't need uniform elements.
+    fn next_field_element_without_rejection<F: FieldExt>(&mut self) -> F {
+        let num_bits = F::NUM_BITS as usize;
+        let mut bytes = [0u8; 64];
+        for i in (0..num_bits).rev() {
+            if self.next_output_bit() {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+        F::from_bytes_wide(&bytes)
+    }
+}
+
+/// One row per round, a row holds the state before the round and the round
+/// constants added to it, the state after the last round is on the last row:
+///
+/// | s0 | s1 | s2 | rc0 | rc1 | rc2 |
+///
+/// The first input and the output are in the first column.
+#[derive(Clone, Debug)]
+pub struct PoseidonConfig<F: FieldExt> {
+    advices: [Column<Advice>; WIDTH],
+    round_constants: [Column<Fixed>; WIDTH],
+    s_full: Selector,
+    s_partial: Selector,
+    poseidon: Poseidon<F>,
+}
+
+pub struct PoseidonChip<F: FieldExt> {
+    config: PoseidonConfig<F>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> Chip<F> for PoseidonChip<F> {
+    type Config = PoseidonConfig<F>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt> PoseidonChip<F> {
+    pub fn construct(
+        config: <Self as Chip<F>>::Config,
+        _loaded: <Self as Chip<F>>::Loaded,
+    ) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        round_constants: [Column<Fixed>; WIDTH],
+    ) -> <Self as Chip<F>>::Config {
+        let advices = [advices[0], advices[1], advices[2]];
+        let poseidon = Poseidon::new();
+        let s_full = meta.selector();
+        let s_partial = meta.selector();
+
+        let pow5 = |x: Expression<F>| {
+            let x2 = x.clone() * x.clone();
+            x2.clone() * x2 * x
+        };
+        for (name, selector, full) in [
+            ("poseidon full round", s_full, true),
+            ("poseidon partial round", s_partial, false),
+        ] {
+            let mds = poseidon.mds;
+            meta.create_gate(name, |meta| {
+                let selector = meta.query_selector(selector);
+                let sboxed: Vec<Expression<F>> = (0..WIDTH)
+                    .map(|i| {
+                        let x = meta.query_advice(advices[i], Rotation::cur())
+                            + meta.query_fixed(round_constants[i], Rotation::cur());
+                        if i == 0 || full {
+                            pow5(x)
+                        } else {
+                            x
+                        }
+                    })
+                    .collect();
+                (0..WIDTH)
+                    .map(|i| {
+                        let next = meta.query_advice(advices[i], Rotation::next());
+                        let mixed = (0..WIDTH).fold(Expression::Constant(F::zero()), |acc, j| {
+                            acc + Expression::Constant(mds[i][j]) * sboxed[j].clone()
+                        });
+                        selector.clone() * (mixed - next)
+                    })
+                    .collect::<Vec<_>>()
+            });
+        }
+
+        PoseidonConfig {
+            advices,
+            round_constants,
+            s_full,
+            s_partial,
+            poseidon,
+        }
+    }
+
+    pub fn poseidon(&self) -> &Poseidon<F> {
+        &self.config.poseidon
+    }
+
+    /// Hashes `a` and `b`, `capacity` must be the constant `Poseidon::capacity`.
+    pub fn hash2(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+        capacity: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let config = self.config();
+
+        let mut out = None;
+        layouter.assign_region(
+            || "poseidon",
+            |mut region: Region<'_, F>| {
+                let mut state = [a.value(), b.value(), capacity.value()];
+                for (i, input) in [&a, &b, &capacity].iter().enumerate() {
+                    let cell = region.assign_advice(
+                        || "input",
+                        config.advices[i],
+                        0,
+                        || state[i].ok_or(Error::Synthesis),
+                    )?;
+                    region.constrain_equal(input.cell().ok_or(Error::Synthesis)?, cell.cell())?;
+                }
+                let mut output = None;
+                for round in 0..ROUNDS {
+                    if Poseidon::<F>::is_full_round(round) {
+                        config.s_full.enable(&mut region, round)?;
+                    } else {
+                        config.s_partial.enable(&mut region, round)?;
+                    }
+                    for i in 0..WIDTH {
+                        let constant = config.poseidon.round_constants[round][i];
+                        region.assign_fixed(
+                            || "round constant",
+                            config.round_constants[i],
+                            round,
+                            || Ok(constant),
+                        )?;
+                    }
+                    let next = match state {
+                        [Some(s0), Some(s1), Some(s2)] => {
+                            config.poseidon.round(round, [s0, s1, s2]).map(Some)
+                        }
+                        _ => [None; WIDTH],
+                    };
+                    state = next;
+                    for (i, value) in state.iter().enumerate() {
+                        let cell = region.assign_advice(
+                            || "state",
+                            config.advices[i],
+                            round + 1,
+                            || value.ok_or(Error::Synthesis),
+                        )?;
+                        if i == 0 {
+                            output = Some(cell.cell());
+                        }
+                    }
+                }
+                // a hash is a field element, the type is never checked
+                out = Some(
+                    Value::new_variable(state[0], output, MoveValueType::U128)
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                Ok(())
+            },
+        )?;
+        Ok(out.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test_p128pow5t3_constants() {
+        // the first round constant and MDS row of P128Pow5T3 over Fp
+        let poseidon = Poseidon::<Fp>::new();
+        assert_eq!(
+            poseidon.round_constants[0][0],
+            Fp::from_raw([
+                0x5753_8c25_9642_6303,
+                0x4e71_162f_3100_3b70,
+                0x353f_628f_76d1_10f3,
+                0x360d_7470_611e_473d,
+            ])
+        );
+        assert_eq!(
+            poseidon.mds[0],
+            [
+                Fp::from_raw([
+                    0x323f_2486_d7e1_1b63,
+                    0x97d7_a0ab_2385_0b56,
+                    0xb3d5_9fbd_c8c9_ead4,
+                    0x0ab5_e5b8_74a6_8de7,
+                ]),
+                Fp::from_raw([
+                    0x8eca_5596_e996_ab5e,
+                    0x240d_4a7c_bf73_5736,
+                    0x293f_0f0d_886c_7954,
+                    0x3191_6628_e58a_5abb,
+                ]),
+                Fp::from_raw([
+                    0x19d1_cf25_d8e8_345d,
+                    0xa0a3_b71a_5fb1_5735,
+                    0xd803_952b_bb36_4fdf,
+                    0x07c0_45d5_f5e9_e5a6,
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash2() {
+        let poseidon = Poseidon::<Fp>::new();
+        assert_eq!(
+            poseidon.permute([Fp::zero(), Fp::one(), Fp::from(2)])[0],
+            Fp::from_raw([
+                0xaeb1_bc02_4aec_a456,
+                0xf7e6_9a71_d0b6_42a0,
+                0x94ef_b364_f966_240f,
+                0x2a52_6acd_0b64_b453,
+            ])
+        );
+        assert_eq!(
+            poseidon.hash2(Fp::one(), Fp::from(2)),
+            Fp::from_raw([
+                0x8975_dc07_94bd_e34c,
+                0x3b46_00ce_9023_c683,
+                0x030a_d4b0_6e79_82eb,
+                0x3555_a5ec_b43c_9998,
+            ])
+        );
+    }
+}
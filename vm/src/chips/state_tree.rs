@@ -0,0 +1,317 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::{EvaluationChip, NUM_OF_ADVICE_COLUMNS};
+use crate::chips::instructions::Opcode;
+use crate::chips::poseidon::{Poseidon, PoseidonChip};
+use crate::chips::utilities::Expr;
+use crate::state_tree::{StateTree, TREE_DEPTH};
+use crate::value::Value;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use logger::prelude::*;
+use movelang::value::MoveValueType;
+
+/// Splits the key of a resource into its low `TREE_DEPTH` bits and the rest,
+/// one row per bit from the most significant one:
+///
+/// | bit | acc | high | key |
+///
+/// `acc` accumulates the bits, the last row holds `key = acc + high * 2^TREE_DEPTH`.
+#[derive(Clone, Debug)]
+pub struct KeyBitsConfig {
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    s_first_bit: Selector,
+    s_bit: Selector,
+    s_split: Selector,
+}
+
+impl KeyBitsConfig {
+    pub fn configure<F: FieldExt>(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    ) -> Self {
+        let s_first_bit = meta.selector();
+        let s_bit = meta.selector();
+        let s_split = meta.selector();
+
+        meta.create_gate("first key bit", |meta| {
+            let s = meta.query_selector(s_first_bit);
+            let bit = meta.query_advice(advices[0], Rotation::cur());
+            let acc = meta.query_advice(advices[1], Rotation::cur());
+            vec![
+                s.clone() * bit.clone() * (1.expr() - bit.clone()),
+                s * (acc - bit),
+            ]
+        });
+        meta.create_gate("key bit", |meta| {
+            let s = meta.query_selector(s_bit);
+            let bit = meta.query_advice(advices[0], Rotation::cur());
+            let acc = meta.query_advice(advices[1], Rotation::cur());
+            let prev = meta.query_advice(advices[1], Rotation::prev());
+            vec![
+                s.clone() * bit.clone() * (1.expr() - bit.clone()),
+                s * (acc - prev * 2.expr() - bit),
+            ]
+        });
+        meta.create_gate("split key", |meta| {
+            let s = meta.query_selector(s_split);
+            let acc = meta.query_advice(advices[1], Rotation::cur());
+            let high = meta.query_advice(advices[2], Rotation::cur());
+            let key = meta.query_advice(advices[3], Rotation::cur());
+            let shift = Expression::Constant(F::from_u128(1 << TREE_DEPTH));
+            vec![s * (acc + high * shift - key)]
+        });
+
+        Self {
+            advices,
+            s_first_bit,
+            s_bit,
+            s_split,
+        }
+    }
+}
+
+/// The gadgets committing to the global state, see `crate::state_tree`.
+impl<F: FieldExt> EvaluationChip<F> {
+    /// Poseidon hash of two values. The hash is typed as u128 but may be any
+    /// field element, it is never range checked.
+    pub fn hash2(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let capacity = self.load_constant(
+            layouter.namespace(|| "load capacity"),
+            Poseidon::<F>::capacity(),
+            MoveValueType::U128,
+        )?;
+        PoseidonChip::construct(self.config().poseidon_config.clone(), ()).hash2(
+            layouter.namespace(|| "hash"),
+            a,
+            b,
+            capacity,
+        )
+    }
+
+    /// `hash2(...hash2(hash2(0, v0), v1)..., vn)`
+    pub fn hash_values(
+        &self,
+        mut layouter: impl Layouter<F>,
+        values: Vec<Value<F>>,
+    ) -> Result<Value<F>, Error> {
+        let mut hash = self.load_constant(
+            layouter.namespace(|| "load zero"),
+            F::zero(),
+            MoveValueType::U128,
+        )?;
+        for (i, value) in values.into_iter().enumerate() {
+            hash = self.hash2(layouter.namespace(|| format!("hash #{}", i)), hash, value)?;
+        }
+        Ok(hash)
+    }
+
+    /// The elements hashed for a value: the fields of a struct in order, the
+    /// length of a vector followed by every slot, where slots past the end
+    /// count as zeros.
+    pub fn flatten_value(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: &Value<F>,
+    ) -> Result<Vec<Value<F>>, Error> {
+        match value {
+            Value::Struct(fields) => {
+                let mut out = vec![];
+                for (i, field) in fields.iter().enumerate() {
+                    out.extend(self.flatten_value(
+                        &mut layouter.namespace(|| format!("field #{}", i)),
+                        field,
+                    )?);
+                }
+                Ok(out)
+            }
+            Value::Vector(vector) => {
+                let one = self.load_constant(
+                    layouter.namespace(|| "load condition"),
+                    F::one(),
+                    MoveValueType::Bool,
+                )?;
+                let zero = self.load_constant(
+                    layouter.namespace(|| "load zero"),
+                    F::zero(),
+                    vector.elem_ty.clone(),
+                )?;
+                let mut out = vec![(*vector.len).clone()];
+                for (k, slot) in vector.slots.iter().enumerate() {
+                    // a slot which was never written is past the end
+                    if matches!(slot, Value::Invalid) {
+                        out.push(zero.clone());
+                        continue;
+                    }
+                    let k_value = self.load_constant(
+                        layouter.namespace(|| format!("load slot index #{}", k)),
+                        F::from_u128(k as u128),
+                        MoveValueType::U64,
+                    )?;
+                    let in_bounds = self.binary_op(
+                        layouter.namespace(|| format!("{} < length", k)),
+                        Opcode::Lt,
+                        k_value,
+                        (*vector.len).clone(),
+                        one.clone(),
+                    )?;
+                    out.push(self.conditional_select(
+                        layouter.namespace(|| format!("mask slot #{}", k)),
+                        slot.clone(),
+                        zero.clone(),
+                        in_bounds,
+                    )?);
+                }
+                Ok(out)
+            }
+            Value::Constant(_) | Value::Variable(_) => Ok(vec![value.clone()]),
+            _ => {
+                error!("can not hash {:?}", value);
+                Err(Error::Synthesis)
+            }
+        }
+    }
+
+    /// The low `TREE_DEPTH` bits of the key of a resource, the least
+    /// significant one first.
+    pub fn key_bits(
+        &self,
+        mut layouter: impl Layouter<F>,
+        key: Value<F>,
+    ) -> Result<Vec<Value<F>>, Error> {
+        let config = &self.config().key_bits_config;
+        // a key of 2^254 or above has no witness, see `StateTree::split_key`
+        let split = key.value().and_then(StateTree::split_key);
+        let low = split.map(|(low, _)| low);
+        let high = split.map(|(_, high)| F::from_u128(high));
+
+        let mut bits = vec![];
+        let mut high_value = None;
+        layouter.assign_region(
+            || "key bits",
+            |mut region| {
+                bits.clear();
+                let mut acc = Some(F::zero());
+                for row in 0..TREE_DEPTH {
+                    if row == 0 {
+                        config.s_first_bit.enable(&mut region, row)?;
+                    } else {
+                        config.s_bit.enable(&mut region, row)?;
+                    }
+                    let bit = low.map(|low| {
+                        if (low >> (TREE_DEPTH - 1 - row)) & 1 == 1 {
+                            F::one()
+                        } else {
+                            F::zero()
+                        }
+                    });
+                    acc = acc.zip(bit).map(|(acc, bit)| acc.double() + bit);
+                    let bit_cell = region.assign_advice(
+                        || "bit",
+                        config.advices[0],
+                        row,
+                        || bit.ok_or(Error::Synthesis),
+                    )?;
+                    region.assign_advice(
+                        || "acc",
+                        config.advices[1],
+                        row,
+                        || acc.ok_or(Error::Synthesis),
+                    )?;
+                    bits.push(
+                        Value::new_variable(bit, Some(bit_cell.cell()), MoveValueType::Bool)
+                            .map_err(|_| Error::Synthesis)?,
+                    );
+                }
+                let row = TREE_DEPTH - 1;
+                config.s_split.enable(&mut region, row)?;
+                let high_cell = region.assign_advice(
+                    || "high",
+                    config.advices[2],
+                    row,
+                    || high.ok_or(Error::Synthesis),
+                )?;
+                let key_cell = region.assign_advice(
+                    || "key",
+                    config.advices[3],
+                    row,
+                    || key.value().ok_or(Error::Synthesis),
+                )?;
+                region.constrain_equal(key.cell().ok_or(Error::Synthesis)?, key_cell.cell())?;
+                high_value = Some(
+                    Value::new_variable(high, Some(high_cell.cell()), MoveValueType::U128)
+                        .map_err(|_| Error::Synthesis)?,
+                );
+                Ok(())
+            },
+        )?;
+        // high < 2^128, so acc + high * 2^TREE_DEPTH < 2^254 can't wrap around
+        // the field, the bits are unique to the key
+        let one = self.load_constant(
+            layouter.namespace(|| "load condition"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        self.range_check(&mut layouter, high_value.unwrap(), one)?;
+        bits.reverse();
+        Ok(bits)
+    }
+
+    /// Constrains `a` and `b` to be equal.
+    pub fn assert_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Value<F>,
+        b: Value<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "assert equal",
+            |mut region| {
+                region.constrain_equal(
+                    a.cell().ok_or(Error::Synthesis)?,
+                    b.cell().ok_or(Error::Synthesis)?,
+                )
+            },
+        )
+    }
+
+    /// The root of the tree holding `leaf`, hashed up with `siblings` from
+    /// the leaf level. A bit of the leaf index is 1 if the node is a right
+    /// child.
+    pub fn merkle_root(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: Value<F>,
+        bits: &[Value<F>],
+        siblings: &[Value<F>],
+    ) -> Result<Value<F>, Error> {
+        let mut node = leaf;
+        for (level, (bit, sibling)) in bits.iter().zip(siblings).enumerate() {
+            let mut layouter = layouter.namespace(|| format!("level #{}", level));
+            let left = self.conditional_select(
+                layouter.namespace(|| "left"),
+                sibling.clone(),
+                node.clone(),
+                bit.clone(),
+            )?;
+            let right = self.conditional_select(
+                layouter.namespace(|| "right"),
+                node,
+                sibling.clone(),
+                bit.clone(),
+            )?;
+            node = self.hash2(layouter.namespace(|| "hash"), left, right)?;
+        }
+        Ok(node)
+    }
+}
@@ -6,16 +6,39 @@ use crate::value::Value;
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{AssignedCell, Layouter, Region},
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, VirtualCells},
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, Selector, TableColumn, VirtualCells,
+    },
     poly::Rotation,
 };
 use logger::prelude::*;
+use movelang::value::MoveValueType;
 use std::convert::TryInto;
 
 pub const NUM_OF_BYTES_U8: usize = 1;
 pub const NUM_OF_BYTES_U64: usize = 8;
 pub const NUM_OF_BYTES_U128: usize = 16;
 
+pub const NUM_OF_BITS_U8: usize = 8;
+pub const NUM_OF_BITS_U64: usize = 64;
+pub const NUM_OF_BITS_U128: usize = 128;
+
+// a valid shift amount is less than the bit width, log2(NUM_OF_BITS) bits are enough
+pub const NUM_OF_SHIFT_BITS_U8: usize = 3;
+pub const NUM_OF_SHIFT_BITS_U64: usize = 6;
+pub const NUM_OF_SHIFT_BITS_U128: usize = 7;
+
+/// 2^bits of an integer type, arithmetic results wrap around it.
+pub fn type_modulus<F: FieldExt>(ty: &MoveValueType) -> F {
+    let bits = match ty {
+        MoveValueType::U8 => NUM_OF_BITS_U8,
+        MoveValueType::U64 => NUM_OF_BITS_U64,
+        MoveValueType::U128 => NUM_OF_BITS_U128,
+        _ => unreachable!(),
+    };
+    F::from(2).pow_vartime([bits as u64])
+}
+
 #[derive(Clone, Debug)]
 pub struct Cell<F: FieldExt> {
     pub expression: Expression<F>,
@@ -52,6 +75,28 @@ impl<F: FieldExt> Cell<F> {
     }
 }
 
+/// Lay out `count` cells row by row across the advice columns, starting at
+/// rotation `offset`.
+pub fn construct_cells<F: FieldExt>(
+    meta: &mut VirtualCells<F>,
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    offset: usize,
+    count: usize,
+) -> Vec<Cell<F>> {
+    let mut cells = Vec::new();
+    for i in 0..count {
+        let column_index = i % NUM_OF_ADVICE_COLUMNS;
+        let rotation = i / NUM_OF_ADVICE_COLUMNS + offset;
+        cells.push(Cell::new(meta, advices[column_index], rotation as i32))
+    }
+    cells
+}
+
+/// Number of rows taken by `count` cells laid out by `construct_cells`.
+pub const fn num_of_rows(count: usize) -> usize {
+    count.div_ceil(NUM_OF_ADVICE_COLUMNS)
+}
+
 pub(crate) trait Expr<F: FieldExt> {
     fn expr(&self) -> Expression<F>;
 }
@@ -76,13 +121,7 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> ByteRepresentation<F, NUM_OF_BYTES>
         advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
         offset: usize,
     ) -> Self {
-        let mut cells = Vec::new();
-        for i in 0..NUM_OF_BYTES {
-            let column_index = i % NUM_OF_ADVICE_COLUMNS;
-            let rotation = i / NUM_OF_ADVICE_COLUMNS + offset;
-            cells.push(Cell::new(meta, advices[column_index], rotation as i32))
-        }
-        cells.into()
+        construct_cells(meta, advices, offset, NUM_OF_BYTES).into()
     }
 
     pub fn assign(
@@ -142,15 +181,161 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> From<Vec<Cell<F>>>
     }
 }
 
+/// A fixed table of the 256 bytes. Every advice cell of a row with `s_byte`
+/// enabled is looked up in it, so a byte representation laid out on such
+/// rows holds bytes.
+#[derive(Clone, Debug)]
+pub struct ByteTableConfig {
+    s_byte: Selector,
+    advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+    table: TableColumn,
+}
+
+impl ByteTableConfig {
+    pub fn configure<F: FieldExt>(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        table: TableColumn,
+    ) -> Self {
+        // selectors in lookups can't be combined with other selectors
+        let s_byte = meta.complex_selector();
+        for column in advices {
+            meta.lookup(|meta| {
+                let s_byte = meta.query_selector(s_byte);
+                let cell = meta.query_advice(column, Rotation::cur());
+                // 0 is in the table, rows without s_byte always match
+                vec![(s_byte * cell, table)]
+            });
+        }
+
+        ByteTableConfig {
+            s_byte,
+            advices,
+            table,
+        }
+    }
+
+    pub fn load<F: FieldExt>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                for byte in 0..256 {
+                    table.assign_cell(|| "byte", self.table, byte, || Ok(F::from(byte as u64)))?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Looks up the cells of `bytes` in the table. The rows they are laid out
+    /// on must hold nothing else, the cells of the last row left over are
+    /// assigned 0, so that no other region can use them.
+    pub fn enable<F: FieldExt>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        bytes: &[Cell<F>],
+    ) -> Result<(), Error> {
+        let first_row = (offset as i32 + bytes[0].rotation.0) as usize;
+        for row in 0..num_of_rows(bytes.len()) {
+            self.s_byte.enable(region, first_row + row)?;
+        }
+        for index in bytes.len()..num_of_rows(bytes.len()) * NUM_OF_ADVICE_COLUMNS {
+            region.assign_advice(
+                || "byte padding",
+                self.advices[index % NUM_OF_ADVICE_COLUMNS],
+                first_row + index / NUM_OF_ADVICE_COLUMNS,
+                || Ok(F::zero()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The selector of each lookup, one per advice column.
+    pub(crate) fn lookup_selectors(&self) -> Vec<Selector> {
+        vec![self.s_byte; NUM_OF_ADVICE_COLUMNS]
+    }
+}
+
+/// Holds the lower NUM_OF_BITS bits of a field element, one cell per bit,
+/// in little-endian order.
+#[derive(Clone, Debug)]
+pub struct BitRepresentation<F: FieldExt, const NUM_OF_BITS: usize>(
+    pub(crate) [Cell<F>; NUM_OF_BITS],
+);
+
+impl<F: FieldExt, const NUM_OF_BITS: usize> BitRepresentation<F, NUM_OF_BITS> {
+    pub fn construct(
+        meta: &mut VirtualCells<F>,
+        advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        offset: usize,
+    ) -> Self {
+        let cells = construct_cells(meta, advices, offset, NUM_OF_BITS);
+        let bits: [Cell<F>; NUM_OF_BITS] = cells.try_into().unwrap_or_else(|v: Vec<Cell<F>>| {
+            panic!(
+                "Expected a Vec of length {} but it was {}",
+                NUM_OF_BITS,
+                v.len()
+            )
+        });
+        BitRepresentation(bits)
+    }
+
+    pub fn assign(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        value: Option<F>,
+    ) -> Result<(), Error> {
+        let bytes: Option<[u8; 32]> = value.map(|v| {
+            v.to_repr()
+                .as_ref()
+                .try_into()
+                .expect("Field fits into 256 bits")
+        });
+        for (index, cell) in self.0.iter().enumerate() {
+            let bit = bytes.map(|b| F::from(((b[index / 8] >> (index % 8)) & 1) as u64));
+            cell.assign(region, offset, bit)?;
+        }
+        Ok(())
+    }
+
+    pub fn bit(&self, index: usize) -> Expression<F> {
+        self.0[index].expression.clone()
+    }
+
+    /// Constraints that every cell holds 0 or 1.
+    pub fn bool_constraints(&self) -> Vec<Expression<F>> {
+        self.0
+            .iter()
+            .map(|bit| bit.expression.clone() * (1.expr() - bit.expression.clone()))
+            .collect()
+    }
+}
+
+impl<F: FieldExt, const NUM_OF_BITS: usize> Expr<F> for BitRepresentation<F, NUM_OF_BITS> {
+    fn expr(&self) -> Expression<F> {
+        let mut value = 0.expr();
+        let mut multiplier = F::one();
+
+        for bit in self.0.iter() {
+            value = value + bit.expression.clone() * multiplier;
+            multiplier = multiplier.double();
+        }
+        value
+    }
+}
+
 /// Reconstruct a value from the input value's byte representation, if the
 /// reconstructed value equals to the input value, then the input value is
-/// in the given range.
+/// in the given range. The bytes are looked up in the byte table.
 #[derive(Clone, Debug)]
 pub struct RangeCheckConfig<F: FieldExt, const NUM_OF_BYTES: usize> {
     pub(crate) s_range: Selector,
     pub(crate) cond_cell: Cell<F>,
     pub(crate) value_cell: Cell<F>,
     pub(crate) cells: ByteRepresentation<F, NUM_OF_BYTES>,
+    pub(crate) byte_table: ByteTableConfig,
 }
 
 pub struct RangeCheckChip<F: FieldExt, const NUM_OF_BYTES: usize> {
@@ -165,6 +350,7 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> RangeCheckChip<F, NUM_OF_BYTES> {
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         advices: [Column<Advice>; NUM_OF_ADVICE_COLUMNS],
+        byte_table: ByteTableConfig,
     ) -> RangeCheckConfig<F, NUM_OF_BYTES> {
         let mut cells = None;
         let mut cond_cell = None;
@@ -188,6 +374,7 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> RangeCheckChip<F, NUM_OF_BYTES> {
             cond_cell: cond_cell.unwrap(),
             value_cell: value_cell.unwrap(),
             cells: cells.unwrap(),
+            byte_table,
         }
     }
 
@@ -195,13 +382,14 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> RangeCheckChip<F, NUM_OF_BYTES> {
         &self,
         layouter: &mut impl Layouter<F>,
         input_value: Value<F>,
-        cond: Option<F>,
+        cond: Value<F>,
     ) -> Result<(), Error> {
         layouter.assign_region(
             || "range check",
             |mut region: Region<'_, F>| {
                 self.config.s_range.enable(&mut region, 0)?;
-                self.config.cond_cell.assign(&mut region, 0, cond)?;
+                let cond_cell = self.config.cond_cell.assign(&mut region, 0, cond.value())?;
+                region.constrain_equal(cond.cell().ok_or(Error::Synthesis)?, cond_cell.cell())?;
                 let value = self
                     .config
                     .value_cell
@@ -211,6 +399,9 @@ impl<F: FieldExt, const NUM_OF_BYTES: usize> RangeCheckChip<F, NUM_OF_BYTES> {
                 self.config
                     .cells
                     .assign(&mut region, 0, input_value.value())?;
+                self.config
+                    .byte_table
+                    .enable(&mut region, 0, &self.config.cells.0)?;
                 Ok(())
             },
         )?;
@@ -0,0 +1,346 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::EvaluationChip;
+use crate::chips::instructions::Opcode;
+use crate::value::{FVector, Value};
+use halo2_proofs::{arithmetic::FieldExt, circuit::Layouter, plonk::Error};
+use movelang::value::MoveValueType;
+
+/// Indexed access to bounded vectors. An index is compared with every slot,
+/// so an access costs a few regions per slot up to the capacity.
+impl<F: FieldExt> EvaluationChip<F> {
+    /// Loads a private vector of `capacity` slots, slots past the length are
+    /// loaded as zeros. The length must not exceed the capacity.
+    pub fn load_private_vector(
+        &self,
+        mut layouter: impl Layouter<F>,
+        values: Option<Vec<F>>,
+        elem_ty: MoveValueType,
+        capacity: usize,
+    ) -> Result<Value<F>, Error> {
+        let len = values
+            .as_ref()
+            .map(|values| F::from_u128(values.len() as u128));
+        let len = self.load_private(
+            layouter.namespace(|| "load vector length"),
+            len,
+            MoveValueType::U64,
+        )?;
+        let mut slots = Vec::with_capacity(capacity);
+        for i in 0..capacity {
+            let value = values
+                .as_ref()
+                .map(|values| values.get(i).cloned().unwrap_or_else(F::zero));
+            slots.push(self.load_private(
+                layouter.namespace(|| format!("load vector slot #{}", i)),
+                value,
+                elem_ty.clone(),
+            )?);
+        }
+
+        // inputs are loaded before any block runs
+        let one = self.load_constant(
+            layouter.namespace(|| "load condition"),
+            F::one(),
+            MoveValueType::Bool,
+        )?;
+        let capacity = self.load_constant(
+            layouter.namespace(|| "load capacity"),
+            F::from_u128(capacity as u128),
+            MoveValueType::U64,
+        )?;
+        let fits = self.binary_op(
+            layouter.namespace(|| "length <= capacity"),
+            Opcode::Le,
+            len.clone(),
+            capacity,
+            one.clone(),
+        )?;
+        let (_, too_long) =
+            self.branch_condition(layouter.namespace(|| "vector length"), one, fits)?;
+        self.abort(layouter.namespace(|| "abort"), too_long)?;
+
+        Ok(Value::new_vector(slots, len, elem_ty))
+    }
+
+    /// The element at `index`. An index out of bounds aborts.
+    pub fn vector_borrow(
+        &self,
+        mut layouter: impl Layouter<F>,
+        vector: &FVector<F>,
+        index: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let in_bounds = self.check_index(&mut layouter, vector, index.clone(), cond)?;
+        let hits = self.slot_hits(&mut layouter, vector, index, in_bounds)?;
+        self.read_slot(&mut layouter, vector, &hits)
+    }
+
+    /// Appends `elem`. Pushing to a full vector aborts.
+    pub fn vector_push_back(
+        &self,
+        mut layouter: impl Layouter<F>,
+        vector: FVector<F>,
+        elem: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let capacity = self.load_constant(
+            layouter.namespace(|| "load capacity"),
+            F::from_u128(vector.capacity() as u128),
+            MoveValueType::U64,
+        )?;
+        let full = self.binary_op(
+            layouter.namespace(|| "length == capacity"),
+            Opcode::Eq,
+            (*vector.len).clone(),
+            capacity,
+            cond.clone(),
+        )?;
+        let (abort_cond, not_full) =
+            self.branch_condition(layouter.namespace(|| "vector full"), cond, full)?;
+        self.abort(layouter.namespace(|| "abort"), abort_cond)?;
+
+        let hits = self.slot_hits(
+            &mut layouter,
+            &vector,
+            (*vector.len).clone(),
+            not_full.clone(),
+        )?;
+        let len = self.add_to_length(&mut layouter, &vector, Opcode::Add, not_full)?;
+        let slots = vector
+            .slots
+            .into_iter()
+            .zip(hits)
+            .enumerate()
+            .map(|(i, (slot, hit))| match slot {
+                // a slot which was never written is past the end
+                Value::Invalid => Ok(elem.clone()),
+                slot => self.conditional_select(
+                    layouter.namespace(|| format!("write slot #{}", i)),
+                    elem.clone(),
+                    slot,
+                    hit,
+                ),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Value::new_vector(slots, len, vector.elem_ty))
+    }
+
+    /// Removes the last element, returns the vector and the element. Popping
+    /// from an empty vector aborts.
+    pub fn vector_pop_back(
+        &self,
+        mut layouter: impl Layouter<F>,
+        vector: FVector<F>,
+        cond: Value<F>,
+    ) -> Result<(Value<F>, Value<F>), Error> {
+        let zero = self.load_constant(
+            layouter.namespace(|| "load zero"),
+            F::zero(),
+            MoveValueType::U64,
+        )?;
+        let empty = self.binary_op(
+            layouter.namespace(|| "length == 0"),
+            Opcode::Eq,
+            (*vector.len).clone(),
+            zero,
+            cond.clone(),
+        )?;
+        let (abort_cond, not_empty) =
+            self.branch_condition(layouter.namespace(|| "vector empty"), cond, empty)?;
+        self.abort(layouter.namespace(|| "abort"), abort_cond)?;
+
+        let len = self.add_to_length(&mut layouter, &vector, Opcode::Sub, not_empty.clone())?;
+        let hits = self.slot_hits(&mut layouter, &vector, len.clone(), not_empty)?;
+        let elem = self.read_slot(&mut layouter, &vector, &hits)?;
+        Ok((Value::new_vector(vector.slots, len, vector.elem_ty), elem))
+    }
+
+    /// Swaps the elements at `i` and `j`. An index out of bounds aborts.
+    pub fn vector_swap(
+        &self,
+        mut layouter: impl Layouter<F>,
+        vector: FVector<F>,
+        i: Value<F>,
+        j: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let i_in_bounds = self.check_index(&mut layouter, &vector, i.clone(), cond.clone())?;
+        let j_in_bounds = self.check_index(&mut layouter, &vector, j.clone(), cond)?;
+        let i_hits = self.slot_hits(&mut layouter, &vector, i, i_in_bounds)?;
+        let j_hits = self.slot_hits(&mut layouter, &vector, j, j_in_bounds)?;
+        let at_i = self.read_slot(&mut layouter, &vector, &i_hits)?;
+        let at_j = self.read_slot(&mut layouter, &vector, &j_hits)?;
+
+        let mut slots = Vec::with_capacity(vector.capacity());
+        for (k, slot) in vector.slots.into_iter().enumerate() {
+            // an index in bounds never hits an invalid slot
+            if matches!(slot, Value::Invalid) {
+                slots.push(slot);
+                continue;
+            }
+            let slot = self.conditional_select(
+                layouter.namespace(|| format!("write slot #{} from i", k)),
+                at_i.clone(),
+                slot,
+                j_hits[k].clone(),
+            )?;
+            let slot = self.conditional_select(
+                layouter.namespace(|| format!("write slot #{} from j", k)),
+                at_j.clone(),
+                slot,
+                i_hits[k].clone(),
+            )?;
+            slots.push(slot);
+        }
+        Ok(Value::new_vector(slots, *vector.len, vector.elem_ty))
+    }
+
+    /// An index out of bounds aborts. Returns the condition under which the
+    /// index is in bounds.
+    fn check_index(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        vector: &FVector<F>,
+        index: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let in_bounds = self.binary_op(
+            layouter.namespace(|| "index < length"),
+            Opcode::Lt,
+            index,
+            (*vector.len).clone(),
+            cond.clone(),
+        )?;
+        let (in_bounds, out_of_bounds) =
+            self.branch_condition(layouter.namespace(|| "index bounds"), cond, in_bounds)?;
+        self.abort(layouter.namespace(|| "abort"), out_of_bounds)?;
+        Ok(in_bounds)
+    }
+
+    /// `cond * (index == k)` for every slot k.
+    fn slot_hits(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        vector: &FVector<F>,
+        index: Value<F>,
+        cond: Value<F>,
+    ) -> Result<Vec<Value<F>>, Error> {
+        let mut hits = Vec::with_capacity(vector.capacity());
+        for k in 0..vector.capacity() {
+            let k_value = self.load_constant(
+                layouter.namespace(|| format!("load slot index #{}", k)),
+                F::from_u128(k as u128),
+                MoveValueType::U64,
+            )?;
+            let is_k = self.binary_op(
+                layouter.namespace(|| format!("index == {}", k)),
+                Opcode::Eq,
+                index.clone(),
+                k_value,
+                cond.clone(),
+            )?;
+            let (hit, _) = self.branch_condition(
+                layouter.namespace(|| format!("hit slot #{}", k)),
+                cond.clone(),
+                is_k,
+            )?;
+            hits.push(hit);
+        }
+        Ok(hits)
+    }
+
+    /// The slot hit by the index. Invalid slots are skipped, an index in
+    /// bounds never hits them.
+    fn read_slot(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        vector: &FVector<F>,
+        hits: &[Value<F>],
+    ) -> Result<Value<F>, Error> {
+        let mut out: Option<Value<F>> = None;
+        for (k, (slot, hit)) in vector.slots.iter().zip(hits).enumerate() {
+            if matches!(slot, Value::Invalid) {
+                continue;
+            }
+            out = Some(match out {
+                None => slot.clone(),
+                Some(out) => self.conditional_select(
+                    layouter.namespace(|| format!("read slot #{}", k)),
+                    slot.clone(),
+                    out,
+                    hit.clone(),
+                )?,
+            });
+        }
+        match out {
+            Some(out) => Ok(out),
+            // the access aborts, the element is never used by a block which runs
+            None => self.placeholder(layouter, &vector.elem_ty, vector.capacity()),
+        }
+    }
+
+    /// A value of type `ty` standing in for an element which can't be read.
+    /// Vectors are empty, structs are invalid like a slot never written.
+    fn placeholder(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        ty: &MoveValueType,
+        capacity: usize,
+    ) -> Result<Value<F>, Error> {
+        match ty {
+            MoveValueType::Bool
+            | MoveValueType::U8
+            | MoveValueType::U64
+            | MoveValueType::U128
+            | MoveValueType::Address
+            | MoveValueType::Signer => self.load_constant(
+                layouter.namespace(|| "load placeholder"),
+                F::zero(),
+                ty.clone(),
+            ),
+            MoveValueType::Vector(elem_ty) => {
+                let len = self.load_constant(
+                    layouter.namespace(|| "load placeholder length"),
+                    F::zero(),
+                    MoveValueType::U64,
+                )?;
+                Ok(Value::new_vector(
+                    vec![Value::Invalid; capacity],
+                    len,
+                    (**elem_ty).clone(),
+                ))
+            }
+            _ => Ok(Value::Invalid),
+        }
+    }
+
+    /// `len + 1` or `len - 1` if `cond` is 1, `len` otherwise.
+    fn add_to_length(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        vector: &FVector<F>,
+        opcode: Opcode,
+        cond: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let one = self.load_constant(
+            layouter.namespace(|| "load one"),
+            F::one(),
+            MoveValueType::U64,
+        )?;
+        let len = self.binary_op(
+            layouter.namespace(|| "update length"),
+            opcode,
+            (*vector.len).clone(),
+            one,
+            cond.clone(),
+        )?;
+        self.conditional_select(
+            layouter.namespace(|| "select length"),
+            len,
+            (*vector.len).clone(),
+            cond,
+        )
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::chips::evaluation_chip::{EvaluationChip, EvaluationConfig};
-use crate::interpreter::Interpreter;
+use crate::chips::evaluation_chip::{EvaluationChip, EvaluationConfig, NUM_OF_ADVICE_COLUMNS};
+use crate::interpreter::{Interpreter, DEFAULT_LOOP_BOUND, DEFAULT_VECTOR_CAPACITY};
+use crate::locals::Locals;
+use crate::state_tree::StateTree;
+use crate::value::Value;
+use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Layouter, SimpleFloorPlanner},
@@ -11,20 +15,73 @@ use halo2_proofs::{
 use logger::prelude::*;
 use move_binary_format::file_format::CompiledScript;
 use move_binary_format::CompiledModule;
+use move_vm_runtime::loader::Function;
 use movelang::argument::ScriptArguments;
 use movelang::loader::MoveLoader;
 use movelang::state::StateStore;
+use movelang::value::MoveValueType;
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Instance row of the state root before the script runs.
+pub const OLD_STATE_ROOT_ROW: usize = 0;
+/// Instance row of the state root after the script runs.
+pub const NEW_STATE_ROOT_ROW: usize = 1;
+/// The public arguments and then the outputs of the script follow the state
+/// roots.
+pub const FIRST_PUBLIC_ROW: usize = 2;
+
+/// The lanes of advice columns a circuit is laid out on by default.
+pub const DEFAULT_LANES: usize = 1;
+
+/// Evaluates `$body` with the const `$lanes` set to the lane count of a
+/// circuit. halo2 configures a circuit from its type alone, so each lane
+/// count a circuit can have is a `LaneCircuit` type of its own, only 1, 2
+/// and 4 lanes are supported.
+#[macro_export]
+macro_rules! with_lanes {
+    ($count:expr, $lanes:ident => $body:expr) => {
+        match $count {
+            1 => {
+                const $lanes: usize = 1;
+                $body
+            }
+            2 => {
+                const $lanes: usize = 2;
+                $body
+            }
+            4 => {
+                const $lanes: usize = 4;
+                $body
+            }
+            count => Err($crate::circuit::unsupported_lanes(count)),
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn unsupported_lanes(count: usize) -> RuntimeError {
+    RuntimeError::new(StatusCode::UnsupportedLanes)
+        .with_message(format!("{} lanes are not supported, use 1, 2 or 4", count))
+}
 
 #[derive(Clone)]
-pub struct MoveCircuit<'l> {
+pub struct MoveCircuit<'l, F: FieldExt> {
     script: CompiledScript,
     modules: Vec<CompiledModule>,
     args: Option<ScriptArguments>,
     state: StateStore,
     loader: &'l MoveLoader,
+    loop_bound: usize,
+    vector_capacity: usize,
+    lanes: usize,
+    public_args: Vec<usize>,
+    public_outputs: Vec<usize>,
+    _marker: PhantomData<F>,
 }
 
-impl<'l> MoveCircuit<'l> {
+impl<'l, F: FieldExt> MoveCircuit<'l, F> {
     pub fn new(
         script: CompiledScript,
         modules: Vec<CompiledModule>,
@@ -38,9 +95,95 @@ impl<'l> MoveCircuit<'l> {
             args,
             state: state_store,
             loader,
+            loop_bound: DEFAULT_LOOP_BOUND,
+            vector_capacity: DEFAULT_VECTOR_CAPACITY,
+            lanes: DEFAULT_LANES,
+            public_args: vec![],
+            public_outputs: vec![],
+            _marker: PhantomData,
         }
     }
 
+    /// Loops are unrolled `bound` times, the bound is part of the circuit
+    /// shape, so proving and verifying keys only work with the same bound.
+    pub fn set_loop_bound(&mut self, bound: usize) {
+        self.loop_bound = bound;
+    }
+
+    pub fn loop_bound(&self) -> usize {
+        self.loop_bound
+    }
+
+    /// Vectors get a slot for every element up to the capacity, like the loop
+    /// bound it is part of the circuit shape.
+    pub fn set_vector_capacity(&mut self, capacity: usize) {
+        self.vector_capacity = capacity;
+    }
+
+    pub fn vector_capacity(&self) -> usize {
+        self.vector_capacity
+    }
+
+    /// The chips are laid out on this many lanes of advice columns, see
+    /// `EvaluationConfig`. More lanes make the circuit wider and shorter, so
+    /// a larger script fits in a smaller k. Like the loop bound it is part
+    /// of the circuit shape.
+    pub fn set_lanes(&mut self, lanes: usize) {
+        self.lanes = lanes;
+    }
+
+    pub fn lanes(&self) -> usize {
+        self.lanes
+    }
+
+    /// The circuit laid out on `LANES` lanes, which must be its lane count,
+    /// see `with_lanes`.
+    pub fn on_lanes<const LANES: usize>(&self) -> LaneCircuit<'_, 'l, F, LANES> {
+        debug_assert_eq!(self.lanes, LANES);
+        LaneCircuit(Cow::Borrowed(self))
+    }
+
+    /// Configures the chips on `lanes` lanes.
+    pub(crate) fn configure(meta: &mut ConstraintSystem<F>, lanes: usize) -> EvaluationConfig<F> {
+        let advice = (0..lanes)
+            .map(|_| [(); NUM_OF_ADVICE_COLUMNS].map(|_| meta.advice_column()))
+            .collect::<Vec<_>>();
+        let instance = meta.instance_column();
+        let constant = meta.fixed_column();
+
+        EvaluationChip::configure(meta, &advice, instance, constant)
+    }
+
+    /// These arguments are exposed after the state roots, in the given order,
+    /// the others stay private. Which arguments are public is part of the
+    /// circuit shape, their values are not.
+    pub fn set_public_args(&mut self, args: Vec<usize>) {
+        self.public_args = args;
+    }
+
+    pub fn public_args(&self) -> &[usize] {
+        &self.public_args
+    }
+
+    /// The values returned by the script and then the final values of these
+    /// locals, arguments first, are exposed after the public arguments. A
+    /// struct is exposed field by field and a vector as its length and slots.
+    pub fn set_public_outputs(&mut self, locals: Vec<usize>) {
+        self.public_outputs = locals;
+    }
+
+    pub fn public_outputs(&self) -> &[usize] {
+        &self.public_outputs
+    }
+
+    pub fn script(&self) -> &CompiledScript {
+        &self.script
+    }
+
+    pub fn modules(&self) -> &[CompiledModule] {
+        &self.modules
+    }
+
     pub fn loader(&self) -> &'l MoveLoader {
         self.loader
     }
@@ -48,81 +191,196 @@ impl<'l> MoveCircuit<'l> {
     pub fn state(&self) -> &StateStore {
         &self.state
     }
+
+    /// The arguments the script runs with, None for a circuit without
+    /// witnesses.
+    pub fn args(&self) -> Option<&ScriptArguments> {
+        self.args.as_ref()
+    }
+
+    /// The entry function of the script and the types of its arguments.
+    pub(crate) fn load_script(&self) -> VmResult<(Arc<Function>, Vec<MoveValueType>)> {
+        let mut script_bytes = vec![];
+        self.script.serialize(&mut script_bytes).map_err(|e| {
+            RuntimeError::new(StatusCode::ScriptLoadingError)
+                .with_message(format!("serialize script failed: {:?}", e))
+        })?;
+        self.loader()
+            .load_script(&script_bytes, &self.state)
+            .map_err(|e| {
+                RuntimeError::new(StatusCode::ScriptLoadingError)
+                    .with_message(format!("load script failed: {:?}", e))
+            })
+    }
+
+    /// Runs the script on `interp`, returns its final locals and its return
+    /// values.
+    pub(crate) fn run(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+    ) -> VmResult<(Locals<F>, Vec<Value<F>>)> {
+        interp.loop_bound = self.loop_bound;
+        interp.vector_capacity = self.vector_capacity;
+
+        let (entry, arg_types) = self.load_script()?;
+        trace!("script entry {:?}", entry.name());
+
+        // condition is true by default
+        interp.conditions().push(F::one())?;
+
+        let locals = interp.run_script(
+            evaluation_chip,
+            layouter,
+            entry,
+            self.args.clone(),
+            arg_types,
+            self.loader(),
+            &self.state,
+        )?;
+        let mut outputs = vec![];
+        while !interp.stack.is_empty() {
+            outputs.push(interp.stack.pop()?);
+        }
+        outputs.reverse();
+        Ok((locals, outputs))
+    }
 }
 
-impl<'l, F: FieldExt> Circuit<F> for MoveCircuit<'l> {
+/// A move circuit laid out on `LANES` lanes, the `Circuit` halo2 proves.
+#[derive(Clone)]
+pub struct LaneCircuit<'c, 'l, F: FieldExt, const LANES: usize>(Cow<'c, MoveCircuit<'l, F>>);
+
+impl<'c, 'l, F: FieldExt, const LANES: usize> Circuit<F> for LaneCircuit<'c, 'l, F, LANES> {
     type Config = EvaluationConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self {
-            script: self.script.clone(),
-            modules: self.modules.clone(),
+        let circuit = &self.0;
+        Self(Cow::Owned(MoveCircuit {
+            script: circuit.script.clone(),
+            modules: circuit.modules.clone(),
             args: None,
-            state: self.state.clone(),
-            loader: self.loader(),
-        }
+            state: circuit.state.clone(),
+            loader: circuit.loader(),
+            loop_bound: circuit.loop_bound,
+            vector_capacity: circuit.vector_capacity,
+            lanes: circuit.lanes,
+            public_args: circuit.public_args.clone(),
+            public_outputs: circuit.public_outputs.clone(),
+            _marker: PhantomData,
+        }))
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let advice = [
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-        ];
-        let instance = meta.instance_column();
-        let constant = meta.fixed_column();
+        MoveCircuit::configure(meta, LANES)
+    }
 
-        EvaluationChip::configure(meta, advice, instance, constant)
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        let evaluation_chip = EvaluationChip::<F>::construct(config, ());
+        self.0
+            .synthesize_with(&evaluation_chip, layouter, &mut Interpreter::new())
     }
+}
 
-    fn synthesize(
+impl<'l, F: FieldExt> MoveCircuit<'l, F> {
+    /// Lays the whole circuit out, running the script on `interp`.
+    pub(crate) fn synthesize_with(
         &self,
-        config: Self::Config,
+        evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
+        interp: &mut Interpreter<F>,
     ) -> Result<(), Error> {
-        let evaluation_chip = EvaluationChip::<F>::construct(config, ());
-        // let state_root = evaluation_chip.load_private(layouter.namespace(|| "load state root"), Some(F::zero()))?;
-        let mut interp = Interpreter::new();
-
-        let mut script_bytes = vec![];
-        self.script.serialize(&mut script_bytes).map_err(|e| {
-            error!("serialize script failed: {:?}", e);
-            Error::Synthesis
+        let mut state_tree = self.state_tree().map_err(|e| {
+            let error: Error = e.into();
+            error
         })?;
-
-        let (entry, arg_types) = self
-            .loader()
-            .load_script(&script_bytes, &self.state)
-            .map_err(|e| {
-                error!("load script failed: {:?}", e);
-                Error::Synthesis
-            })?;
-        trace!("script entry {:?}", entry.name());
-
-        // condition is true by default
-        interp.conditions().push(F::one()).map_err(|e| {
-            error!("set condition failed: {:?}", e);
-            Error::Synthesis
-        })?;
-
-        interp
-            .run_script(
-                &evaluation_chip,
-                layouter.namespace(|| "run script"),
-                entry,
-                self.args.clone(),
-                arg_types,
-                self.loader(),
-            )
+        interp.charge_costs_to(None, None, || "byte table".to_string());
+        evaluation_chip.load_byte_table(layouter.namespace(|| "load byte table"))?;
+        let (locals, outputs) = self
+            .run(evaluation_chip, layouter.namespace(|| "run script"), interp)
             .map_err(|e| {
                 let error: Error = e.into();
                 error
             })?;
-
-        // evaluation_chip.expose_public(layouter.namespace(|| "expose state root"), state_root, 0)?;
+        self.expose(
+            evaluation_chip,
+            layouter,
+            interp,
+            &mut state_tree,
+            &locals,
+            outputs,
+        )
+        .map_err(|e| {
+            let error: Error = e.into();
+            error
+        })?;
 
         Ok(())
     }
+
+    /// The state tree of the state the script starts from.
+    pub(crate) fn state_tree(&self) -> VmResult<StateTree<F>> {
+        StateTree::new(&self.state, self.vector_capacity)
+    }
+
+    /// Commits the global state a run of the script leaves and exposes the
+    /// state roots before and after it, the public arguments and the outputs.
+    /// Returns the exposed values, which are the public inputs of the proof.
+    pub(crate) fn expose(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+        state_tree: &mut StateTree<F>,
+        locals: &Locals<F>,
+        outputs: Vec<Value<F>>,
+    ) -> VmResult<Vec<Value<F>>> {
+        interp.charge_costs_to(None, None, || "state commit".to_string());
+        let (old_root, new_root) = interp.globals.commit(
+            evaluation_chip,
+            layouter.namespace(|| "commit state"),
+            state_tree,
+        )?;
+        let mut exposed = vec![];
+        for arg in self.public_args.iter() {
+            exposed.push(interp.arguments.get(*arg).cloned().ok_or_else(|| {
+                RuntimeError::new(StatusCode::OutOfBounds)
+                    .with_message(format!("public argument {} does not exist", arg))
+            })?);
+        }
+        exposed.extend(outputs);
+        for local in self.public_outputs.iter() {
+            exposed.push(locals.copy(*local)?);
+        }
+        interp.charge_costs_to(None, None, || "public inputs".to_string());
+        let mut public = vec![old_root.clone(), new_root.clone()];
+        for (i, value) in exposed.iter().enumerate() {
+            public.extend(
+                evaluation_chip
+                    .flatten_value(&mut layouter.namespace(|| format!("public #{}", i)), value)?,
+            );
+        }
+
+        evaluation_chip.expose_public(
+            layouter.namespace(|| "expose old state root"),
+            old_root,
+            OLD_STATE_ROOT_ROW,
+        )?;
+        evaluation_chip.expose_public(
+            layouter.namespace(|| "expose new state root"),
+            new_root,
+            NEW_STATE_ROOT_ROW,
+        )?;
+        for (i, value) in public.iter().skip(FIRST_PUBLIC_ROW).enumerate() {
+            evaluation_chip.expose_public(
+                layouter.namespace(|| format!("expose public #{}", i)),
+                value.clone(),
+                FIRST_PUBLIC_ROW + i,
+            )?;
+        }
+
+        Ok(public)
+    }
 }
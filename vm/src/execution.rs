@@ -0,0 +1,909 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A native interpreter running scripts on plain Move values.
+//!
+//! The circuit runs every arm of a branch and every unrolled loop iteration
+//! under a condition, which makes each run as costly as laying the circuit
+//! out. The native interpreter only follows the path the script takes, with
+//! the same semantics: the same bytecodes are supported, loops fail beyond the
+//! loop bound and vectors beyond the vector capacity, and a failing
+//! operation gives the same error as in the circuit. Its results are what a
+//! proof of the run exposes.
+
+use crate::cfg::{branch_targets, ControlFlowGraph};
+use crate::chips::instructions::Opcode;
+use crate::circuit::MoveCircuit;
+use crate::interpreter::ScriptAbort;
+use crate::program_block::arithmetic_error;
+use crate::state_tree::StateTree;
+use crate::value::{Reference, Root};
+use error::{RuntimeError, StatusCode, VmResult};
+use halo2_proofs::arithmetic::FieldExt;
+use logger::prelude::*;
+use move_binary_format::file_format::{Bytecode, FunctionHandleIndex};
+use move_core_types::account_address::AccountAddress;
+use move_core_types::language_storage::StructTag;
+use move_core_types::value::MoveStruct;
+use move_vm_runtime::loader::Function;
+use movelang::argument::{convert_from, convert_vector_from, ScriptArguments};
+use movelang::loader::MoveLoader;
+use movelang::state::StateStore;
+use movelang::value::{
+    field_to_address, move_cast, move_div, move_overflowing_add, move_overflowing_mul,
+    move_overflowing_sub, move_rem, move_shl, move_shr, MoveValue, MoveValueType,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// What a script run without a proof gives back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionResult<F: FieldExt> {
+    /// The values the script returns, empty if it aborts.
+    pub return_values: Vec<MoveValue>,
+    /// The final values of the locals exposed as public outputs, empty if the
+    /// script aborts.
+    pub outputs: Vec<MoveValue>,
+    /// The public inputs of a proof of the run, the state roots before and
+    /// after the script, the public arguments and then the outputs, empty if
+    /// the script aborts.
+    pub public_inputs: Vec<F>,
+    /// Where the script aborted, if it did.
+    pub abort: Option<ScriptAbort>,
+    /// The number of bytecodes the script executes.
+    pub steps: u64,
+}
+
+/// Runs the script of the circuit with its arguments on the native
+/// interpreter, nothing is laid out. An abort is a result, not an error.
+pub fn execute<F: FieldExt>(circuit: &MoveCircuit<F>) -> VmResult<ExecutionResult<F>> {
+    let (entry, arg_types) = circuit.load_script()?;
+    let mut interp = NativeInterpreter::new(circuit);
+    let (arguments, locals) = interp.process_arguments::<F>(circuit.args(), arg_types)?;
+    let (locals, return_values) = match interp.run_script(entry, locals)? {
+        Exit::Return(locals, values) => (locals, values),
+        Exit::Abort(abort) => {
+            return Ok(ExecutionResult {
+                return_values: vec![],
+                outputs: vec![],
+                public_inputs: vec![],
+                abort: Some(abort),
+                steps: interp.steps,
+            })
+        }
+    };
+    let outputs = circuit
+        .public_outputs()
+        .iter()
+        .map(|local| match locals.get(*local) {
+            Some(Some(NativeValue::Value(value))) | Some(Some(NativeValue::Snapshot(value))) => {
+                Ok(value.clone())
+            }
+            Some(Some(NativeValue::Reference(_))) => {
+                Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                    .with_message(format!("local {} is a mutable reference", local)))
+            }
+            Some(None) => Err(RuntimeError::new(StatusCode::CopyLocalError)),
+            None => Err(RuntimeError::new(StatusCode::OutOfBounds)),
+        })
+        .collect::<VmResult<Vec<_>>>()?;
+
+    // the same values the circuit exposes, see `MoveCircuit::expose`
+    let old_tree = StateTree::<F>::new(circuit.state(), circuit.vector_capacity())?;
+    let new_tree = StateTree::<F>::new(&interp.state, circuit.vector_capacity())?;
+    let mut public_inputs = vec![old_tree.root(), new_tree.root()];
+    for arg in circuit.public_args().iter() {
+        let value = arguments.get(*arg).ok_or_else(|| {
+            RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("public argument {} does not exist", arg))
+        })?;
+        public_inputs.extend(new_tree.flatten(value)?);
+    }
+    for value in return_values.iter().chain(outputs.iter()) {
+        public_inputs.extend(new_tree.flatten(value)?);
+    }
+
+    Ok(ExecutionResult {
+        return_values,
+        outputs,
+        public_inputs,
+        abort: None,
+        steps: interp.steps,
+    })
+}
+
+/// A value on the stack or in a local. An immutable reference to a value
+/// which can't be written through, like a vector element or the signer of
+/// the script, is a snapshot of the value, as in the circuit.
+#[derive(Clone, Debug)]
+enum NativeValue {
+    Value(MoveValue),
+    Reference(Reference),
+    Snapshot(MoveValue),
+}
+
+impl NativeValue {
+    fn into_value(self) -> VmResult<MoveValue> {
+        match self {
+            Self::Value(value) => Ok(value),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a value, got {:?}", v))),
+        }
+    }
+}
+
+/// How the current instruction leaves the running function.
+enum Step {
+    Next(u16 /* pc */),
+    Call(FunctionHandleIndex),
+    Return,
+    Abort(u128 /* error code */),
+}
+
+/// How the script ends, with its final locals and return values or aborted.
+enum Exit {
+    Return(Vec<Option<NativeValue>>, Vec<MoveValue>),
+    Abort(ScriptAbort),
+}
+
+struct NativeFrame {
+    function: Arc<Function>,
+    cfg: ControlFlowGraph,
+    locals: Vec<Option<NativeValue>>,
+    pc: u16,
+    // the iterations run by the loops entered, by the pc of their branch
+    iterations: HashMap<u16, usize>,
+}
+
+impl NativeFrame {
+    fn new(function: Arc<Function>, locals: Vec<Option<NativeValue>>) -> VmResult<Self> {
+        // the same control flow is rejected as in the circuit
+        let cfg = ControlFlowGraph::new(function.code()).map_err(|e| {
+            RuntimeError::new(StatusCode::ProgramBlockError).with_message(format!(
+                "{} in function {}",
+                e.message().unwrap_or_default(),
+                function.name()
+            ))
+        })?;
+        Ok(NativeFrame {
+            function,
+            cfg,
+            locals,
+            pc: 0,
+            iterations: HashMap::new(),
+        })
+    }
+}
+
+struct NativeInterpreter<'l> {
+    loader: &'l MoveLoader,
+    // the global state as the script leaves it
+    state: StateStore,
+    // the resources borrowed, global references point into this
+    borrowed: Vec<(AccountAddress, StructTag)>,
+    stack: Vec<NativeValue>,
+    frames: Vec<NativeFrame>,
+    loop_bound: usize,
+    vector_capacity: usize,
+    steps: u64,
+}
+
+impl<'l> NativeInterpreter<'l> {
+    fn new<F: FieldExt>(circuit: &MoveCircuit<'l, F>) -> Self {
+        Self {
+            loader: circuit.loader(),
+            state: circuit.state().clone(),
+            borrowed: vec![],
+            stack: vec![],
+            frames: vec![],
+            loop_bound: circuit.loop_bound(),
+            vector_capacity: circuit.vector_capacity(),
+            steps: 0,
+        }
+    }
+
+    /// The arguments as Move values of the types the script takes, and the
+    /// locals they are passed in, see `Interpreter::process_arguments`.
+    fn process_arguments<F: FieldExt>(
+        &self,
+        args: Option<&ScriptArguments>,
+        arg_types: Vec<MoveValueType>,
+    ) -> VmResult<(Vec<MoveValue>, Vec<NativeValue>)> {
+        let args = args.map(|args| args.as_inner().clone()).unwrap_or_default();
+        if args.len() != arg_types.len() {
+            return Err(
+                RuntimeError::new(StatusCode::TypeMissMatch).with_message(format!(
+                    "the script takes {} arguments, got {}",
+                    arg_types.len(),
+                    args.len()
+                )),
+            );
+        }
+        let mut arguments = vec![];
+        let mut locals = vec![];
+        for (i, (arg, ty)) in args.into_iter().zip(arg_types).enumerate() {
+            let value = match ty {
+                MoveValueType::Vector(elem_ty) => {
+                    let values: Vec<F> = convert_vector_from(arg)?;
+                    if values.len() > self.vector_capacity {
+                        return Err(RuntimeError::new(StatusCode::VectorOperationError)
+                            .with_message(format!(
+                                "argument #{} has {} elements, the vector capacity is {}",
+                                i,
+                                values.len(),
+                                self.vector_capacity
+                            )));
+                    }
+                    let vector = MoveValue::Vector(
+                        values
+                            .into_iter()
+                            .map(|v| from_field(v, &elem_ty))
+                            .collect::<VmResult<_>>()?,
+                    );
+                    locals.push(NativeValue::Value(vector.clone()));
+                    vector
+                }
+                // an address argument passed for a signer is the signer of the script
+                MoveValueType::Reference(inner) if *inner == MoveValueType::Signer => {
+                    let signer = from_field(convert_from::<F>(arg)?, &inner)?;
+                    locals.push(NativeValue::Snapshot(signer.clone()));
+                    signer
+                }
+                ty => {
+                    let value = from_field(convert_from::<F>(arg)?, &ty)?;
+                    locals.push(NativeValue::Value(value.clone()));
+                    value
+                }
+            };
+            arguments.push(value);
+        }
+        Ok((arguments, locals))
+    }
+
+    fn run_script(&mut self, entry: Arc<Function>, arguments: Vec<NativeValue>) -> VmResult<Exit> {
+        let mut locals = vec![None; entry.local_count()];
+        for (local, value) in locals.iter_mut().zip(arguments) {
+            *local = Some(value);
+        }
+        self.frames.push(NativeFrame::new(entry, locals)?);
+
+        loop {
+            let depth = self.frames.len() - 1;
+            let frame = &self.frames[depth];
+            let pc = frame.pc;
+            let function = frame.function.clone();
+            let instruction = function.code().get(pc as usize).cloned().ok_or_else(|| {
+                RuntimeError::new(StatusCode::OutOfBounds)
+                    .with_message(format!("no bytecode at pc {}", pc))
+            })?;
+            trace!(
+                "step #{}, pc #{}, instruction {:?}",
+                self.steps,
+                pc,
+                instruction
+            );
+            self.steps += 1;
+
+            let step = self.step(depth, &function, pc, &instruction).map_err(|e| {
+                match e.status_code() {
+                    StatusCode::ArithmeticError
+                    | StatusCode::VectorOperationError
+                    | StatusCode::GlobalStateError => {
+                        let message = format!(
+                            "{} in function {}",
+                            e.message().unwrap_or_default(),
+                            function.name()
+                        );
+                        e.with_message(message)
+                    }
+                    _ => e,
+                }
+            })?;
+            match step {
+                Step::Next(next) => self.frames[depth].pc = next,
+                Step::Call(index) => {
+                    let callee = self.loader.function_from_handle(&function, index);
+                    trace!("Call into function: {:?}", callee.name());
+                    let mut locals = vec![None; callee.local_count()];
+                    for i in (0..callee.arg_count()).rev() {
+                        locals[i] = Some(self.pop()?);
+                    }
+                    self.frames.push(NativeFrame::new(callee, locals)?);
+                }
+                Step::Return => {
+                    let frame = self.frames.pop().expect("the running frame");
+                    match self.frames.last_mut() {
+                        Some(caller) => caller.pc += 1,
+                        None => {
+                            let values = std::mem::take(&mut self.stack)
+                                .into_iter()
+                                .map(NativeValue::into_value)
+                                .collect::<VmResult<_>>()?;
+                            return Ok(Exit::Return(frame.locals, values));
+                        }
+                    }
+                }
+                Step::Abort(code) => {
+                    return Ok(Exit::Abort(ScriptAbort {
+                        function: function.pretty_string(),
+                        pc,
+                        code,
+                    }))
+                }
+            }
+        }
+    }
+
+    /// Executes the instruction at `pc` of the frame at call depth `depth`.
+    fn step(
+        &mut self,
+        depth: usize,
+        function: &Arc<Function>,
+        pc: u16,
+        instruction: &Bytecode,
+    ) -> VmResult<Step> {
+        macro_rules! arithmetic_op {
+            ($opcode:expr, $op:expr) => {{
+                let b = self.pop_value()?;
+                let a = self.pop_value()?;
+                if let Some(reason) = arithmetic_error($opcode, a.clone(), b.clone()) {
+                    return Err(RuntimeError::new(StatusCode::ArithmeticError)
+                        .with_message(format!("{} at pc {}", reason, pc)));
+                }
+                self.push(($op)(a, b)?);
+            }};
+        }
+
+        macro_rules! compare_op {
+            ($op:tt) => {{
+                let b = as_u128(&self.pop_value()?)?;
+                let a = as_u128(&self.pop_value()?)?;
+                self.push(MoveValue::Bool(a $op b));
+            }};
+        }
+
+        macro_rules! bitwise_op {
+            ($op:tt) => {{
+                let b = self.pop_value()?;
+                let a = self.pop_value()?;
+                let c = as_u128(&a)? $op as_u128(&b)?;
+                self.push(with_type_of(&a, c)?);
+            }};
+        }
+
+        macro_rules! vector_error {
+            ($failed:expr, $reason:expr) => {{
+                if $failed {
+                    return Err(RuntimeError::new(StatusCode::VectorOperationError)
+                        .with_message(format!("{} at pc {}", $reason, pc)));
+                }
+            }};
+        }
+
+        macro_rules! global_error {
+            ($failed:expr, $reason:expr) => {{
+                if $failed {
+                    return Err(RuntimeError::new(StatusCode::GlobalStateError)
+                        .with_message(format!("{} at pc {}", $reason, pc)));
+                }
+            }};
+        }
+
+        macro_rules! resource_tag {
+            ($index:expr) => {{
+                let ty = self.loader.resolver(function).get_struct_type($index);
+                self.loader.struct_tag(&ty)?
+            }};
+        }
+
+        match instruction {
+            Bytecode::LdU8(v) => self.push(MoveValue::U8(*v)),
+            Bytecode::LdU64(v) => self.push(MoveValue::U64(*v)),
+            Bytecode::LdU128(v) => self.push(MoveValue::U128(*v)),
+            Bytecode::LdTrue => self.push(MoveValue::Bool(true)),
+            Bytecode::LdFalse => self.push(MoveValue::Bool(false)),
+            Bytecode::Pop => {
+                self.pop()?;
+            }
+            Bytecode::Add => arithmetic_op!(Opcode::Add, |a, b| move_overflowing_add(a, b)
+                .map(|(c, _)| c)),
+            Bytecode::Sub => arithmetic_op!(Opcode::Sub, |a, b| move_overflowing_sub(a, b)
+                .map(|(c, _)| c)),
+            Bytecode::Mul => arithmetic_op!(Opcode::Mul, |a, b| move_overflowing_mul(a, b)
+                .map(|(c, _)| c)),
+            Bytecode::Div => arithmetic_op!(Opcode::Div, move_div),
+            Bytecode::Mod => arithmetic_op!(Opcode::Mod, move_rem),
+            Bytecode::Shl => arithmetic_op!(Opcode::Shl, move_shl),
+            Bytecode::Shr => arithmetic_op!(Opcode::Shr, move_shr),
+            Bytecode::Lt => compare_op!(<),
+            Bytecode::Gt => compare_op!(>),
+            Bytecode::Le => compare_op!(<=),
+            Bytecode::Ge => compare_op!(>=),
+            Bytecode::BitAnd => bitwise_op!(&),
+            Bytecode::BitOr => bitwise_op!(|),
+            Bytecode::Xor => bitwise_op!(^),
+            // references are compared by the values they point to
+            Bytecode::Eq | Bytecode::Neq => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let equal = self.deref(a)? == self.deref(b)?;
+                self.push(MoveValue::Bool(
+                    equal == matches!(instruction, Bytecode::Eq),
+                ));
+            }
+            Bytecode::And | Bytecode::Or => {
+                let b = as_bool(&self.pop_value()?)?;
+                let a = as_bool(&self.pop_value()?)?;
+                let c = if matches!(instruction, Bytecode::And) {
+                    a && b
+                } else {
+                    a || b
+                };
+                self.push(MoveValue::Bool(c));
+            }
+            Bytecode::Not => {
+                let a = as_bool(&self.pop_value()?)?;
+                self.push(MoveValue::Bool(!a));
+            }
+            Bytecode::CastU8 => {
+                let a = self.pop_value()?;
+                self.push(move_cast(a, MoveValueType::U8)?);
+            }
+            Bytecode::CastU64 => {
+                let a = self.pop_value()?;
+                self.push(move_cast(a, MoveValueType::U64)?);
+            }
+            Bytecode::CastU128 => {
+                let a = self.pop_value()?;
+                self.push(move_cast(a, MoveValueType::U128)?);
+            }
+            Bytecode::CopyLoc(v) => {
+                let value = match self.frames[depth].locals.get(*v as usize) {
+                    Some(Some(value)) => value.clone(),
+                    Some(None) => return Err(RuntimeError::new(StatusCode::CopyLocalError)),
+                    None => return Err(RuntimeError::new(StatusCode::OutOfBounds)),
+                };
+                self.stack.push(value);
+            }
+            Bytecode::MoveLoc(v) => {
+                let value = match self.frames[depth].locals.get_mut(*v as usize) {
+                    Some(local) => local
+                        .take()
+                        .ok_or_else(|| RuntimeError::new(StatusCode::MoveLocalError))?,
+                    None => return Err(RuntimeError::new(StatusCode::OutOfBounds)),
+                };
+                self.stack.push(value);
+            }
+            Bytecode::StLoc(v) => {
+                let value = self.pop()?;
+                match self.frames[depth].locals.get_mut(*v as usize) {
+                    Some(local) => *local = Some(value),
+                    None => return Err(RuntimeError::new(StatusCode::OutOfBounds)),
+                }
+            }
+            Bytecode::BrTrue(_) | Bytecode::BrFalse(_) => {
+                let condition = as_bool(&self.pop_value()?)?;
+                let (true_target, false_target) = branch_targets(function.code(), pc)
+                    .ok_or_else(|| RuntimeError::new(StatusCode::ShouldNotReachHere))?;
+                let frame = &mut self.frames[depth];
+                if let Some(info) = frame.cfg.loop_at(pc) {
+                    if condition == info.continue_on_true {
+                        let iteration = frame.iterations.entry(pc).or_insert(0);
+                        if *iteration == self.loop_bound {
+                            return Err(RuntimeError::new(StatusCode::LoopBoundExceeded)
+                                .with_message(format!(
+                                    "loop at {} of function {} runs more than {} iterations",
+                                    info.header,
+                                    function.name(),
+                                    self.loop_bound
+                                )));
+                        }
+                        *iteration += 1;
+                    } else {
+                        frame.iterations.remove(&pc);
+                    }
+                }
+                return Ok(Step::Next(if condition {
+                    true_target
+                } else {
+                    false_target
+                }));
+            }
+            Bytecode::Branch(offset) => return Ok(Step::Next(*offset)),
+            Bytecode::Call(index) => return Ok(Step::Call(*index)),
+            Bytecode::Ret => return Ok(Step::Return),
+            Bytecode::Abort => {
+                let code = as_u128(&self.pop_value()?)?;
+                return Ok(Step::Abort(code));
+            }
+            Bytecode::ImmBorrowLoc(v) | Bytecode::MutBorrowLoc(v) => {
+                self.stack
+                    .push(NativeValue::Reference(Reference::new(depth, *v as usize)));
+            }
+            Bytecode::ReadRef => {
+                let reference = self.pop()?;
+                let value = self.read_ref(reference)?;
+                self.push(value);
+            }
+            Bytecode::WriteRef => {
+                let reference = self.pop_reference()?;
+                let value = self.pop_value()?;
+                self.write_ref(&reference, value)?;
+            }
+            // an immutable reference is the same reference
+            Bytecode::FreezeRef => {}
+            Bytecode::Pack(index) => {
+                let count = self.loader.resolver(function).field_count(*index);
+                self.pack(count as usize)?;
+            }
+            Bytecode::PackGeneric(index) => {
+                let count = self
+                    .loader
+                    .resolver(function)
+                    .field_instantiation_count(*index);
+                self.pack(count as usize)?;
+            }
+            Bytecode::Unpack(_) | Bytecode::UnpackGeneric(_) => match self.pop_value()? {
+                MoveValue::Struct(s) => {
+                    for field in s.fields() {
+                        self.push(field.clone());
+                    }
+                }
+                v => {
+                    return Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                        .with_message(format!("can not unpack {:?}", v)))
+                }
+            },
+            Bytecode::ImmBorrowField(index) | Bytecode::MutBorrowField(index) => {
+                let offset = self.loader.resolver(function).field_offset(*index);
+                self.borrow_field(offset)?;
+            }
+            Bytecode::ImmBorrowFieldGeneric(index) | Bytecode::MutBorrowFieldGeneric(index) => {
+                let offset = self
+                    .loader
+                    .resolver(function)
+                    .field_instantiation_offset(*index);
+                self.borrow_field(offset)?;
+            }
+            Bytecode::VecPack(_, count) => {
+                let count = *count as usize;
+                if count > self.vector_capacity {
+                    return Err(
+                        RuntimeError::new(StatusCode::VectorOperationError).with_message(format!(
+                            "can not pack {} elements, the vector capacity is {}",
+                            count, self.vector_capacity
+                        )),
+                    );
+                }
+                let mut elements = Vec::with_capacity(count);
+                for _ in 0..count {
+                    elements.push(self.pop_value()?);
+                }
+                elements.reverse();
+                self.push(MoveValue::Vector(elements));
+            }
+            Bytecode::VecLen(_) => {
+                let reference = self.pop()?;
+                let len = as_vector(self.read_ref(reference)?)?.len();
+                self.push(MoveValue::U64(len as u64));
+            }
+            Bytecode::VecImmBorrow(_) => {
+                let index = as_u128(&self.pop_value()?)?;
+                let reference = self.pop()?;
+                let mut elements = as_vector(self.read_ref(reference)?)?;
+                vector_error!(
+                    index >= elements.len() as u128,
+                    "vector index out of bounds"
+                );
+                let elem = elements.swap_remove(index as usize);
+                self.stack.push(NativeValue::Snapshot(elem));
+            }
+            Bytecode::VecPushBack(_) => {
+                let elem = self.pop_value()?;
+                let reference = self.pop_reference()?;
+                let mut elements = as_vector(self.load_ref(&reference)?)?;
+                vector_error!(
+                    elements.len() == self.vector_capacity,
+                    "vector capacity exceeded"
+                );
+                elements.push(elem);
+                self.write_ref(&reference, MoveValue::Vector(elements))?;
+            }
+            Bytecode::VecPopBack(_) => {
+                let reference = self.pop_reference()?;
+                let mut elements = as_vector(self.load_ref(&reference)?)?;
+                let elem = elements.pop();
+                vector_error!(elem.is_none(), "pop from an empty vector");
+                self.write_ref(&reference, MoveValue::Vector(elements))?;
+                self.push(elem.expect("the vector is not empty"));
+            }
+            Bytecode::VecSwap(_) => {
+                let j = as_u128(&self.pop_value()?)?;
+                let i = as_u128(&self.pop_value()?)?;
+                let reference = self.pop_reference()?;
+                let mut elements = as_vector(self.load_ref(&reference)?)?;
+                let len = elements.len() as u128;
+                vector_error!(i >= len || j >= len, "vector index out of bounds");
+                elements.swap(i as usize, j as usize);
+                self.write_ref(&reference, MoveValue::Vector(elements))?;
+            }
+            Bytecode::Exists(index) => {
+                let tag = resource_tag!(*index);
+                let address = as_address(&self.pop_value()?)?;
+                let exists = self.state.resource(address, &tag).is_some();
+                self.push(MoveValue::Bool(exists));
+            }
+            Bytecode::MoveTo(index) => {
+                let tag = resource_tag!(*index);
+                let value = self.pop_value()?;
+                let signer = self.pop()?;
+                let address = as_address(&self.read_ref(signer)?)?;
+                global_error!(
+                    self.state.resource(address, &tag).is_some(),
+                    "resource already exists"
+                );
+                self.state.add_resource(address, tag, value);
+            }
+            Bytecode::MoveFrom(index) => {
+                let tag = resource_tag!(*index);
+                let address = as_address(&self.pop_value()?)?;
+                let value = self.state.remove_resource(address, &tag);
+                global_error!(value.is_none(), "resource does not exist");
+                self.push(value.expect("the resource exists"));
+            }
+            Bytecode::ImmBorrowGlobal(index) | Bytecode::MutBorrowGlobal(index) => {
+                let tag = resource_tag!(*index);
+                let address = as_address(&self.pop_value()?)?;
+                global_error!(
+                    self.state.resource(address, &tag).is_none(),
+                    "resource does not exist"
+                );
+                self.borrowed.push((address, tag));
+                let reference = Reference::global(self.borrowed.len() - 1);
+                self.stack.push(NativeValue::Reference(reference));
+            }
+            _ => {
+                return Err(RuntimeError::new(StatusCode::UnsupportedBytecode)
+                    .with_message(format!("{:?} at pc {}", instruction, pc)))
+            }
+        }
+        Ok(Step::Next(pc + 1))
+    }
+
+    fn push(&mut self, value: MoveValue) {
+        self.stack.push(NativeValue::Value(value))
+    }
+
+    fn pop(&mut self) -> VmResult<NativeValue> {
+        self.stack
+            .pop()
+            .ok_or_else(|| RuntimeError::new(StatusCode::StackUnderflow))
+    }
+
+    fn pop_value(&mut self) -> VmResult<MoveValue> {
+        self.pop()?.into_value()
+    }
+
+    fn pop_reference(&mut self) -> VmResult<Reference> {
+        match self.pop()? {
+            NativeValue::Reference(reference) => Ok(reference),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a mutable reference, got {:?}", v))),
+        }
+    }
+
+    fn pack(&mut self, field_count: usize) -> VmResult<()> {
+        let mut fields = Vec::with_capacity(field_count);
+        for _ in 0..field_count {
+            fields.push(self.pop_value()?);
+        }
+        fields.reverse();
+        self.push(MoveValue::Struct(MoveStruct::new(fields)));
+        Ok(())
+    }
+
+    fn borrow_field(&mut self, offset: usize) -> VmResult<()> {
+        let field = match self.pop()? {
+            NativeValue::Reference(reference) => {
+                NativeValue::Reference(reference.borrow_field(offset))
+            }
+            NativeValue::Snapshot(value) => {
+                NativeValue::Snapshot(field(&value, &[offset])?.clone())
+            }
+            v => {
+                return Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                    .with_message(format!("can not borrow field from {:?}", v)))
+            }
+        };
+        self.stack.push(field);
+        Ok(())
+    }
+
+    /// The value a reference points to.
+    fn load_ref(&self, reference: &Reference) -> VmResult<MoveValue> {
+        let root = match reference.root {
+            Root::Local(frame, local) => {
+                let locals = &self
+                    .frames
+                    .get(frame)
+                    .ok_or_else(|| {
+                        RuntimeError::new(StatusCode::OutOfBounds)
+                            .with_message(format!("no frame at call depth {}", frame))
+                    })?
+                    .locals;
+                match locals.get(local) {
+                    Some(Some(NativeValue::Value(value))) => value.clone(),
+                    Some(_) => return Err(RuntimeError::new(StatusCode::CopyLocalError)),
+                    None => return Err(RuntimeError::new(StatusCode::OutOfBounds)),
+                }
+            }
+            Root::Global(index) => {
+                let (address, tag) = self.resource(index)?;
+                self.state.resource(*address, tag).ok_or_else(|| {
+                    RuntimeError::new(StatusCode::GlobalStateError)
+                        .with_message(format!("resource {} does not exist", tag))
+                })?
+            }
+        };
+        Ok(field(&root, &reference.path)?.clone())
+    }
+
+    /// The value a reference points to, or the value of a snapshot.
+    fn read_ref(&self, reference: NativeValue) -> VmResult<MoveValue> {
+        match reference {
+            NativeValue::Reference(reference) => self.load_ref(&reference),
+            NativeValue::Snapshot(value) => Ok(value),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a reference, got {:?}", v))),
+        }
+    }
+
+    /// The value a reference points to, other values as they are.
+    fn deref(&self, value: NativeValue) -> VmResult<MoveValue> {
+        match value {
+            NativeValue::Value(value) => Ok(value),
+            reference => self.read_ref(reference),
+        }
+    }
+
+    fn write_ref(&mut self, reference: &Reference, value: MoveValue) -> VmResult<()> {
+        match reference.root {
+            Root::Local(frame, local) => {
+                let locals = &mut self
+                    .frames
+                    .get_mut(frame)
+                    .ok_or_else(|| {
+                        RuntimeError::new(StatusCode::OutOfBounds)
+                            .with_message(format!("no frame at call depth {}", frame))
+                    })?
+                    .locals;
+                match locals.get_mut(local) {
+                    Some(Some(NativeValue::Value(root))) => set_field(root, &reference.path, value),
+                    Some(_) => Err(RuntimeError::new(StatusCode::StoreLocalError)),
+                    None => Err(RuntimeError::new(StatusCode::OutOfBounds)),
+                }
+            }
+            Root::Global(index) => {
+                let (address, tag) = self.resource(index)?.clone();
+                let mut root = self.state.resource(address, &tag).ok_or_else(|| {
+                    RuntimeError::new(StatusCode::GlobalStateError)
+                        .with_message(format!("resource {} does not exist", tag))
+                })?;
+                set_field(&mut root, &reference.path, value)?;
+                self.state.add_resource(address, tag, root);
+                Ok(())
+            }
+        }
+    }
+
+    fn resource(&self, index: usize) -> VmResult<&(AccountAddress, StructTag)> {
+        self.borrowed.get(index).ok_or_else(|| {
+            RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("no global resource #{}", index))
+        })
+    }
+}
+
+/// The field at `path` of a value, the value itself if the path is empty.
+fn field<'v>(value: &'v MoveValue, path: &[usize]) -> VmResult<&'v MoveValue> {
+    match (path.split_first(), value) {
+        (None, _) => Ok(value),
+        (Some((offset, rest)), MoveValue::Struct(s)) => match s.fields().get(*offset) {
+            Some(field_value) => field(field_value, rest),
+            None => Err(RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("no field #{} in {:?}", offset, value))),
+        },
+        (Some(_), _) => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("can not borrow field from {:?}", value))),
+    }
+}
+
+fn set_field(value: &mut MoveValue, path: &[usize], new: MoveValue) -> VmResult<()> {
+    let (offset, rest) = match path.split_first() {
+        None => {
+            *value = new;
+            return Ok(());
+        }
+        Some(split) => split,
+    };
+    let mut fields = match value {
+        MoveValue::Struct(s) => s.fields().to_vec(),
+        v => {
+            return Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("can not borrow field from {:?}", v)))
+        }
+    };
+    let field_value = fields.get_mut(*offset).ok_or_else(|| {
+        RuntimeError::new(StatusCode::OutOfBounds).with_message(format!("no field #{}", offset))
+    })?;
+    set_field(field_value, rest, new)?;
+    *value = MoveValue::Struct(MoveStruct::new(fields));
+    Ok(())
+}
+
+/// A Move value of type `ty` from its field element.
+fn from_field<F: FieldExt>(field: F, ty: &MoveValueType) -> VmResult<MoveValue> {
+    let v = field.get_lower_128();
+    let value = match ty {
+        MoveValueType::U8 => u8::try_from(v).ok().map(MoveValue::U8),
+        MoveValueType::U64 => u64::try_from(v).ok().map(MoveValue::U64),
+        MoveValueType::U128 => Some(MoveValue::U128(v)),
+        MoveValueType::Bool => (v <= 1).then_some(MoveValue::Bool(v == 1)),
+        MoveValueType::Address => Some(MoveValue::Address(field_to_address(field)?)),
+        MoveValueType::Signer => Some(MoveValue::Signer(field_to_address(field)?)),
+        ty => {
+            return Err(RuntimeError::new(StatusCode::UnsupportedMoveType)
+                .with_message(format!("{:?} is not supported as an argument", ty)))
+        }
+    };
+    value.ok_or_else(|| {
+        RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("{} is out of the range of {:?}", v, ty))
+    })
+}
+
+fn as_u128(value: &MoveValue) -> VmResult<u128> {
+    match value {
+        MoveValue::U8(v) => Ok(*v as u128),
+        MoveValue::U64(v) => Ok(*v as u128),
+        MoveValue::U128(v) => Ok(*v),
+        v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("expect an integer, got {:?}", v))),
+    }
+}
+
+/// `v` as an integer of the same type as `value`, `v` must fit.
+fn with_type_of(value: &MoveValue, v: u128) -> VmResult<MoveValue> {
+    match value {
+        MoveValue::U8(_) => Ok(MoveValue::U8(v as u8)),
+        MoveValue::U64(_) => Ok(MoveValue::U64(v as u64)),
+        MoveValue::U128(_) => Ok(MoveValue::U128(v)),
+        v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("expect an integer, got {:?}", v))),
+    }
+}
+
+fn as_bool(value: &MoveValue) -> VmResult<bool> {
+    match value {
+        MoveValue::Bool(b) => Ok(*b),
+        v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("expect a bool, got {:?}", v))),
+    }
+}
+
+fn as_address(value: &MoveValue) -> VmResult<AccountAddress> {
+    match value {
+        MoveValue::Address(address) | MoveValue::Signer(address) => Ok(*address),
+        v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("expect an address, got {:?}", v))),
+    }
+}
+
+fn as_vector(value: MoveValue) -> VmResult<Vec<MoveValue>> {
+    match value {
+        MoveValue::Vector(elements) => Ok(elements),
+        v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+            .with_message(format!("expect a vector, got {:?}", v))),
+    }
+}
@@ -1,22 +1,34 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::cfg::{branch_targets, ControlFlowGraph};
 use crate::chips::evaluation_chip::EvaluationChip;
-use crate::interpreter::Interpreter;
+use crate::interpreter::{Interpreter, ScriptAbort};
 use crate::locals::Locals;
-use crate::program_block::{Block, ExitStatus, ProgramBlock};
+use crate::program_block::{merge_locals, ArmExit, Block, ExitStatus, LoopBlock, ProgramBlock};
 use crate::stack::BlockStack;
+use crate::value::Value;
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::{arithmetic::FieldExt, circuit::Layouter};
 use logger::prelude::*;
-use move_binary_format::file_format::Bytecode;
-use move_vm_runtime::loader::Function;
+use move_vm_runtime::loader::{Function, Resolver};
+use movelang::loader::MoveLoader;
+use movelang::state::StateStore;
 use std::sync::Arc;
 
+/// What the running function needs from outside the interpreter.
+pub struct ExecutionContext<'a> {
+    pub function: &'a Function,
+    pub resolver: Resolver<'a>,
+    pub loader: &'a MoveLoader,
+    pub state: &'a StateStore,
+}
+
 pub struct Frame<F: FieldExt> {
     current_block: ProgramBlock<F>,
     blocks: BlockStack<F>,
     function: Arc<Function>,
+    cfg: ControlFlowGraph,
 }
 
 impl<F: FieldExt> Frame<F> {
@@ -26,15 +38,23 @@ impl<F: FieldExt> Frame<F> {
         end: Option<u16>,
         function: Arc<Function>,
         locals: Locals<F>,
-    ) -> Self {
+        condition: Value<F>,
+    ) -> VmResult<Self> {
         let code = function.code();
-        let func_body =
-            ProgramBlock::new_block(pc, start, end, locals, code.to_vec(), Some(F::one()));
-        Frame {
+        let cfg = ControlFlowGraph::new(code).map_err(|e| {
+            RuntimeError::new(StatusCode::ProgramBlockError).with_message(format!(
+                "{} in function {}",
+                e.message().unwrap_or_default(),
+                function.name()
+            ))
+        })?;
+        let func_body = ProgramBlock::new_block(pc, start, end, locals, code.to_vec(), condition);
+        Ok(Frame {
             current_block: func_body,
             blocks: BlockStack::default(),
             function,
-        }
+            cfg,
+        })
     }
 
     pub fn current_block(&mut self) -> &mut ProgramBlock<F> {
@@ -45,186 +65,493 @@ impl<F: FieldExt> Frame<F> {
         &self.function
     }
 
-    // todo: identify blocks through static analysis?
+    /// Both arms of the branch at pc run up to the point where they join
+    /// again, the true arm first.
     pub fn prepare_conditional_block(
         &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: impl Layouter<F>,
         pc: u16,
-        condition: Option<F>,
+        condition: Value<F>,
+        stack_height: usize,
     ) -> VmResult<ProgramBlock<F>> {
         let code = self.function.code();
-        let not_condition = condition.map(|v| F::one() - v);
-        let (_br_type, true_branch_start) = match &code[pc as usize] {
-            Bytecode::BrTrue(offset) => (true, *offset),
-            _ => {
-                return Err(RuntimeError::new(StatusCode::ProgramBlockError)
-                    .with_message("expect BrTrue or BrFalse".to_string()))
-            }
+        let (true_branch_start, false_branch_start) =
+            branch_targets(code, pc).ok_or_else(|| {
+                RuntimeError::new(StatusCode::ProgramBlockError)
+                    .with_message("expect BrTrue or BrFalse".to_string())
+            })?;
+        let merge = self.cfg.merge_point(pc);
+
+        // the arms only run if the block containing the branch runs
+        let (condition, not_condition) = evaluation_chip
+            .branch_condition(layouter, self.current_block.condition(), condition)
+            .map_err(RuntimeError::from)?;
+        let true_branch = Block::new(
+            true_branch_start,
+            true_branch_start,
+            merge,
+            self.current_block.locals().clone(),
+            code.to_vec(),
+            condition,
+        );
+        let false_branch = Block::new(
+            false_branch_start,
+            false_branch_start,
+            merge,
+            self.current_block.locals().clone(),
+            code.to_vec(),
+            not_condition,
+        );
+        Ok(ProgramBlock::new_conditional_block(
+            true_branch,
+            false_branch,
+            merge,
+            stack_height,
+        ))
+    }
+
+    /// Returns the loop block if the branch at pc leaves a loop.
+    pub fn prepare_loop_block(&mut self, pc: u16, bound: usize) -> Option<ProgramBlock<F>> {
+        let code = self.function.code();
+        let info = self.cfg.loop_at(pc)?.clone();
+
+        // the header of the first iteration has run in the current block
+        let condition = self.current_block.condition();
+        let locals = self.current_block.locals().clone();
+        let running = Block::new(
+            pc,
+            info.header,
+            None,
+            locals.clone(),
+            code.to_vec(),
+            condition.clone(),
+        );
+        Some(ProgramBlock::LoopBlock(LoopBlock {
+            header: info.header,
+            branch: pc,
+            body_start: info.body_start,
+            exit: info.exit,
+            continue_on_true: info.continue_on_true,
+            bound,
+            iteration: 0,
+            condition,
+            locals,
+            running,
+            in_body: false,
+        }))
+    }
+
+    /// The loop header reaches its branch, run the body again if the loop
+    /// continues, otherwise leave the loop.
+    fn continue_loop(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        branch_condition: Value<F>,
+    ) -> VmResult<()> {
+        let loop_block = match &mut self.current_block {
+            ProgramBlock::LoopBlock(loop_block) => loop_block,
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
         };
-        match &code[(true_branch_start - 1) as usize] {
-            Bytecode::Branch(offset) => {
-                let true_branch_end = *offset - 1;
-                match &code[(true_branch_end) as usize] {
-                    Bytecode::Branch(offset) => {
-                        let true_branch = Block::new(
-                            true_branch_start,
-                            true_branch_start,
-                            Some(true_branch_end - 1), //ignore the branch instruction at the end
-                            self.current_block.locals().clone(),
-                            self.function.code().to_vec(),
-                            condition,
-                        );
-                        let false_branch_start = true_branch_end + 1;
-                        let false_branch_end = *offset - 1;
-                        let false_branch = Block::new(
-                            false_branch_start,
-                            false_branch_start,
-                            Some(false_branch_end),
-                            self.current_block().locals().clone(),
-                            self.function.code().to_vec(),
-                            not_condition,
-                        );
-                        Ok(ProgramBlock::new_conditional_block(
-                            Some(true_branch),
-                            Some(false_branch),
-                        ))
+        // the header runs only if the loop is still running
+        let carried = loop_block.locals.clone();
+        merge_locals(
+            evaluation_chip,
+            layouter.namespace(|| "merge loop header"),
+            &mut loop_block.locals,
+            loop_block.running.locals(),
+            &carried,
+            loop_block.condition.clone(),
+        )?;
+
+        let (t_condition, f_condition) = evaluation_chip
+            .branch_condition(
+                layouter.namespace(|| "loop condition"),
+                loop_block.condition.clone(),
+                branch_condition,
+            )
+            .map_err(RuntimeError::from)?;
+        let condition = if loop_block.continue_on_true {
+            t_condition
+        } else {
+            f_condition
+        };
+        if loop_block.iteration < loop_block.bound {
+            trace!("loop iteration #{}", loop_block.iteration);
+            loop_block.running = loop_block.body(condition);
+            loop_block.in_body = true;
+            return Ok(());
+        }
+
+        // a loop still running after its last iteration aborts, so a proof
+        // of a run cut short by the bound doesn't verify
+        evaluation_chip
+            .abort(layouter.namespace(|| "loop bound"), condition.clone())
+            .map_err(RuntimeError::from)?;
+        if condition.value() == Some(F::one()) {
+            return Err(
+                RuntimeError::new(StatusCode::LoopBoundExceeded).with_message(format!(
+                    "loop at {} of function {} runs more than {} iterations",
+                    loop_block.header,
+                    self.function.name(),
+                    loop_block.bound
+                )),
+            );
+        }
+        self.leave_loop()
+    }
+
+    /// The loop body reaches the back edge, run the header again.
+    fn finish_loop_body(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: impl Layouter<F>,
+    ) -> VmResult<()> {
+        let loop_block = match &mut self.current_block {
+            ProgramBlock::LoopBlock(loop_block) => loop_block,
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+        };
+        let condition = loop_block.running.condition();
+        let carried = loop_block.locals.clone();
+        merge_locals(
+            evaluation_chip,
+            layouter,
+            &mut loop_block.locals,
+            loop_block.running.locals(),
+            &carried,
+            condition.clone(),
+        )?;
+        loop_block.condition = condition;
+        loop_block.iteration += 1;
+        loop_block.running = loop_block.header();
+        loop_block.in_body = false;
+        Ok(())
+    }
+
+    /// Continue after the loop with the locals of its last iteration.
+    fn leave_loop(&mut self) -> VmResult<()> {
+        trace!("leave the loop");
+        let (locals, exit) = match &self.current_block {
+            ProgramBlock::LoopBlock(loop_block) => (loop_block.locals.clone(), loop_block.exit),
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+        };
+        self.current_block = self
+            .blocks
+            .pop()
+            .ok_or_else(|| RuntimeError::new(StatusCode::ShouldNotReachHere))?;
+        self.current_block.set_locals(locals);
+        self.current_block.set_pc(exit);
+        Ok(())
+    }
+
+    /// The running arm of the conditional block is left, switch to the false
+    /// arm, or merge both arms into the enclosing block. Returns the status the
+    /// frame exits with, if the arms leave the function.
+    fn end_arm(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: &mut impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+        exit: ArmExit<F>,
+    ) -> VmResult<Option<ExitStatus<F>>> {
+        let cb = match &mut self.current_block {
+            ProgramBlock::ConditionalBlock(cb) => cb,
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+        };
+        if cb.true_branch.is_running {
+            trace!("switch conditional branch");
+            cb.true_branch.exit = Some(exit);
+            cb.true_branch.is_running = false;
+            cb.false_branch.is_running = true;
+            return Ok(None);
+        }
+        cb.false_branch.exit = Some(exit);
+
+        trace!("merge the branch");
+        interp.charge_costs_to(Some(self.function.name()), None, || "merge".to_string());
+        let next_running = self
+            .blocks
+            .pop()
+            .ok_or_else(|| RuntimeError::new(StatusCode::ShouldNotReachHere))?;
+        let cb = match std::mem::replace(&mut self.current_block, next_running) {
+            ProgramBlock::ConditionalBlock(cb) => cb,
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+        };
+        let (t_branch, f_branch) = (cb.true_branch, cb.false_branch);
+        let condition = t_branch.block.condition();
+        let merge = cb.merge;
+        let merge = || merge.ok_or_else(|| RuntimeError::new(StatusCode::ShouldNotReachHere));
+        let t_returns = matches!(t_branch.exit, Some(ArmExit::Return(_)));
+        match (t_branch.exit, f_branch.exit) {
+            (Some(ArmExit::Merge), Some(ArmExit::Merge)) => {
+                self.current_block.merge_locals(
+                    evaluation_chip,
+                    layouter.namespace(|| format!("merge locals in step#{}", interp.step)),
+                    t_branch.block.locals(),
+                    f_branch.block.locals(),
+                    condition.clone(),
+                )?;
+                self.current_block.set_pc(merge()?);
+            }
+            // an aborting arm doesn't change the locals
+            (Some(ArmExit::Merge), Some(ArmExit::Abort(..))) => {
+                self.current_block
+                    .set_locals(t_branch.block.locals().clone());
+                self.current_block.set_pc(merge()?);
+            }
+            (Some(ArmExit::Abort(..)), Some(ArmExit::Merge)) => {
+                self.current_block
+                    .set_locals(f_branch.block.locals().clone());
+                self.current_block.set_pc(merge()?);
+            }
+            (Some(ArmExit::Return(t_values)), Some(ArmExit::Return(f_values))) => {
+                debug_assert!(t_values.len() == f_values.len());
+                // the locals of the script are read after it returns
+                if interp.frames.is_empty() {
+                    self.current_block.merge_locals(
+                        evaluation_chip,
+                        layouter.namespace(|| format!("merge locals in step#{}", interp.step)),
+                        t_branch.block.locals(),
+                        f_branch.block.locals(),
+                        condition.clone(),
+                    )?;
+                }
+                for (i, (t, f)) in t_values.into_iter().zip(f_values).enumerate() {
+                    let value = if t.equals(&f) {
+                        t
+                    } else {
+                        evaluation_chip
+                            .conditional_select(
+                                layouter.namespace(|| {
+                                    format!("merge return value {} in step#{}", i, interp.step)
+                                }),
+                                t,
+                                f,
+                                condition.clone(),
+                            )
+                            .map_err(RuntimeError::from)?
+                    };
+                    interp.stack.push(value)?;
+                }
+                return self.handle_status(evaluation_chip, layouter, interp, ExitStatus::Return);
+            }
+            (Some(ArmExit::Return(values)), Some(ArmExit::Abort(..)))
+            | (Some(ArmExit::Abort(..)), Some(ArmExit::Return(values))) => {
+                if interp.frames.is_empty() {
+                    let locals = if t_returns {
+                        t_branch.block.locals()
+                    } else {
+                        f_branch.block.locals()
+                    };
+                    self.current_block.set_locals(locals.clone());
+                }
+                for value in values {
+                    interp.stack.push(value)?;
+                }
+                return self.handle_status(evaluation_chip, layouter, interp, ExitStatus::Return);
+            }
+            // the enclosing block aborts as well
+            (Some(ArmExit::Abort(pc, error_code)), Some(ArmExit::Abort(..))) => {
+                return self.handle_status(
+                    evaluation_chip,
+                    layouter,
+                    interp,
+                    ExitStatus::Abort(pc, error_code),
+                );
+            }
+            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+        }
+        Ok(None)
+    }
+
+    /// Handle the status the current block exits with. Returns the status the
+    /// frame exits with, or None if the frame goes on running.
+    fn handle_status(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: &mut impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+        status: ExitStatus<F>,
+    ) -> VmResult<Option<ExitStatus<F>>> {
+        match status {
+            ExitStatus::Call(index) => Ok(Some(ExitStatus::Call(index))),
+            ExitStatus::Return => match &self.current_block {
+                ProgramBlock::Block(_) => Ok(Some(ExitStatus::Return)),
+                ProgramBlock::ConditionalBlock(cb) => {
+                    // the return values are left on top of the stack
+                    let mut values = vec![];
+                    while interp.stack.len() > cb.stack_height {
+                        values.push(interp.stack.pop()?);
                     }
-                    _ => {
-                        let true_branch = Block::new(
-                            true_branch_start,
-                            true_branch_start,
-                            Some(true_branch_end),
-                            self.current_block.locals().clone(),
-                            self.function.code().to_vec(),
-                            condition,
-                        );
-                        Ok(ProgramBlock::new_conditional_block(Some(true_branch), None))
+                    values.reverse();
+                    self.end_arm(evaluation_chip, layouter, interp, ArmExit::Return(values))
+                }
+                // returning from a loop is rejected by the control flow analysis
+                ProgramBlock::LoopBlock(_) => {
+                    Err(RuntimeError::new(StatusCode::ShouldNotReachHere))
+                }
+            },
+            ExitStatus::ConditionalBranch(cb) => {
+                trace!("handle conditional branch");
+                if let ProgramBlock::LoopBlock(loop_block) = &self.current_block {
+                    if !loop_block.in_body && loop_block.branch == cb.pc {
+                        interp.charge_costs_to(Some(self.function.name()), None, || {
+                            "loop".to_string()
+                        });
+                        self.continue_loop(
+                            evaluation_chip,
+                            layouter.namespace(|| format!("loop in step#{}", interp.step)),
+                            cb.condition,
+                        )?;
+                        return Ok(None);
                     }
                 }
+                let block = match self.prepare_loop_block(cb.pc, interp.loop_bound) {
+                    Some(block) => block,
+                    None => self.prepare_conditional_block(
+                        evaluation_chip,
+                        layouter.namespace(|| format!("branch condition in step#{}", interp.step)),
+                        cb.pc,
+                        cb.condition.clone(),
+                        interp.stack.len(),
+                    )?,
+                };
+                trace!("{:?}", block);
+                self.blocks.push(self.current_block.clone())?;
+                self.current_block = block;
+                if let ProgramBlock::LoopBlock(_) = &self.current_block {
+                    interp.charge_costs_to(Some(self.function.name()), None, || "loop".to_string());
+                    self.continue_loop(
+                        evaluation_chip,
+                        layouter.namespace(|| format!("loop in step#{}", interp.step)),
+                        cb.condition,
+                    )?;
+                }
+                Ok(None)
             }
-            Bytecode::Abort => {
-                let false_branch_start = pc + 1;
-                let false_branch_end = true_branch_start - 1;
-                let false_branch = Block::new(
-                    false_branch_start,
-                    false_branch_start,
-                    Some(false_branch_end),
-                    self.current_block().locals().clone(),
-                    self.function.code().to_vec(),
-                    not_condition,
-                );
-                Ok(ProgramBlock::new_conditional_block(
-                    None,
-                    Some(false_branch),
-                ))
+            ExitStatus::BranchEnd(_pc) => match &self.current_block {
+                ProgramBlock::LoopBlock(_) => {
+                    interp.charge_costs_to(Some(self.function.name()), None, || "loop".to_string());
+                    self.finish_loop_body(
+                        evaluation_chip,
+                        layouter.namespace(|| format!("loop in step#{}", interp.step)),
+                    )?;
+                    Ok(None)
+                }
+                ProgramBlock::ConditionalBlock(_) => {
+                    self.end_arm(evaluation_chip, layouter, interp, ArmExit::Merge)
+                }
+                ProgramBlock::Block(_) => Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
+            },
+            ExitStatus::Abort(pc, error_code) => {
+                // the aborting block must not run in a valid proof
+                evaluation_chip
+                    .abort(
+                        layouter.namespace(|| format!("abort in step#{}", interp.step)),
+                        self.current_block.condition(),
+                    )
+                    .map_err(RuntimeError::from)?;
+                if self.current_block.condition().value() == Some(F::one()) {
+                    interp.abort = Some(ScriptAbort {
+                        function: self.function.pretty_string(),
+                        pc,
+                        code: error_code,
+                    });
+                    return Err(
+                        RuntimeError::new(StatusCode::MoveAbort).with_message(format!(
+                            "Move bytecode {} aborted with error code {}",
+                            self.function.pretty_string(),
+                            error_code
+                        )),
+                    );
+                }
+                trace!("handle Abort");
+                match &self.current_block {
+                    ProgramBlock::ConditionalBlock(cb) => {
+                        // what the aborting arm left on the stack is dropped
+                        while interp.stack.len() > cb.stack_height {
+                            interp.stack.pop()?;
+                        }
+                        self.end_arm(
+                            evaluation_chip,
+                            layouter,
+                            interp,
+                            ArmExit::Abort(pc, error_code),
+                        )
+                    }
+                    // the iteration doesn't run, the loop has been left before
+                    ProgramBlock::LoopBlock(_) => {
+                        self.leave_loop()?;
+                        Ok(None)
+                    }
+                    // the function aborts, and so does the call in its caller
+                    ProgramBlock::Block(_) => Ok(Some(ExitStatus::Abort(pc, error_code))),
+                }
             }
-            _ => Err(RuntimeError::new(StatusCode::ProgramBlockError)
-                .with_message("Should not reach here".to_string())),
         }
     }
 
+    /// The function called by the current block aborts without running,
+    /// the call aborts the current block. Returns the status the frame exits
+    /// with, or None if the frame goes on running.
+    pub fn abort_call(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+        error_code: u128,
+    ) -> VmResult<Option<ExitStatus<F>>> {
+        let pc = self.current_block.pc();
+        self.handle_status(
+            evaluation_chip,
+            &mut layouter,
+            interp,
+            ExitStatus::Abort(pc, error_code),
+        )
+    }
+
     pub fn execute(
         &mut self,
         evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
         interp: &mut Interpreter<F>,
+        loader: &MoveLoader,
+        state: &StateStore,
     ) -> VmResult<ExitStatus<F>> {
+        let function = self.function.clone();
+        let ctx = ExecutionContext {
+            function: &function,
+            resolver: loader.resolver(&self.function),
+            loader,
+            state,
+        };
         loop {
-            let status = self.current_block.execute(
-                evaluation_chip,
-                layouter.namespace(|| format!("into block in step#{}", interp.step)),
-                interp,
-            )?;
-            match status {
-                ExitStatus::Return => return Ok(ExitStatus::Return),
-                ExitStatus::Call(index) => return Ok(ExitStatus::Call(index)),
-                ExitStatus::ConditionalBranch(cb) => {
-                    trace!("handle conditional branch");
-                    let block = self.prepare_conditional_block(cb.pc, cb.condition)?;
-                    trace!("{:?}", block);
-                    self.blocks.push(self.current_block.clone())?;
-                    self.current_block = block;
-                }
-                ExitStatus::BranchEnd(pc) => match &mut self.current_block {
-                    ProgramBlock::ConditionalBlock(cb) => {
-                        match (&mut cb.true_branch, &mut cb.false_branch) {
-                            (Some(t_branch), Some(f_branch)) => {
-                                if t_branch.is_running {
-                                    debug_assert!(t_branch.block.end() == Some(pc));
-                                    trace!("switch conditional branch");
-                                    t_branch.is_running = false;
-                                    f_branch.is_running = true;
-                                } else {
-                                    debug_assert!(f_branch.is_running);
-                                    debug_assert!(f_branch.block.end() == Some(pc));
-                                    trace!("merge the branch");
-                                    let mut next_running = self.blocks.pop().ok_or_else(|| {
-                                        RuntimeError::new(StatusCode::ShouldNotReachHere)
-                                    })?;
-                                    next_running.merge_locals(
-                                        evaluation_chip,
-                                        layouter.namespace(|| {
-                                            format!("merge locals in step#{}", interp.step)
-                                        }),
-                                        t_branch.block.locals(),
-                                        f_branch.block.locals(),
-                                        t_branch.block.condition(),
-                                    )?;
-                                    self.current_block = next_running;
-                                    self.current_block.set_pc(pc + 1);
-                                }
-                            }
-                            (Some(t_branch), None) => {
-                                debug_assert!(t_branch.block.end() == Some(pc));
-                                trace!("merge the branch");
-                                let mut next_running = self.blocks.pop().ok_or_else(|| {
-                                    RuntimeError::new(StatusCode::ShouldNotReachHere)
-                                })?;
-                                next_running.set_locals(t_branch.block.locals().clone());
-                                self.current_block = next_running;
-                                self.current_block.set_pc(pc + 1);
-                            }
-                            (None, Some(f_branch)) => {
-                                debug_assert!(f_branch.block.end() == Some(pc));
-                                trace!("merge the branch");
-                                let mut next_running = self.blocks.pop().ok_or_else(|| {
-                                    RuntimeError::new(StatusCode::ShouldNotReachHere)
-                                })?;
-                                next_running.set_locals(f_branch.block.locals().clone());
-                                self.current_block = next_running;
-                                self.current_block.set_pc(pc + 1);
-                            }
-                            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
-                        }
-                    }
-                    _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
-                },
-                ExitStatus::Abort(pc, error_code) => match &mut self.current_block {
-                    ProgramBlock::ConditionalBlock(cb) => {
-                        match (&mut cb.true_branch, &mut cb.false_branch) {
-                            (None, Some(f_branch)) => {
-                                debug_assert!(f_branch.block.end() == Some(pc));
-                                trace!("handle Abort");
-                                let cond = f_branch.block.condition();
-                                self.current_block = self.blocks.pop().ok_or_else(|| {
-                                    RuntimeError::new(StatusCode::ShouldNotReachHere)
-                                })?;
-                                self.current_block.set_pc(pc + 1);
-
-                                // todo: error handle
-                                if cond == Some(F::one()) {
-                                    return Err(RuntimeError::new(StatusCode::MoveAbort)
-                                        .with_message(format!(
-                                            "Move bytecode {} aborted with error code {}",
-                                            self.function.pretty_string(),
-                                            error_code
-                                        )));
-                                }
-                            }
-                            _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
-                        }
+            let status = self
+                .current_block
+                .execute(
+                    evaluation_chip,
+                    layouter.namespace(|| format!("into block in step#{}", interp.step)),
+                    interp,
+                    &ctx,
+                )
+                .map_err(|e| match e.status_code() {
+                    StatusCode::ArithmeticError
+                    | StatusCode::VectorOperationError
+                    | StatusCode::GlobalStateError => {
+                        let message = format!(
+                            "{} in function {}",
+                            e.message().unwrap_or_default(),
+                            self.function.name()
+                        );
+                        e.with_message(message)
                     }
-                    _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
-                },
+                    _ => e,
+                })?;
+            if let Some(status) =
+                self.handle_status(evaluation_chip, &mut layouter, interp, status)?
+            {
+                return Ok(status);
             }
         }
     }
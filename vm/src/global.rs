@@ -0,0 +1,700 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::EvaluationChip;
+use crate::chips::instructions::Opcode;
+use crate::frame::ExecutionContext;
+use crate::state_tree::{StateTree, TREE_DEPTH};
+use crate::value::Value;
+use error::{RuntimeError, StatusCode, VmResult};
+use halo2_proofs::{arithmetic::FieldExt, circuit::Layouter, plonk::Error};
+use move_core_types::language_storage::StructTag;
+use movelang::value::{convert_to_field, field_to_address, MoveValue, MoveValueType};
+
+/// A resource touched by the script at an address. The circuit holds a copy of
+/// the resource for every address value the script accesses it at, loaded as a
+/// private input from the state store when it is first accessed. The loaded
+/// copy is kept to prove it against the state root.
+#[derive(Clone, Debug)]
+pub struct GlobalResource<F: FieldExt> {
+    pub(crate) ty: MoveValueType,
+    pub(crate) tag: StructTag,
+    pub(crate) address: Value<F>,
+    pub(crate) exists: Value<F>, // bool
+    pub(crate) value: Value<F>,  // all zeros if the resource doesn't exist
+    pub(crate) initial_exists: Value<F>,
+    pub(crate) initial_value: Value<F>,
+    /// Whether an earlier copy of the same type is at the same address, the
+    /// earlier copy is committed then. None if there is no earlier copy.
+    pub(crate) duplicate: Option<Value<F>>,
+    /// The time of the last write this copy has seen.
+    written: usize,
+}
+
+impl<F: FieldExt> GlobalResource<F> {
+    pub fn tag(&self) -> &StructTag {
+        &self.tag
+    }
+
+    pub fn address(&self) -> &Value<F> {
+        &self.address
+    }
+
+    pub fn exists(&self) -> &Value<F> {
+        &self.exists
+    }
+
+    pub fn value(&self) -> &Value<F> {
+        &self.value
+    }
+}
+
+/// Two copies of a resource type, `same` is whether they are at the same
+/// address.
+#[derive(Clone, Debug)]
+struct Alias<F: FieldExt> {
+    first: usize,
+    second: usize,
+    same: Value<F>, // bool
+}
+
+/// The resources touched by the script. They are shared by all frames and
+/// arms, so a write only takes effect if the block writing runs. The copies of
+/// a type at address values which turn out to be equal are kept in sync, a copy
+/// is brought up to date with the writes through the others when it is
+/// accessed.
+#[derive(Clone, Debug)]
+pub struct GlobalState<F: FieldExt> {
+    resources: Vec<GlobalResource<F>>,
+    aliases: Vec<Alias<F>>,
+    clock: usize,
+}
+
+impl<F: FieldExt> GlobalState<F> {
+    pub fn new() -> Self {
+        Self {
+            resources: vec![],
+            aliases: vec![],
+            clock: 0,
+        }
+    }
+
+    pub fn resources(&self) -> &[GlobalResource<F>] {
+        &self.resources
+    }
+
+    pub fn get(&self, index: usize) -> VmResult<&GlobalResource<F>> {
+        self.resources.get(index).ok_or_else(|| {
+            RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("no global resource #{}", index))
+        })
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> VmResult<&mut GlobalResource<F>> {
+        self.resources.get_mut(index).ok_or_else(|| {
+            RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("no global resource #{}", index))
+        })
+    }
+
+    /// The value of the resource, to be written.
+    pub fn value_mut(&mut self, index: usize) -> VmResult<&mut Value<F>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let resource = self.get_mut(index)?;
+        resource.written = clock;
+        Ok(&mut resource.value)
+    }
+
+    /// The index of the resource of type `ty` at `address`, it is loaded if
+    /// the type hasn't been accessed at this address value yet. The resource is
+    /// brought up to date with the copies of its type at the same address.
+    pub fn access(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        ctx: &ExecutionContext,
+        ty: MoveValueType,
+        address: Value<F>,
+        vector_capacity: usize,
+    ) -> VmResult<usize> {
+        if let Some(index) = self
+            .resources
+            .iter()
+            .position(|r| r.ty == ty && r.address.equals(&address))
+        {
+            self.refresh(evaluation_chip, &mut layouter, index, false)?;
+            return Ok(index);
+        }
+        let mut resource = load_resource(
+            evaluation_chip,
+            layouter.namespace(|| "load resource"),
+            ctx,
+            ty,
+            address.clone(),
+            vector_capacity,
+        )?;
+        let index = self.resources.len();
+        let others: Vec<usize> = (0..index)
+            .filter(|&other| self.resources[other].ty == resource.ty)
+            .collect();
+        if !others.is_empty() {
+            // whether the copies alias holds on every path, not only the
+            // running one
+            let one = evaluation_chip
+                .load_constant(
+                    layouter.namespace(|| "load true"),
+                    F::one(),
+                    MoveValueType::Bool,
+                )
+                .map_err(RuntimeError::from)?;
+            let mut duplicate: Option<Value<F>> = None;
+            for other in others {
+                let same = evaluation_chip
+                    .binary_op(
+                        layouter.namespace(|| format!("same address as #{}", other)),
+                        Opcode::Eq,
+                        address.clone(),
+                        self.resources[other].address.clone(),
+                        one.clone(),
+                    )
+                    .map_err(RuntimeError::from)?;
+                duplicate = Some(match duplicate {
+                    None => same.clone(),
+                    Some(duplicate) => evaluation_chip
+                        .binary_op(
+                            layouter.namespace(|| "duplicate"),
+                            Opcode::Or,
+                            duplicate,
+                            same.clone(),
+                            one.clone(),
+                        )
+                        .map_err(RuntimeError::from)?,
+                });
+                self.aliases.push(Alias {
+                    first: other,
+                    second: index,
+                    same,
+                });
+            }
+            resource.duplicate = duplicate;
+        }
+        self.resources.push(resource);
+        // the loaded copy is only proven if it isn't a duplicate, otherwise it
+        // takes the state of the copies at its address
+        self.refresh(evaluation_chip, &mut layouter, index, true)?;
+        Ok(index)
+    }
+
+    /// Takes the state of the copies of the resource at the same address which
+    /// have been written since the resource was, or of all of them if `all`
+    /// is set, in the order they were written.
+    fn refresh(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: &mut impl Layouter<F>,
+        index: usize,
+        all: bool,
+    ) -> VmResult<()> {
+        let written = self.get(index)?.written;
+        let mut newer: Vec<(usize, Value<F>)> = self
+            .aliases
+            .iter()
+            .filter_map(|alias| {
+                let other = if alias.first == index {
+                    alias.second
+                } else if alias.second == index {
+                    alias.first
+                } else {
+                    return None;
+                };
+                Some((other, alias.same.clone()))
+            })
+            .filter(|(other, _)| all || self.resources[*other].written > written)
+            .collect();
+        newer.sort_by_key(|(other, _)| self.resources[*other].written);
+        for (other, same) in newer {
+            let other = self.resources[other].clone();
+            let mut resource = self.resources[index].clone();
+            if !other.exists.equals(&resource.exists) {
+                resource.exists = evaluation_chip
+                    .conditional_select(
+                        layouter.namespace(|| "existence at the same address"),
+                        other.exists,
+                        resource.exists,
+                        same.clone(),
+                    )
+                    .map_err(RuntimeError::from)?;
+            }
+            if !other.value.equals(&resource.value) {
+                resource.value = evaluation_chip
+                    .conditional_select(
+                        layouter.namespace(|| "value at the same address"),
+                        other.value,
+                        resource.value,
+                        same,
+                    )
+                    .map_err(RuntimeError::from)?;
+            }
+            resource.written = resource.written.max(other.written);
+            self.resources[index] = resource;
+        }
+        Ok(())
+    }
+
+    /// Publishes `value` as the resource, it must not exist yet.
+    pub fn move_to(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        index: usize,
+        value: Value<F>,
+        cond: Value<F>,
+    ) -> VmResult<()> {
+        let resource = self.get(index)?;
+        let (already_exists, _) = evaluation_chip
+            .branch_condition(
+                layouter.namespace(|| "resource exists"),
+                cond.clone(),
+                resource.exists.clone(),
+            )
+            .map_err(RuntimeError::from)?;
+        evaluation_chip
+            .abort(layouter.namespace(|| "abort"), already_exists)
+            .map_err(RuntimeError::from)?;
+        let value = evaluation_chip
+            .conditional_select(
+                layouter.namespace(|| "write resource"),
+                value,
+                resource.value.clone(),
+                cond.clone(),
+            )
+            .map_err(RuntimeError::from)?;
+        self.set_exists(evaluation_chip, &mut layouter, index, true, cond)?;
+        *self.value_mut(index)? = value;
+        Ok(())
+    }
+
+    /// Removes the resource and returns its value, it must exist.
+    pub fn move_from(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        index: usize,
+        cond: Value<F>,
+    ) -> VmResult<Value<F>> {
+        self.check_exists(
+            evaluation_chip,
+            layouter.namespace(|| "check resource"),
+            index,
+            cond.clone(),
+        )?;
+        self.set_exists(evaluation_chip, &mut layouter, index, false, cond)?;
+        Ok(self.get(index)?.value.clone())
+    }
+
+    /// Aborts unless the resource exists.
+    pub fn check_exists(
+        &self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        index: usize,
+        cond: Value<F>,
+    ) -> VmResult<()> {
+        let (_, missing) = evaluation_chip
+            .branch_condition(
+                layouter.namespace(|| "resource exists"),
+                cond,
+                self.get(index)?.exists.clone(),
+            )
+            .map_err(RuntimeError::from)?;
+        evaluation_chip
+            .abort(layouter.namespace(|| "abort"), missing)
+            .map_err(RuntimeError::from)
+    }
+
+    /// Proves the resources as loaded against the state root before the
+    /// script, and computes the root after it. The leaves are updated one by
+    /// one in the order the resources were first touched, every path is
+    /// checked against the root left by the previous update. A duplicate of an
+    /// earlier resource leaves the root as it is. Returns the old and the new
+    /// root, `tree` is updated if the witness is known.
+    pub fn commit(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        tree: &mut StateTree<F>,
+    ) -> VmResult<(Value<F>, Value<F>)> {
+        for index in 0..self.resources.len() {
+            self.refresh(
+                evaluation_chip,
+                &mut layouter.namespace(|| format!("refresh resource #{}", index)),
+                index,
+                false,
+            )?;
+        }
+        let mut roots: Option<(Value<F>, Value<F>)> = None;
+        for (i, resource) in self.resources.iter().enumerate() {
+            let mut layouter = layouter.namespace(|| format!("commit resource #{}", i));
+            let type_id = tree.type_id(&resource.tag);
+            let duplicate = resource
+                .duplicate
+                .as_ref()
+                .and_then(|duplicate| duplicate.value())
+                == Some(F::one());
+            // the witness is unknown during keygen
+            let index = match resource.address.value() {
+                Some(address) => {
+                    let key = tree.key(address, type_id);
+                    let index = StateTree::index(key)?;
+                    if !duplicate {
+                        tree.claim(index, key, &resource.tag)?;
+                    }
+                    Some(index)
+                }
+                None => None,
+            };
+            let siblings = index.map(|index| tree.siblings(index));
+            let update =
+                commit_resource(evaluation_chip, &mut layouter, resource, type_id, siblings)
+                    .map_err(RuntimeError::from)?;
+            if let (Some(index), Some(leaf), false) = (index, update.new_leaf.value(), duplicate) {
+                tree.update(index, leaf);
+            }
+            roots = match (roots, &resource.duplicate) {
+                (None, _) => Some((update.old_root, update.new_root)),
+                (Some((first, last)), None) => {
+                    evaluation_chip
+                        .assert_equal(
+                            layouter.namespace(|| "previous root"),
+                            update.old_root,
+                            last,
+                        )
+                        .map_err(RuntimeError::from)?;
+                    Some((first, update.new_root))
+                }
+                (Some((first, last)), Some(duplicate)) => {
+                    let new_root = commit_unless_duplicate(
+                        evaluation_chip,
+                        &mut layouter,
+                        update,
+                        last,
+                        duplicate.clone(),
+                    )
+                    .map_err(RuntimeError::from)?;
+                    Some((first, new_root))
+                }
+            };
+        }
+        match roots {
+            Some(roots) => Ok(roots),
+            None => {
+                // the script doesn't touch the state, it runs on any root
+                let root = evaluation_chip
+                    .load_witness(layouter.namespace(|| "load state root"), Some(tree.root()))
+                    .map_err(RuntimeError::from)?;
+                Ok((root.clone(), root))
+            }
+        }
+    }
+
+    /// `exists = cond ? flag : exists`
+    fn set_exists(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        layouter: &mut impl Layouter<F>,
+        index: usize,
+        flag: bool,
+        cond: Value<F>,
+    ) -> VmResult<()> {
+        let flag = evaluation_chip
+            .load_constant(
+                layouter.namespace(|| "load existence"),
+                if flag { F::one() } else { F::zero() },
+                MoveValueType::Bool,
+            )
+            .map_err(RuntimeError::from)?;
+        let exists = evaluation_chip
+            .conditional_select(
+                layouter.namespace(|| "write existence"),
+                flag,
+                self.get(index)?.exists.clone(),
+                cond,
+            )
+            .map_err(RuntimeError::from)?;
+        self.clock += 1;
+        let clock = self.clock;
+        let resource = self.get_mut(index)?;
+        resource.exists = exists;
+        resource.written = clock;
+        Ok(())
+    }
+}
+
+impl<F: FieldExt> Default for GlobalState<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn load_resource<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    mut layouter: impl Layouter<F>,
+    ctx: &ExecutionContext,
+    ty: MoveValueType,
+    address: Value<F>,
+    vector_capacity: usize,
+) -> VmResult<GlobalResource<F>> {
+    let tag = ctx.loader.struct_tag(&ty)?;
+    // the witness is unknown during keygen
+    let stored = match address.value() {
+        Some(address) => Some(ctx.state.resource(field_to_address(address)?, &tag)),
+        None => None,
+    };
+    let exists = stored
+        .as_ref()
+        .map(|v| if v.is_some() { F::one() } else { F::zero() });
+    let exists = evaluation_chip
+        .load_private(
+            layouter.namespace(|| "load resource existence"),
+            exists,
+            MoveValueType::Bool,
+        )
+        .map_err(RuntimeError::from)?;
+    let value = load_value(
+        evaluation_chip,
+        &mut layouter,
+        ctx,
+        &ty,
+        stored.as_ref().map(|v| v.as_ref()),
+        vector_capacity,
+    )?;
+    Ok(GlobalResource {
+        ty,
+        tag,
+        address,
+        initial_exists: exists.clone(),
+        initial_value: value.clone(),
+        exists,
+        value,
+        duplicate: None,
+        written: 0,
+    })
+}
+
+/// The update of the leaf of a resource in the state tree.
+struct LeafUpdate<F: FieldExt> {
+    old_root: Value<F>, // computed from the old leaf
+    new_root: Value<F>,
+    new_leaf: Value<F>,
+}
+
+/// `siblings` is the path of the leaf in the tree before the update.
+fn commit_resource<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    layouter: &mut impl Layouter<F>,
+    resource: &GlobalResource<F>,
+    type_id: F,
+    siblings: Option<Vec<F>>,
+) -> Result<LeafUpdate<F>, Error> {
+    let type_id = evaluation_chip.load_constant(
+        layouter.namespace(|| "load type id"),
+        type_id,
+        MoveValueType::U128,
+    )?;
+    let key = evaluation_chip.hash2(
+        layouter.namespace(|| "key"),
+        resource.address.clone(),
+        type_id,
+    )?;
+    let bits = evaluation_chip.key_bits(layouter.namespace(|| "key bits"), key.clone())?;
+    let siblings = (0..TREE_DEPTH)
+        .map(|level| {
+            evaluation_chip.load_witness(
+                layouter.namespace(|| format!("load sibling #{}", level)),
+                siblings.as_ref().map(|siblings| siblings[level]),
+            )
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let old_leaf = leaf(
+        evaluation_chip,
+        &mut layouter.namespace(|| "old leaf"),
+        key.clone(),
+        &resource.initial_exists,
+        &resource.initial_value,
+    )?;
+    let old_root = evaluation_chip.merkle_root(
+        layouter.namespace(|| "old root"),
+        old_leaf.clone(),
+        &bits,
+        &siblings,
+    )?;
+    // the layout can't depend on the witness, only a resource which is never
+    // written is known to be unchanged
+    if resource.exists.equals(&resource.initial_exists)
+        && resource.value.equals(&resource.initial_value)
+    {
+        return Ok(LeafUpdate {
+            old_root: old_root.clone(),
+            new_root: old_root,
+            new_leaf: old_leaf,
+        });
+    }
+    let new_leaf = leaf(
+        evaluation_chip,
+        &mut layouter.namespace(|| "new leaf"),
+        key,
+        &resource.exists,
+        &resource.value,
+    )?;
+    let new_root = evaluation_chip.merkle_root(
+        layouter.namespace(|| "new root"),
+        new_leaf.clone(),
+        &bits,
+        &siblings,
+    )?;
+    Ok(LeafUpdate {
+        old_root,
+        new_root,
+        new_leaf,
+    })
+}
+
+/// The root after `update` if the resource isn't a duplicate, its path must be
+/// checked against `last` then, otherwise `last`.
+fn commit_unless_duplicate<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    layouter: &mut impl Layouter<F>,
+    update: LeafUpdate<F>,
+    last: Value<F>,
+    duplicate: Value<F>,
+) -> Result<Value<F>, Error> {
+    let one = evaluation_chip.load_constant(
+        layouter.namespace(|| "load true"),
+        F::one(),
+        MoveValueType::Bool,
+    )?;
+    let (_, unique) = evaluation_chip.branch_condition(
+        layouter.namespace(|| "unique"),
+        one,
+        duplicate.clone(),
+    )?;
+    let same_root = evaluation_chip.binary_op(
+        layouter.namespace(|| "previous root"),
+        Opcode::Eq,
+        update.old_root,
+        last.clone(),
+        unique.clone(),
+    )?;
+    let (_, mismatch) = evaluation_chip.branch_condition(
+        layouter.namespace(|| "path mismatch"),
+        unique,
+        same_root,
+    )?;
+    evaluation_chip.abort(layouter.namespace(|| "abort"), mismatch)?;
+    evaluation_chip.conditional_select(
+        layouter.namespace(|| "root unless duplicate"),
+        last,
+        update.new_root,
+        duplicate,
+    )
+}
+
+/// `exists ? hash2(key, hash of the value) : 0`
+fn leaf<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    layouter: &mut impl Layouter<F>,
+    key: Value<F>,
+    exists: &Value<F>,
+    value: &Value<F>,
+) -> Result<Value<F>, Error> {
+    let values = evaluation_chip.flatten_value(layouter, value)?;
+    let hash = evaluation_chip.hash_values(layouter.namespace(|| "hash value"), values)?;
+    let leaf = evaluation_chip.hash2(layouter.namespace(|| "leaf"), key, hash)?;
+    let zero = evaluation_chip.load_constant(
+        layouter.namespace(|| "load zero"),
+        F::zero(),
+        MoveValueType::U128,
+    )?;
+    evaluation_chip.conditional_select(
+        layouter.namespace(|| "leaf if exists"),
+        leaf,
+        zero,
+        exists.clone(),
+    )
+}
+
+/// Loads a private value of type `ty`, field by field. The outer option is
+/// None during keygen, the inner one is None if the value is missing, it is
+/// loaded as zeros then.
+fn load_value<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    layouter: &mut impl Layouter<F>,
+    ctx: &ExecutionContext,
+    ty: &MoveValueType,
+    value: Option<Option<&MoveValue>>,
+    vector_capacity: usize,
+) -> VmResult<Value<F>> {
+    match ty {
+        MoveValueType::Struct(_) | MoveValueType::StructInstantiation(..) => {
+            let field_types = ctx.loader.struct_field_types(ty)?;
+            let mut fields = Vec::with_capacity(field_types.len());
+            for (i, field_ty) in field_types.iter().enumerate() {
+                let field = value.map(|v| match v {
+                    Some(MoveValue::Struct(s)) => s.fields().get(i),
+                    _ => None,
+                });
+                fields.push(load_value(
+                    evaluation_chip,
+                    &mut layouter.namespace(|| format!("load field #{}", i)),
+                    ctx,
+                    field_ty,
+                    field,
+                    vector_capacity,
+                )?);
+            }
+            Ok(Value::new_struct(fields))
+        }
+        MoveValueType::Vector(elem_ty) => {
+            // the slots of a vector are single cells
+            if matches!(
+                **elem_ty,
+                MoveValueType::Vector(_)
+                    | MoveValueType::Struct(_)
+                    | MoveValueType::StructInstantiation(..)
+            ) {
+                return Err(RuntimeError::new(StatusCode::UnsupportedMoveType)
+                    .with_message(format!("a stored vector of {:?} is not supported", elem_ty)));
+            }
+            let values: Option<Vec<F>> = value.map(|v| match v {
+                Some(MoveValue::Vector(items)) => {
+                    items.iter().cloned().map(convert_to_field).collect()
+                }
+                _ => vec![],
+            });
+            if matches!(&values, Some(values) if values.len() > vector_capacity) {
+                return Err(
+                    RuntimeError::new(StatusCode::VectorOperationError).with_message(format!(
+                        "a stored vector exceeds the vector capacity {}",
+                        vector_capacity
+                    )),
+                );
+            }
+            evaluation_chip
+                .load_private_vector(
+                    layouter.namespace(|| "load vector"),
+                    values,
+                    *elem_ty.clone(),
+                    vector_capacity,
+                )
+                .map_err(RuntimeError::from)
+        }
+        _ => {
+            let field = value.map(|v| v.cloned().map(convert_to_field).unwrap_or_else(F::zero));
+            evaluation_chip
+                .load_private(layouter.namespace(|| "load value"), field, ty.clone())
+                .map_err(RuntimeError::from)
+        }
+    }
+}
@@ -3,24 +3,54 @@
 
 use crate::chips::evaluation_chip::EvaluationChip;
 use crate::frame::Frame;
+use crate::global::GlobalState;
 use crate::locals::Locals;
 use crate::program_block::ExitStatus;
+use crate::sizing::CostOwner;
 use crate::stack::{CallStack, CondStack, EvalStack};
 use crate::value::Value;
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::{arithmetic::FieldExt, circuit::Layouter};
 use logger::prelude::*;
 use move_vm_runtime::loader::Function;
-use movelang::argument::{convert_from, ScriptArguments};
+use movelang::argument::{convert_from, convert_vector_from, ScriptArguments};
 use movelang::loader::MoveLoader;
+use movelang::state::StateStore;
 use movelang::value::MoveValueType;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
 
+// how many times a loop is unrolled if not configured
+pub const DEFAULT_LOOP_BOUND: usize = 16;
+// how many elements a vector can hold if not configured
+pub const DEFAULT_VECTOR_CAPACITY: usize = 16;
+
+/// Where a script aborted and the code it aborted with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptAbort {
+    pub function: String,
+    pub pc: u16,
+    pub code: u128,
+}
+
 pub struct Interpreter<F: FieldExt> {
     pub stack: EvalStack<F>,
     pub frames: CallStack<F>,
     pub conditions: CondStack<F>,
+    pub globals: GlobalState<F>,
+    // the script arguments as loaded, before the script runs
+    pub arguments: Vec<Value<F>>,
+    // set when an abort is taken, the script stops with a MoveAbort error
+    pub abort: Option<ScriptAbort>,
     pub step: u64,
+    // the steps taken under a true condition, the others are on paths the
+    // script doesn't take
+    pub executed_steps: u64,
+    pub loop_bound: usize,
+    pub vector_capacity: usize,
+    // what the cells laid out next are spent on, only kept to size the circuit
+    pub cost_owner: Option<Rc<RefCell<CostOwner>>>,
 }
 
 impl<F: FieldExt> Interpreter<F> {
@@ -29,7 +59,14 @@ impl<F: FieldExt> Interpreter<F> {
             stack: EvalStack::new(),
             frames: CallStack::new(),
             conditions: CondStack::new(),
+            globals: GlobalState::new(),
+            arguments: vec![],
+            abort: None,
             step: 0,
+            executed_steps: 0,
+            loop_bound: DEFAULT_LOOP_BOUND,
+            vector_capacity: DEFAULT_VECTOR_CAPACITY,
+            cost_owner: None,
         }
     }
 
@@ -49,6 +86,23 @@ impl<F: FieldExt> Interpreter<F> {
         &mut self.conditions
     }
 
+    /// Charges the cells laid out from now on to `opcode` at `pc` of
+    /// `function`, if the costs are counted.
+    pub fn charge_costs_to(
+        &self,
+        function: Option<&str>,
+        pc: Option<u16>,
+        opcode: impl FnOnce() -> String,
+    ) {
+        if let Some(owner) = &self.cost_owner {
+            *owner.borrow_mut() = CostOwner {
+                function: function.map(|name| name.to_string()),
+                pc,
+                opcode: opcode(),
+            };
+        }
+    }
+
     fn process_arguments(
         &mut self,
         locals: &mut Locals<F>,
@@ -68,6 +122,45 @@ impl<F: FieldExt> Interpreter<F> {
         };
 
         for (i, (arg, ty)) in arg_type_pairs.into_iter().enumerate() {
+            if let MoveValueType::Vector(elem_ty) = ty {
+                let values = match arg {
+                    Some(a) => {
+                        let values: Vec<F> = convert_vector_from(a)?;
+                        if values.len() > self.vector_capacity {
+                            return Err(RuntimeError::new(StatusCode::VectorOperationError)
+                                .with_message(format!(
+                                    "argument #{} has {} elements, the vector capacity is {}",
+                                    i,
+                                    values.len(),
+                                    self.vector_capacity
+                                )));
+                        }
+                        Some(values)
+                    }
+                    None => None,
+                };
+                let vector = evaluation_chip
+                    .load_private_vector(
+                        layouter.namespace(|| format!("load argument #{}", i)),
+                        values,
+                        *elem_ty,
+                        self.vector_capacity,
+                    )
+                    .map_err(|e| {
+                        debug!("Process arguments error: {:?}", e);
+                        RuntimeError::from(e)
+                    })?;
+                self.arguments.push(vector.clone());
+                locals.store(i, vector)?;
+                continue;
+            }
+            // an address argument passed for a signer is the signer of the script
+            let (ty, is_ref) = match ty {
+                MoveValueType::Reference(inner) if *inner == MoveValueType::Signer => {
+                    (MoveValueType::Signer, true)
+                }
+                ty => (ty, false),
+            };
             let val = match arg {
                 Some(a) => {
                     let value: F = convert_from(a)?;
@@ -86,21 +179,32 @@ impl<F: FieldExt> Interpreter<F> {
                     RuntimeError::from(e)
                 })?;
 
-            locals.store(i, Value::new_variable(cell.value(), cell.cell(), ty)?)?;
+            let value = Value::new_variable(cell.value(), cell.cell(), ty)?;
+            self.arguments.push(value.clone());
+            if is_ref {
+                locals.store(i, Value::ImmRef(Box::new(value)))?;
+            } else {
+                locals.store(i, value)?;
+            }
         }
 
         Ok(())
     }
 
-    fn make_frame(&mut self, func: Arc<Function>) -> VmResult<Frame<F>> {
+    /// The callee runs under the condition of the block the call is made in.
+    fn make_frame(&mut self, func: Arc<Function>, condition: Value<F>) -> VmResult<Frame<F>> {
         let mut locals = Locals::new(func.local_count());
         let arg_count = func.arg_count();
         for i in 0..arg_count {
             locals.store(arg_count - i - 1, self.stack.pop()?)?;
         }
-        Ok(Frame::new(0, 0, None, func, locals))
+        Frame::new(0, 0, None, func, locals, condition)
     }
 
+    /// Runs the script and returns its locals when it returns, the return
+    /// values are left on the stack. If the script returns from both arms of
+    /// a branch, their locals are merged.
+    #[allow(clippy::too_many_arguments)]
     pub fn run_script(
         &mut self,
         evaluation_chip: &EvaluationChip<F>,
@@ -109,9 +213,11 @@ impl<F: FieldExt> Interpreter<F> {
         args: Option<ScriptArguments>,
         arg_types: Vec<MoveValueType>,
         loader: &MoveLoader,
-    ) -> VmResult<()> {
+        state: &StateStore,
+    ) -> VmResult<Locals<F>> {
         let mut locals = Locals::new(entry.local_count());
 
+        self.charge_costs_to(Some(entry.name()), None, || "arguments".to_string());
         self.process_arguments(
             &mut locals,
             args,
@@ -120,33 +226,76 @@ impl<F: FieldExt> Interpreter<F> {
             layouter.namespace(|| format!("process arguments in step#{}", self.step)),
         )?;
 
-        let mut frame = Frame::new(0, 0, None, entry, locals);
+        // the script body always runs
+        let condition = evaluation_chip
+            .load_constant(
+                layouter.namespace(|| "load entry condition"),
+                F::one(),
+                MoveValueType::Bool,
+            )
+            .map_err(RuntimeError::from)?;
+        let mut frame = Frame::new(0, 0, None, entry, locals, condition)?;
         frame.print_frame();
+        let mut status = frame.execute(
+            evaluation_chip,
+            layouter.namespace(|| format!("into frame in step#{}", self.step)),
+            self,
+            loader,
+            state,
+        )?;
         loop {
-            let status = frame.execute(
-                evaluation_chip,
-                layouter.namespace(|| format!("into frame in step#{}", self.step)),
-                self,
-            )?;
             match status {
                 ExitStatus::Return => {
                     if let Some(caller_frame) = self.frames.pop() {
                         frame = caller_frame;
                         frame.current_block().add_pc();
                     } else {
-                        return Ok(());
+                        return Ok(frame.current_block().locals().clone());
                     }
                 }
                 ExitStatus::Call(index) => {
                     let func = loader.function_from_handle(frame.func(), index);
                     trace!("Call into function: {:?}", func.name());
-                    let callee_frame = self.make_frame(func)?;
+                    let condition = frame.current_block().condition();
+                    let callee_frame = self.make_frame(func, condition)?;
                     callee_frame.print_frame();
                     self.frames.push(frame)?;
                     frame = callee_frame;
                 }
+                // the function aborts under a condition that isn't known to
+                // hold, the constraints leave it to the block making the call
+                ExitStatus::Abort(_pc, error_code) => match self.frames.pop() {
+                    Some(caller_frame) => {
+                        frame = caller_frame;
+                        if let Some(caller_status) = frame.abort_call(
+                            evaluation_chip,
+                            layouter.namespace(|| format!("abort call in step#{}", self.step)),
+                            self,
+                            error_code,
+                        )? {
+                            status = caller_status;
+                            continue;
+                        }
+                    }
+                    None => {
+                        return Err(
+                            RuntimeError::new(StatusCode::MoveAbort).with_message(format!(
+                                "Move bytecode {} aborted with error code {}",
+                                frame.func().pretty_string(),
+                                error_code
+                            )),
+                        )
+                    }
+                },
                 _ => return Err(RuntimeError::new(StatusCode::ShouldNotReachHere)),
             }
+            status = frame.execute(
+                evaluation_chip,
+                layouter.namespace(|| format!("into frame in step#{}", self.step)),
+                self,
+                loader,
+                state,
+            )?;
         }
     }
 }
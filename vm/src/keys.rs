@@ -0,0 +1,183 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::circuit::MoveCircuit;
+use crate::proof::{io_error, modules_hash, read_bytes, read_u32, script_hash};
+use error::{RuntimeError, StatusCode, VmResult};
+use halo2_proofs::pasta::Fp;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Bumped whenever the layout of a key info file changes.
+pub const KEY_FORMAT_VERSION: u32 = 2;
+const KEY_MAGIC: &[u8; 4] = b"ZKMK";
+
+/// What a pair of proving and verifying keys was generated for. The keys
+/// aren't saved: in the pinned halo2_proofs 0.1 their fields are private,
+/// there is no function to write or read them, and `keygen_vk` and
+/// `keygen_pk` are the only way to build them. They are regenerated from the
+/// saved params instead, then checked against `vk_fingerprint`.
+/// The loop bound, vector capacity and lane count are kept so the circuit
+/// can be rebuilt with the same shape. A key info file holds, little endian:
+///
+/// | magic | version | k | loop bound | vector capacity | lanes | script hash | modules hash | vk fingerprint |
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyInfo {
+    pub k: u32,
+    pub loop_bound: u32,
+    pub vector_capacity: u32,
+    pub lanes: u32,
+    pub script_hash: [u8; 32],
+    pub modules_hash: [u8; 32],
+    pub vk_fingerprint: [u8; 32],
+}
+
+impl KeyInfo {
+    /// The info of a circuit before its keys are generated, the fingerprint
+    /// is left zero.
+    pub fn for_circuit(circuit: &MoveCircuit<Fp>, k: u32) -> VmResult<Self> {
+        Ok(Self {
+            k,
+            loop_bound: circuit.loop_bound() as u32,
+            vector_capacity: circuit.vector_capacity() as u32,
+            lanes: circuit.lanes() as u32,
+            script_hash: script_hash(circuit.script())?,
+            modules_hash: modules_hash(circuit.modules())?,
+            vk_fingerprint: [0u8; 32],
+        })
+    }
+
+    /// Gives the circuit the shape the keys were generated for.
+    pub fn configure(&self, circuit: &mut MoveCircuit<Fp>) {
+        circuit.set_loop_bound(self.loop_bound as usize);
+        circuit.set_vector_capacity(self.vector_capacity as usize);
+        circuit.set_lanes(self.lanes as usize);
+    }
+
+    /// Refuses keys generated for another script, module set, k, loop bound,
+    /// vector capacity or lane count.
+    pub fn check_compatible(&self, expected: &KeyInfo) -> VmResult<()> {
+        let mismatch = if self.k != expected.k {
+            Some(format!(
+                "keys are for k = {}, expected {}",
+                self.k, expected.k
+            ))
+        } else if self.loop_bound != expected.loop_bound {
+            Some(format!(
+                "keys are for loop bound {}, expected {}",
+                self.loop_bound, expected.loop_bound
+            ))
+        } else if self.vector_capacity != expected.vector_capacity {
+            Some(format!(
+                "keys are for vector capacity {}, expected {}",
+                self.vector_capacity, expected.vector_capacity
+            ))
+        } else if self.lanes != expected.lanes {
+            Some(format!(
+                "keys are for {} lanes, expected {}",
+                self.lanes, expected.lanes
+            ))
+        } else if self.script_hash != expected.script_hash {
+            Some("keys are generated for another script".to_string())
+        } else if self.modules_hash != expected.modules_hash {
+            Some("keys are generated for another module set".to_string())
+        } else {
+            None
+        };
+        match mismatch {
+            Some(message) => {
+                Err(RuntimeError::new(StatusCode::IncompatibleKey).with_message(message))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> VmResult<()> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(KEY_MAGIC);
+        bytes.extend_from_slice(&KEY_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.k.to_le_bytes());
+        bytes.extend_from_slice(&self.loop_bound.to_le_bytes());
+        bytes.extend_from_slice(&self.vector_capacity.to_le_bytes());
+        bytes.extend_from_slice(&self.lanes.to_le_bytes());
+        bytes.extend_from_slice(&self.script_hash);
+        bytes.extend_from_slice(&self.modules_hash);
+        bytes.extend_from_slice(&self.vk_fingerprint);
+        writer.write_all(&bytes).map_err(io_error)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> VmResult<Self> {
+        let magic: [u8; 4] = read_bytes(reader)?;
+        if &magic != KEY_MAGIC {
+            return Err(RuntimeError::new(StatusCode::ProofFormatError)
+                .with_message("not a key info file".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != KEY_FORMAT_VERSION {
+            return Err(
+                RuntimeError::new(StatusCode::ProofFormatError).with_message(format!(
+                    "key format version {} is not supported, expected {}",
+                    version, KEY_FORMAT_VERSION
+                )),
+            );
+        }
+        Ok(Self {
+            k: read_u32(reader)?,
+            loop_bound: read_u32(reader)?,
+            vector_capacity: read_u32(reader)?,
+            lanes: read_u32(reader)?,
+            script_hash: read_bytes(reader)?,
+            modules_hash: read_bytes(reader)?,
+            vk_fingerprint: read_bytes(reader)?,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> VmResult<()> {
+        let mut file = File::create(path).map_err(io_error)?;
+        self.write(&mut file)
+    }
+
+    pub fn load(path: &Path) -> VmResult<Self> {
+        let mut file = File::open(path).map_err(io_error)?;
+        Self::read(&mut file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_file() {
+        let info = KeyInfo {
+            k: 10,
+            loop_bound: 16,
+            vector_capacity: 16,
+            lanes: 2,
+            script_hash: [1u8; 32],
+            modules_hash: [2u8; 32],
+            vk_fingerprint: [3u8; 32],
+        };
+        let mut bytes = vec![];
+        info.write(&mut bytes).unwrap();
+        let loaded = KeyInfo::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, info);
+
+        // the fingerprint is only known after key generation
+        let mut expected = info.clone();
+        expected.vk_fingerprint = [0u8; 32];
+        assert!(loaded.check_compatible(&expected).is_ok());
+        expected.k = 11;
+        assert!(loaded.check_compatible(&expected).is_err());
+        expected.k = 10;
+        expected.loop_bound = 8;
+        assert!(loaded.check_compatible(&expected).is_err());
+        expected.loop_bound = 16;
+        expected.lanes = 4;
+        assert!(loaded.check_compatible(&expected).is_err());
+        expected.lanes = 2;
+        expected.modules_hash = [4u8; 32];
+        assert!(loaded.check_compatible(&expected).is_err());
+    }
+}
@@ -1,12 +1,20 @@
 // Copyright (c) zkMove Authors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod cfg;
 pub mod chips;
 pub mod circuit;
+pub mod execution;
 pub mod frame;
+pub mod global;
 pub mod interpreter;
+pub mod keys;
 pub mod locals;
+pub mod package;
 pub mod program_block;
+pub mod proof;
 pub mod runtime;
+pub mod sizing;
 pub mod stack;
+pub mod state_tree;
 pub mod value;
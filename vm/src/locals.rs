@@ -25,7 +25,7 @@ impl<F: FieldExt> Locals<F> {
     pub fn store(&mut self, index: usize, value: Value<F>) -> VmResult<()> {
         let mut values = self.0.borrow_mut();
         match values.get_mut(index) {
-            // Todo: check ref count
+            // the bytecode verifier makes sure no reference to the local is alive
             Some(_v) => {
                 values[index] = value;
                 Ok(())
@@ -44,6 +44,31 @@ impl<F: FieldExt> Locals<F> {
     }
 }
 
+impl<F: FieldExt> Locals<F> {
+    /// The value a reference to local `index` points to, `path` holds the
+    /// field offsets from the local down to the value.
+    pub fn read_ref(&self, index: usize, path: &[usize]) -> VmResult<Value<F>> {
+        let values = self.0.borrow();
+        match values.get(index) {
+            Some(Value::Invalid) => Err(RuntimeError::new(StatusCode::CopyLocalError)),
+            Some(v) => Ok(v.field(path)?.clone()),
+            None => Err(RuntimeError::new(StatusCode::OutOfBounds)),
+        }
+    }
+
+    pub fn write_ref(&mut self, index: usize, path: &[usize], value: Value<F>) -> VmResult<()> {
+        let mut values = self.0.borrow_mut();
+        match values.get_mut(index) {
+            Some(Value::Invalid) => Err(RuntimeError::new(StatusCode::StoreLocalError)),
+            Some(v) => {
+                *v.field_mut(path)? = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::new(StatusCode::OutOfBounds)),
+        }
+    }
+}
+
 impl<F: FieldExt> Locals<F> {
     pub fn len(&self) -> usize {
         self.0.borrow().len()
@@ -0,0 +1,217 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::proof::{io_error, read_bytes, read_u32};
+use error::{RuntimeError, StatusCode, VmResult};
+use move_binary_format::file_format::CompiledScript;
+use move_binary_format::CompiledModule;
+use movelang::compiler::{SourceLine, SourceLines};
+use movelang::state::StateStore;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Bumped whenever the layout of a package file changes.
+pub const PACKAGE_FORMAT_VERSION: u32 = 2;
+const PACKAGE_MAGIC: &[u8; 4] = b"ZKMS";
+
+/// A compiled script with the modules it depends on and the values it
+/// exposes, all the circuit is built from besides its size and witness. A
+/// package file holds, little endian:
+///
+/// | magic | version | script | modules | public args | public outputs | source lines |
+///
+/// where the script is a u32 length followed by its bytecode, the modules a
+/// u32 count followed by each module the same way, and the public args and
+/// outputs a u32 count followed by u32 indices. The source lines are the
+/// file names, a u32 count followed by each name the same way as the
+/// script, then a u32 count of functions, each its name, a u32 count of
+/// instructions and for each of them a u32 file number, 0 for no line and
+/// else the index of the file plus 1, and a u32 line.
+#[derive(Clone, Debug)]
+pub struct ScriptPackage {
+    pub script: CompiledScript,
+    pub modules: Vec<CompiledModule>,
+    pub public_args: Vec<usize>,
+    pub public_outputs: Vec<usize>,
+    /// Where the instructions are in the sources, for the profile.
+    pub source_lines: SourceLines,
+}
+
+impl ScriptPackage {
+    /// The state the script runs against, holding the modules of the package.
+    pub fn state(&self) -> StateStore {
+        let mut state = StateStore::new();
+        for module in self.modules.iter() {
+            state.add_module(module.clone());
+        }
+        state
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> VmResult<()> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(PACKAGE_MAGIC);
+        bytes.extend_from_slice(&PACKAGE_FORMAT_VERSION.to_le_bytes());
+        let mut script = vec![];
+        self.script.serialize(&mut script).map_err(|e| {
+            RuntimeError::new(StatusCode::ScriptLoadingError)
+                .with_message(format!("serialize script failed: {:?}", e))
+        })?;
+        write_blob(&mut bytes, &script);
+        bytes.extend_from_slice(&(self.modules.len() as u32).to_le_bytes());
+        for module in self.modules.iter() {
+            let mut module_bytes = vec![];
+            module.serialize(&mut module_bytes).map_err(|e| {
+                RuntimeError::new(StatusCode::ScriptLoadingError)
+                    .with_message(format!("serialize module failed: {:?}", e))
+            })?;
+            write_blob(&mut bytes, &module_bytes);
+        }
+        write_indices(&mut bytes, &self.public_args);
+        write_indices(&mut bytes, &self.public_outputs);
+        write_source_lines(&mut bytes, &self.source_lines);
+        writer.write_all(&bytes).map_err(io_error)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> VmResult<Self> {
+        let magic: [u8; 4] = read_bytes(reader)?;
+        if &magic != PACKAGE_MAGIC {
+            return Err(RuntimeError::new(StatusCode::ProofFormatError)
+                .with_message("not a package file".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != PACKAGE_FORMAT_VERSION {
+            return Err(
+                RuntimeError::new(StatusCode::ProofFormatError).with_message(format!(
+                    "package format version {} is not supported, expected {}",
+                    version, PACKAGE_FORMAT_VERSION
+                )),
+            );
+        }
+        let script = CompiledScript::deserialize(&read_blob(reader)?).map_err(|e| {
+            RuntimeError::new(StatusCode::ScriptLoadingError)
+                .with_message(format!("deserialize script failed: {:?}", e))
+        })?;
+        let count = read_u32(reader)?;
+        let mut modules = vec![];
+        for _ in 0..count {
+            modules.push(
+                CompiledModule::deserialize(&read_blob(reader)?).map_err(|e| {
+                    RuntimeError::new(StatusCode::ScriptLoadingError)
+                        .with_message(format!("deserialize module failed: {:?}", e))
+                })?,
+            );
+        }
+        Ok(Self {
+            script,
+            modules,
+            public_args: read_indices(reader)?,
+            public_outputs: read_indices(reader)?,
+            source_lines: read_source_lines(reader)?,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> VmResult<()> {
+        let mut file = File::create(path).map_err(io_error)?;
+        self.write(&mut file)
+    }
+
+    pub fn load(path: &Path) -> VmResult<Self> {
+        let mut file = File::open(path).map_err(io_error)?;
+        Self::read(&mut file)
+    }
+}
+
+fn write_blob(bytes: &mut Vec<u8>, blob: &[u8]) {
+    bytes.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(blob);
+}
+
+fn read_blob<R: Read>(reader: &mut R) -> VmResult<Vec<u8>> {
+    let len = read_u32(reader)?;
+    let mut blob = vec![0u8; len as usize];
+    reader.read_exact(&mut blob).map_err(io_error)?;
+    Ok(blob)
+}
+
+fn write_indices(bytes: &mut Vec<u8>, indices: &[usize]) {
+    bytes.extend_from_slice(&(indices.len() as u32).to_le_bytes());
+    for index in indices.iter() {
+        bytes.extend_from_slice(&(*index as u32).to_le_bytes());
+    }
+}
+
+fn read_indices<R: Read>(reader: &mut R) -> VmResult<Vec<usize>> {
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| read_u32(reader).map(|index| index as usize))
+        .collect()
+}
+
+fn write_source_lines(bytes: &mut Vec<u8>, source_lines: &SourceLines) {
+    let mut files: Vec<&str> = vec![];
+    for line in source_lines.functions.values().flatten().flatten() {
+        if !files.contains(&line.file.as_str()) {
+            files.push(&line.file);
+        }
+    }
+    bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for file in files.iter() {
+        write_blob(bytes, file.as_bytes());
+    }
+    bytes.extend_from_slice(&(source_lines.functions.len() as u32).to_le_bytes());
+    for (function, lines) in source_lines.functions.iter() {
+        write_blob(bytes, function.as_bytes());
+        bytes.extend_from_slice(&(lines.len() as u32).to_le_bytes());
+        for line in lines.iter() {
+            let (file, line) = match line {
+                Some(line) => {
+                    let index = files.iter().position(|file| *file == line.file);
+                    (index.map_or(0, |index| index as u32 + 1), line.line)
+                }
+                None => (0, 0),
+            };
+            bytes.extend_from_slice(&file.to_le_bytes());
+            bytes.extend_from_slice(&line.to_le_bytes());
+        }
+    }
+}
+
+fn read_source_lines<R: Read>(reader: &mut R) -> VmResult<SourceLines> {
+    let count = read_u32(reader)?;
+    let mut files = vec![];
+    for _ in 0..count {
+        files.push(read_string(reader)?);
+    }
+    let count = read_u32(reader)?;
+    let mut functions = BTreeMap::new();
+    for _ in 0..count {
+        let function = read_string(reader)?;
+        let len = read_u32(reader)?;
+        let mut lines = vec![];
+        for _ in 0..len {
+            let file = read_u32(reader)? as usize;
+            let line = read_u32(reader)?;
+            lines.push(match file {
+                0 => None,
+                _ => Some(SourceLine {
+                    file: files.get(file - 1).cloned().ok_or_else(|| {
+                        RuntimeError::new(StatusCode::ProofFormatError)
+                            .with_message(format!("no source file {}", file - 1))
+                    })?,
+                    line,
+                }),
+            });
+        }
+        functions.insert(function, lines);
+    }
+    Ok(SourceLines { functions })
+}
+
+fn read_string<R: Read>(reader: &mut R) -> VmResult<String> {
+    String::from_utf8(read_blob(reader)?).map_err(|e| {
+        RuntimeError::new(StatusCode::ProofFormatError)
+            .with_message(format!("invalid string: {}", e))
+    })
+}
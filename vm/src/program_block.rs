@@ -3,24 +3,30 @@
 
 use crate::chips::evaluation_chip::EvaluationChip;
 use crate::chips::instructions::Opcode;
+use crate::frame::ExecutionContext;
 use crate::interpreter::Interpreter;
 use crate::locals::Locals;
+use crate::sizing::opcode_name;
+use crate::value::{FVector, Reference, Root, Value};
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::{arithmetic::FieldExt, circuit::Layouter};
 use logger::prelude::*;
 use move_binary_format::file_format::{Bytecode, FunctionHandleIndex};
-use movelang::value::MoveValueType;
+use movelang::value::{
+    move_cast, move_div, move_overflowing_add, move_overflowing_mul, move_overflowing_sub,
+    move_shl, move_shr, MoveValue, MoveValueType,
+};
 
 pub struct ConditionalBranch<F: FieldExt> {
     pub pc: u16,
-    pub condition: Option<F>,
+    pub condition: Value<F>, // the bool popped by BrTrue or BrFalse
 }
 
 pub enum ExitStatus<F: FieldExt> {
     Return,
     Call(FunctionHandleIndex),
     ConditionalBranch(ConditionalBranch<F>),
-    BranchEnd(u16 /* pc */),
+    BranchEnd(u16 /* pc where the block stops */),
     Abort(u16 /* pc */, u128 /* error code */),
 }
 
@@ -29,10 +35,10 @@ pub enum ExitStatus<F: FieldExt> {
 pub struct Block<F: FieldExt> {
     pc: u16,
     start: u16,
-    end: Option<u16>,
+    end: Option<u16>, // the block stops when it reaches end, which is not executed
     locals: Locals<F>,
     code: Vec<Bytecode>,
-    condition: Option<F>,
+    condition: Value<F>, // the block runs if it is 1
 }
 
 impl<F: FieldExt> Block<F> {
@@ -42,7 +48,7 @@ impl<F: FieldExt> Block<F> {
         end: Option<u16>,
         locals: Locals<F>,
         code: Vec<Bytecode>,
-        condition: Option<F>,
+        condition: Value<F>,
     ) -> Self {
         Block {
             pc,
@@ -78,8 +84,8 @@ impl<F: FieldExt> Block<F> {
         &mut self.locals
     }
 
-    pub fn condition(&self) -> Option<F> {
-        self.condition
+    pub fn condition(&self) -> Value<F> {
+        self.condition.clone()
     }
 
     pub fn execute(
@@ -87,6 +93,7 @@ impl<F: FieldExt> Block<F> {
         evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
         interp: &mut Interpreter<F>,
+        ctx: &ExecutionContext,
     ) -> VmResult<ExitStatus<F>> {
         macro_rules! load_constant {
             ($constant:expr, $ty:expr) => {{
@@ -105,12 +112,15 @@ impl<F: FieldExt> Block<F> {
             ($opcode:expr) => {{
                 let b = interp.stack.pop()?;
                 let a = interp.stack.pop()?;
+                binary_op!($opcode, a, b)
+            }};
+            ($opcode:expr, $a:expr, $b:expr) => {{
                 let c = evaluation_chip
                     .binary_op(
                         layouter.namespace(|| format!("step#{}", interp.step)),
                         $opcode,
-                        a,
-                        b,
+                        $a,
+                        $b,
                         self.condition(),
                     )
                     .map_err(|e| RuntimeError::from(e))?;
@@ -118,9 +128,137 @@ impl<F: FieldExt> Block<F> {
             }};
         }
 
+        macro_rules! arithmetic_op {
+            ($opcode:expr) => {{
+                let b = interp.stack.pop()?;
+                let a = interp.stack.pop()?;
+                // an arithmetic error aborts only if the block is really executed
+                if self.condition().value() == Some(F::one()) {
+                    let l: Option<MoveValue> = a.clone().into();
+                    let r: Option<MoveValue> = b.clone().into();
+                    if let Some(reason) =
+                        l.zip(r).and_then(|(l, r)| arithmetic_error($opcode, l, r))
+                    {
+                        return Err(RuntimeError::new(StatusCode::ArithmeticError)
+                            .with_message(format!("{} at pc {}", reason, self.pc)));
+                    }
+                }
+                binary_op!($opcode, a, b)
+            }};
+        }
+
+        macro_rules! equality_op {
+            ($opcode:expr) => {{
+                // references are compared by the values they point to
+                let b = interp.stack.pop()?;
+                let a = interp.stack.pop()?;
+                let b = deref(&mut self.locals, interp, b)?;
+                let a = deref(&mut self.locals, interp, a)?;
+                binary_op!($opcode, a, b)
+            }};
+        }
+
+        macro_rules! cast {
+            ($ty:expr) => {{
+                let a = interp.stack.pop()?;
+                // an out of range cast aborts only if the block is really executed
+                if self.condition().value() == Some(F::one()) {
+                    let value: Option<MoveValue> = a.clone().into();
+                    if let Some(v) = value {
+                        move_cast(v, $ty)?;
+                    }
+                }
+                let b = evaluation_chip
+                    .cast(
+                        layouter.namespace(|| format!("cast in step#{}", interp.step)),
+                        a,
+                        $ty,
+                        self.condition(),
+                    )
+                    .map_err(RuntimeError::from)?;
+                interp.stack.push(b)
+            }};
+        }
+
+        macro_rules! pack {
+            ($field_count:expr) => {{
+                let field_count = $field_count as usize;
+                let mut fields = Vec::with_capacity(field_count);
+                for _ in 0..field_count {
+                    fields.push(interp.stack.pop()?);
+                }
+                fields.reverse();
+                interp.stack.push(Value::new_struct(fields))
+            }};
+        }
+
+        macro_rules! unpack {
+            () => {{
+                for field in interp.stack.pop()?.unpack()? {
+                    interp.stack.push(field)?;
+                }
+                Ok(())
+            }};
+        }
+
+        macro_rules! borrow_field {
+            ($offset:expr) => {{
+                match interp.stack.pop()? {
+                    Value::Reference(reference) => interp
+                        .stack
+                        .push(Value::Reference(reference.borrow_field($offset))),
+                    Value::ImmRef(value) => {
+                        let field = value.field(&[$offset])?.clone();
+                        interp.stack.push(Value::ImmRef(Box::new(field)))
+                    }
+                    v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                        .with_message(format!("can not borrow field from {:?}", v))),
+                }
+            }};
+        }
+
+        macro_rules! vector_error {
+            ($failed:expr, $reason:expr) => {{
+                // a vector operation aborts only if the block is really executed
+                if self.condition().value() == Some(F::one()) && $failed {
+                    return Err(RuntimeError::new(StatusCode::VectorOperationError)
+                        .with_message(format!("{} at pc {}", $reason, self.pc)));
+                }
+            }};
+        }
+
+        macro_rules! global_error {
+            ($failed:expr, $reason:expr) => {{
+                // a global operation aborts only if the block is really executed
+                if self.condition().value() == Some(F::one()) && $failed {
+                    return Err(RuntimeError::new(StatusCode::GlobalStateError)
+                        .with_message(format!("{} at pc {}", $reason, self.pc)));
+                }
+            }};
+        }
+
+        macro_rules! access_global {
+            ($index:expr, $address:expr) => {{
+                let ty = ctx.resolver.get_struct_type($index);
+                interp.globals.access(
+                    evaluation_chip,
+                    layouter.namespace(|| format!("access resource in step#{}", interp.step)),
+                    ctx,
+                    ty,
+                    $address.with_ty(MoveValueType::Address),
+                    interp.vector_capacity,
+                )?
+            }};
+        }
+
         let code = self.code.as_slice();
         loop {
             for instruction in &code[self.pc as usize..] {
+                // the block stops where it joins the enclosing block again
+                if Some(self.pc) == self.end {
+                    trace!("reach BranchEnd at pc {}", self.pc);
+                    return Ok(ExitStatus::BranchEnd(self.pc));
+                }
                 trace!(
                     "step #{}, pc #{}, instruction {:?}",
                     interp.step,
@@ -128,6 +266,12 @@ impl<F: FieldExt> Block<F> {
                     instruction
                 );
                 interp.step += 1;
+                interp.charge_costs_to(Some(ctx.function.name()), Some(self.pc), || {
+                    opcode_name(instruction)
+                });
+                if self.condition.value() == Some(F::one()) {
+                    interp.executed_steps += 1;
+                }
 
                 match instruction {
                     Bytecode::LdU8(v) => {
@@ -155,40 +299,44 @@ impl<F: FieldExt> Block<F> {
                         Ok(())
                     }
                     Bytecode::Add => {
-                        binary_op!(Opcode::Add)
+                        arithmetic_op!(Opcode::Add)
                     }
                     Bytecode::Sub => {
-                        binary_op!(Opcode::Sub)
+                        arithmetic_op!(Opcode::Sub)
                     }
                     Bytecode::Mul => {
-                        binary_op!(Opcode::Mul)
+                        arithmetic_op!(Opcode::Mul)
                     }
                     Bytecode::Div => {
-                        binary_op!(Opcode::Div)
+                        arithmetic_op!(Opcode::Div)
                     }
                     Bytecode::Mod => {
-                        binary_op!(Opcode::Mod)
+                        arithmetic_op!(Opcode::Mod)
                     }
                     Bytecode::Ret => return Ok(ExitStatus::Return),
                     Bytecode::Call(index) => return Ok(ExitStatus::Call(*index)),
                     Bytecode::CopyLoc(v) => interp.stack.push(self.locals.copy(*v as usize)?),
                     Bytecode::StLoc(v) => self.locals.store(*v as usize, interp.stack.pop()?),
                     Bytecode::MoveLoc(v) => interp.stack.push(self.locals.move_(*v as usize)?),
-                    Bytecode::BrTrue(_offset) => {
-                        let cond = interp.stack.pop()?.value();
-                        return Ok(ExitStatus::ConditionalBranch(ConditionalBranch {
-                            pc: self.pc,
-                            condition: cond,
-                        }));
-                    }
-                    Bytecode::BrFalse(_offset) => {
-                        let cond = interp.stack.pop()?.value();
+                    // the frame picks the arms from the branch targets, BrFalse jumps
+                    // if the condition is false, and falls through to its true arm
+                    Bytecode::BrTrue(_) | Bytecode::BrFalse(_) => {
+                        let cond = interp.stack.pop()?;
                         return Ok(ExitStatus::ConditionalBranch(ConditionalBranch {
                             pc: self.pc,
                             condition: cond,
                         }));
                     }
                     Bytecode::Branch(offset) => {
+                        // loops are unrolled by the frame, the only back edge a block
+                        // follows is the one ending an iteration
+                        if *offset <= self.pc && Some(*offset) != self.end {
+                            return Err(RuntimeError::new(StatusCode::ProgramBlockError)
+                                .with_message(format!(
+                                    "unsupported backward branch from {} to {}",
+                                    self.pc, offset
+                                )));
+                        }
                         self.pc = *offset;
                         break;
                     }
@@ -201,10 +349,10 @@ impl<F: FieldExt> Block<F> {
                         return Ok(ExitStatus::Abort(self.pc, error_code));
                     }
                     Bytecode::Eq => {
-                        binary_op!(Opcode::Eq)
+                        equality_op!(Opcode::Eq)
                     }
                     Bytecode::Neq => {
-                        binary_op!(Opcode::Neq)
+                        equality_op!(Opcode::Neq)
                     }
                     Bytecode::And => {
                         binary_op!(Opcode::And)
@@ -227,19 +375,388 @@ impl<F: FieldExt> Block<F> {
                     Bytecode::Lt => {
                         binary_op!(Opcode::Lt)
                     }
+                    Bytecode::Gt => {
+                        binary_op!(Opcode::Gt)
+                    }
+                    Bytecode::Le => {
+                        binary_op!(Opcode::Le)
+                    }
+                    Bytecode::Ge => {
+                        binary_op!(Opcode::Ge)
+                    }
+                    Bytecode::BitAnd => {
+                        binary_op!(Opcode::BitAnd)
+                    }
+                    Bytecode::BitOr => {
+                        binary_op!(Opcode::BitOr)
+                    }
+                    Bytecode::Xor => {
+                        binary_op!(Opcode::Xor)
+                    }
+                    Bytecode::Shl => {
+                        arithmetic_op!(Opcode::Shl)
+                    }
+                    Bytecode::Shr => {
+                        arithmetic_op!(Opcode::Shr)
+                    }
+                    Bytecode::CastU8 => {
+                        cast!(MoveValueType::U8)
+                    }
+                    Bytecode::CastU64 => {
+                        cast!(MoveValueType::U64)
+                    }
+                    Bytecode::CastU128 => {
+                        cast!(MoveValueType::U128)
+                    }
+                    Bytecode::ImmBorrowLoc(v) | Bytecode::MutBorrowLoc(v) => {
+                        // the current frame isn't on the call stack while it runs
+                        let reference = Reference::new(interp.frames.len(), *v as usize);
+                        interp.stack.push(Value::Reference(reference))
+                    }
+                    Bytecode::ReadRef => {
+                        let reference = interp.stack.pop()?;
+                        let value = read_ref(&mut self.locals, interp, reference)?;
+                        interp.stack.push(value)
+                    }
+                    Bytecode::WriteRef => {
+                        let reference = interp.stack.pop()?.into_reference()?;
+                        let mut value = interp.stack.pop()?;
+                        // the locals of the current frame are copied into every arm and
+                        // merged later, but a caller's locals and the global resources
+                        // are written in place, so the write only takes effect if this
+                        // block runs
+                        if in_place(interp, &reference) {
+                            let old = load_ref(&mut self.locals, interp, &reference)?;
+                            if !old.equals(&value) {
+                                value = evaluation_chip
+                                    .conditional_select(
+                                        layouter.namespace(|| {
+                                            format!("write ref in step#{}", interp.step)
+                                        }),
+                                        value,
+                                        old,
+                                        self.condition(),
+                                    )
+                                    .map_err(RuntimeError::from)?;
+                            }
+                        }
+                        store_ref(&mut self.locals, interp, &reference, value)
+                    }
+                    // an immutable reference is the same reference
+                    Bytecode::FreezeRef => Ok(()),
+                    Bytecode::VecPack(index, count) => {
+                        let count = *count as usize;
+                        if count > interp.vector_capacity {
+                            return Err(RuntimeError::new(StatusCode::VectorOperationError)
+                                .with_message(format!(
+                                    "can not pack {} elements, the vector capacity is {}",
+                                    count, interp.vector_capacity
+                                )));
+                        }
+                        let mut slots = Vec::with_capacity(interp.vector_capacity);
+                        for _ in 0..count {
+                            slots.push(interp.stack.pop()?);
+                        }
+                        slots.reverse();
+                        slots.resize(interp.vector_capacity, Value::Invalid);
+                        let len = evaluation_chip
+                            .load_constant(
+                                layouter
+                                    .namespace(|| format!("load length in step#{}", interp.step)),
+                                F::from_u128(count as u128),
+                                MoveValueType::U64,
+                            )
+                            .map_err(RuntimeError::from)?;
+                        let elem_ty = ctx.resolver.single_type_at(*index);
+                        interp.stack.push(Value::new_vector(slots, len, elem_ty))
+                    }
+                    Bytecode::VecLen(_) => {
+                        let reference = interp.stack.pop()?;
+                        let vector =
+                            read_ref(&mut self.locals, interp, reference)?.into_vector()?;
+                        interp.stack.push(*vector.len)
+                    }
+                    Bytecode::VecImmBorrow(_) => {
+                        let index = interp.stack.pop()?;
+                        let reference = interp.stack.pop()?;
+                        let vector =
+                            read_ref(&mut self.locals, interp, reference)?.into_vector()?;
+                        vector_error!(out_of_bounds(&vector, &index), "vector index out of bounds");
+                        let elem = evaluation_chip
+                            .vector_borrow(
+                                layouter
+                                    .namespace(|| format!("vector borrow in step#{}", interp.step)),
+                                &vector,
+                                index,
+                                self.condition(),
+                            )
+                            .map_err(RuntimeError::from)?;
+                        interp.stack.push(Value::ImmRef(Box::new(elem)))
+                    }
+                    // the updated vector is selected by the condition of the block, so
+                    // it is written in place even to the locals of a caller or to a
+                    // global resource
+                    Bytecode::VecPushBack(_) => {
+                        let elem = interp.stack.pop()?;
+                        let reference = interp.stack.pop()?.into_reference()?;
+                        let vector =
+                            load_ref(&mut self.locals, interp, &reference)?.into_vector()?;
+                        vector_error!(
+                            vector.length() == Some(vector.capacity() as u64),
+                            "vector capacity exceeded"
+                        );
+                        let vector = evaluation_chip
+                            .vector_push_back(
+                                layouter
+                                    .namespace(|| format!("vector push in step#{}", interp.step)),
+                                vector,
+                                elem,
+                                self.condition(),
+                            )
+                            .map_err(RuntimeError::from)?;
+                        store_ref(&mut self.locals, interp, &reference, vector)
+                    }
+                    Bytecode::VecPopBack(_) => {
+                        let reference = interp.stack.pop()?.into_reference()?;
+                        let vector =
+                            load_ref(&mut self.locals, interp, &reference)?.into_vector()?;
+                        vector_error!(vector.length() == Some(0), "pop from an empty vector");
+                        let (vector, elem) = evaluation_chip
+                            .vector_pop_back(
+                                layouter
+                                    .namespace(|| format!("vector pop in step#{}", interp.step)),
+                                vector,
+                                self.condition(),
+                            )
+                            .map_err(RuntimeError::from)?;
+                        store_ref(&mut self.locals, interp, &reference, vector)?;
+                        interp.stack.push(elem)
+                    }
+                    Bytecode::VecSwap(_) => {
+                        let j = interp.stack.pop()?;
+                        let i = interp.stack.pop()?;
+                        let reference = interp.stack.pop()?.into_reference()?;
+                        let vector =
+                            load_ref(&mut self.locals, interp, &reference)?.into_vector()?;
+                        vector_error!(
+                            out_of_bounds(&vector, &i) || out_of_bounds(&vector, &j),
+                            "vector index out of bounds"
+                        );
+                        let vector = evaluation_chip
+                            .vector_swap(
+                                layouter
+                                    .namespace(|| format!("vector swap in step#{}", interp.step)),
+                                vector,
+                                i,
+                                j,
+                                self.condition(),
+                            )
+                            .map_err(RuntimeError::from)?;
+                        store_ref(&mut self.locals, interp, &reference, vector)
+                    }
+                    Bytecode::Pack(index) => {
+                        pack!(ctx.resolver.field_count(*index))
+                    }
+                    Bytecode::PackGeneric(index) => {
+                        pack!(ctx.resolver.field_instantiation_count(*index))
+                    }
+                    Bytecode::Unpack(_) | Bytecode::UnpackGeneric(_) => {
+                        unpack!()
+                    }
+                    Bytecode::ImmBorrowField(index) | Bytecode::MutBorrowField(index) => {
+                        borrow_field!(ctx.resolver.field_offset(*index))
+                    }
+                    Bytecode::ImmBorrowFieldGeneric(index)
+                    | Bytecode::MutBorrowFieldGeneric(index) => {
+                        borrow_field!(ctx.resolver.field_instantiation_offset(*index))
+                    }
+                    Bytecode::Exists(index) => {
+                        let address = interp.stack.pop()?;
+                        let index = access_global!(*index, address);
+                        interp.stack.push(interp.globals.get(index)?.exists.clone())
+                    }
+                    Bytecode::MoveTo(index) => {
+                        let value = interp.stack.pop()?;
+                        let signer = interp.stack.pop()?;
+                        let signer = read_ref(&mut self.locals, interp, signer)?;
+                        let index = access_global!(*index, signer);
+                        global_error!(
+                            interp.globals.get(index)?.exists.value() == Some(F::one()),
+                            "resource already exists"
+                        );
+                        interp.globals.move_to(
+                            evaluation_chip,
+                            layouter.namespace(|| format!("move to in step#{}", interp.step)),
+                            index,
+                            value,
+                            self.condition(),
+                        )
+                    }
+                    Bytecode::MoveFrom(index) => {
+                        let address = interp.stack.pop()?;
+                        let index = access_global!(*index, address);
+                        global_error!(
+                            interp.globals.get(index)?.exists.value() == Some(F::zero()),
+                            "resource does not exist"
+                        );
+                        let value = interp.globals.move_from(
+                            evaluation_chip,
+                            layouter.namespace(|| format!("move from in step#{}", interp.step)),
+                            index,
+                            self.condition(),
+                        )?;
+                        interp.stack.push(value)
+                    }
+                    Bytecode::ImmBorrowGlobal(index) | Bytecode::MutBorrowGlobal(index) => {
+                        let address = interp.stack.pop()?;
+                        let index = access_global!(*index, address);
+                        global_error!(
+                            interp.globals.get(index)?.exists.value() == Some(F::zero()),
+                            "resource does not exist"
+                        );
+                        interp.globals.check_exists(
+                            evaluation_chip,
+                            layouter.namespace(|| format!("borrow global in step#{}", interp.step)),
+                            index,
+                            self.condition(),
+                        )?;
+                        interp
+                            .stack
+                            .push(Value::Reference(Reference::global(index)))
+                    }
+                    // the type arguments of the running function are not tracked
+                    Bytecode::ExistsGeneric(_)
+                    | Bytecode::MoveToGeneric(_)
+                    | Bytecode::MoveFromGeneric(_)
+                    | Bytecode::ImmBorrowGlobalGeneric(_)
+                    | Bytecode::MutBorrowGlobalGeneric(_) => {
+                        return Err(RuntimeError::new(StatusCode::UnsupportedBytecode)
+                            .with_message(format!("{:?} at pc {}", instruction, self.pc)));
+                    }
                     _ => unreachable!(),
                 }?;
 
-                if Some(self.pc) == self.end {
-                    trace!("reach BranchEnd at pc {}", self.pc);
-                    return Ok(ExitStatus::BranchEnd(self.pc));
-                }
                 self.pc += 1;
             }
         }
     }
 }
 
+/// The locals of the frame at call depth `frame`, `locals` of the running
+/// block if it is the current frame, otherwise those of the running block of
+/// the caller.
+fn referenced_locals<'a, F: FieldExt>(
+    locals: &'a mut Locals<F>,
+    interp: &'a mut Interpreter<F>,
+    frame: usize,
+) -> VmResult<&'a mut Locals<F>> {
+    if frame == interp.frames.len() {
+        Ok(locals)
+    } else {
+        let frame = interp.frames.get(frame).ok_or_else(|| {
+            RuntimeError::new(StatusCode::OutOfBounds)
+                .with_message(format!("no frame at call depth {}", frame))
+        })?;
+        Ok(frame.current_block().locals())
+    }
+}
+
+/// Whether a write through the reference changes a value shared with other
+/// blocks, rather than the locals copied into the running block.
+fn in_place<F: FieldExt>(interp: &Interpreter<F>, reference: &Reference) -> bool {
+    match reference.root {
+        Root::Local(frame, _) => frame != interp.frames.len(),
+        Root::Global(_) => true,
+    }
+}
+
+/// The value a reference points to.
+fn load_ref<F: FieldExt>(
+    locals: &mut Locals<F>,
+    interp: &mut Interpreter<F>,
+    reference: &Reference,
+) -> VmResult<Value<F>> {
+    match reference.root {
+        Root::Local(frame, local) => {
+            referenced_locals(locals, interp, frame)?.read_ref(local, &reference.path)
+        }
+        Root::Global(index) => Ok(interp
+            .globals
+            .get(index)?
+            .value
+            .field(&reference.path)?
+            .clone()),
+    }
+}
+
+fn store_ref<F: FieldExt>(
+    locals: &mut Locals<F>,
+    interp: &mut Interpreter<F>,
+    reference: &Reference,
+    value: Value<F>,
+) -> VmResult<()> {
+    match reference.root {
+        Root::Local(frame, local) => {
+            referenced_locals(locals, interp, frame)?.write_ref(local, &reference.path, value)
+        }
+        Root::Global(index) => {
+            *interp
+                .globals
+                .value_mut(index)?
+                .field_mut(&reference.path)? = value;
+            Ok(())
+        }
+    }
+}
+
+/// The value a reference points to, or the value an immutable reference to a
+/// snapshot holds.
+fn read_ref<F: FieldExt>(
+    locals: &mut Locals<F>,
+    interp: &mut Interpreter<F>,
+    reference: Value<F>,
+) -> VmResult<Value<F>> {
+    match reference {
+        Value::ImmRef(value) => Ok(*value),
+        reference => load_ref(locals, interp, &reference.into_reference()?),
+    }
+}
+
+/// The value a reference points to, other values as they are.
+fn deref<F: FieldExt>(
+    locals: &mut Locals<F>,
+    interp: &mut Interpreter<F>,
+    value: Value<F>,
+) -> VmResult<Value<F>> {
+    match value {
+        Value::Reference(_) | Value::ImmRef(_) => read_ref(locals, interp, value),
+        value => Ok(value),
+    }
+}
+
+fn out_of_bounds<F: FieldExt>(vector: &FVector<F>, index: &Value<F>) -> bool {
+    let index = index.value().map(|v| v.get_lower_128());
+    matches!((index, vector.length()), (Some(i), Some(len)) if i >= len as u128)
+}
+
+/// Why the arithmetic operation aborts, if it does.
+pub(crate) fn arithmetic_error(opcode: Opcode, l: MoveValue, r: MoveValue) -> Option<&'static str> {
+    let overflows = |result: VmResult<(MoveValue, bool)>| matches!(result, Ok((_, true)));
+    match opcode {
+        Opcode::Add => overflows(move_overflowing_add(l, r)).then_some("addition overflow"),
+        Opcode::Sub => overflows(move_overflowing_sub(l, r)).then_some("subtraction underflow"),
+        Opcode::Mul => overflows(move_overflowing_mul(l, r)).then_some("multiplication overflow"),
+        Opcode::Div | Opcode::Mod => move_div(l, r).is_err().then_some("division by zero"),
+        Opcode::Shl => move_shl(l, r)
+            .is_err()
+            .then_some("shift amount out of range"),
+        Opcode::Shr => move_shr(l, r)
+            .is_err()
+            .then_some("shift amount out of range"),
+        _ => None,
+    }
+}
+
 impl<F: FieldExt> std::fmt::Debug for Block<F> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -250,66 +767,61 @@ impl<F: FieldExt> std::fmt::Debug for Block<F> {
     }
 }
 
+/// How an arm of a conditional block is left.
+#[derive(Clone, Debug)]
+pub enum ArmExit<F: FieldExt> {
+    Merge,                 // reaches the merge point
+    Return(Vec<Value<F>>), // returns from the function with these values
+    Abort(u16, u128),      // aborts at pc with the error code
+}
+
 #[derive(Clone, Debug)]
 pub struct Branch<F: FieldExt> {
     pub(crate) block: Block<F>,
     pub(crate) is_running: bool, //which arm of conditional branch is running
+    pub(crate) exit: Option<ArmExit<F>>,
 }
 
+/// Both arms of a conditional branch, each running from its target up to
+/// `merge`, the first pc they share. If the arms only meet after leaving the
+/// function, `merge` is None and they end with `Ret` or `Abort`.
 #[derive(Clone, Debug)]
 pub struct ConditionalBlock<F: FieldExt> {
-    pub(crate) true_branch: Option<Branch<F>>,
-    pub(crate) false_branch: Option<Branch<F>>,
+    pub(crate) true_branch: Branch<F>,
+    pub(crate) false_branch: Branch<F>,
+    pub(crate) merge: Option<u16>,
+    pub(crate) stack_height: usize, // values above it are returned by an arm
 }
 
 impl<F: FieldExt> ConditionalBlock<F> {
-    pub fn new(true_branch: Option<Block<F>>, false_branch: Option<Block<F>>) -> Self {
-        let (true_branch, false_branch) = match (true_branch, false_branch) {
-            (Some(true_bl), Some(false_bl)) => (
-                Some(Branch {
-                    block: true_bl,
-                    is_running: true,
-                }),
-                Some(Branch {
-                    block: false_bl,
-                    is_running: false,
-                }),
-            ),
-            (Some(true_bl), None) => (
-                Some(Branch {
-                    block: true_bl,
-                    is_running: true,
-                }),
-                None,
-            ),
-            (None, Some(false_bl)) => (
-                None,
-                Some(Branch {
-                    block: false_bl,
-                    is_running: true,
-                }),
-            ),
-            _ => (None, None),
-        };
+    pub fn new(
+        true_branch: Block<F>,
+        false_branch: Block<F>,
+        merge: Option<u16>,
+        stack_height: usize,
+    ) -> Self {
         ConditionalBlock {
-            true_branch,
-            false_branch,
+            true_branch: Branch {
+                block: true_branch,
+                is_running: true,
+                exit: None,
+            },
+            false_branch: Branch {
+                block: false_branch,
+                is_running: false,
+                exit: None,
+            },
+            merge,
+            stack_height,
         }
     }
 
-    pub fn current_running(&mut self) -> Option<&mut Block<F>> {
-        let mut current = None;
-        if let Some(true_br) = &mut self.true_branch {
-            if true_br.is_running {
-                current = Some(&mut true_br.block);
-            }
-        }
-        if let Some(false_br) = &mut self.false_branch {
-            if false_br.is_running {
-                current = Some(&mut false_br.block);
-            }
+    pub fn current_running(&mut self) -> &mut Block<F> {
+        if self.true_branch.is_running {
+            &mut self.true_branch.block
+        } else {
+            &mut self.false_branch.block
         }
-        current
     }
 
     pub fn execute(
@@ -317,12 +829,78 @@ impl<F: FieldExt> ConditionalBlock<F> {
         evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
         interp: &mut Interpreter<F>,
+        ctx: &ExecutionContext,
     ) -> VmResult<ExitStatus<F>> {
-        let current = self.current_running().unwrap(); //fixme
+        let current = self.current_running();
         current.execute(
             evaluation_chip,
             layouter.namespace(|| format!("into block in step#{}", interp.step)),
             interp,
+            ctx,
+        )
+    }
+}
+
+/// A loop unrolled `bound` times. Every iteration runs the body and then
+/// the header again, both under the condition that the loop is still
+/// running, so the circuit shape doesn't depend on the witness.
+#[derive(Clone, Debug)]
+pub struct LoopBlock<F: FieldExt> {
+    pub(crate) header: u16,
+    pub(crate) branch: u16, // pc of the conditional branch leaving the loop
+    pub(crate) body_start: u16,
+    pub(crate) exit: u16,
+    pub(crate) continue_on_true: bool,
+    pub(crate) bound: usize,
+    pub(crate) iteration: usize,
+    pub(crate) condition: Value<F>, // the loop is still running in this iteration
+    pub(crate) locals: Locals<F>,   // locals carried from one iteration to the next
+    pub(crate) running: Block<F>,
+    pub(crate) in_body: bool,
+}
+
+impl<F: FieldExt> LoopBlock<F> {
+    /// The rest of the iteration after the branch, up to the back edge.
+    pub fn body(&self, condition: Value<F>) -> Block<F> {
+        Block::new(
+            self.body_start,
+            self.body_start,
+            Some(self.header),
+            self.locals.clone(),
+            self.running.code.clone(),
+            condition,
+        )
+    }
+
+    /// The start of the iteration, up to the branch.
+    pub fn header(&self) -> Block<F> {
+        Block::new(
+            self.header,
+            self.header,
+            None,
+            self.locals.clone(),
+            self.running.code.clone(),
+            self.condition.clone(),
+        )
+    }
+
+    pub fn execute(
+        &mut self,
+        evaluation_chip: &EvaluationChip<F>,
+        mut layouter: impl Layouter<F>,
+        interp: &mut Interpreter<F>,
+        ctx: &ExecutionContext,
+    ) -> VmResult<ExitStatus<F>> {
+        self.running.execute(
+            evaluation_chip,
+            layouter.namespace(|| {
+                format!(
+                    "into loop iteration #{} in step#{}",
+                    self.iteration, interp.step
+                )
+            }),
+            interp,
+            ctx,
         )
     }
 }
@@ -331,6 +909,7 @@ impl<F: FieldExt> ConditionalBlock<F> {
 pub enum ProgramBlock<F: FieldExt> {
     Block(Block<F>),
     ConditionalBlock(ConditionalBlock<F>),
+    LoopBlock(LoopBlock<F>),
 }
 
 impl<F: FieldExt> ProgramBlock<F> {
@@ -340,81 +919,74 @@ impl<F: FieldExt> ProgramBlock<F> {
         end: Option<u16>,
         locals: Locals<F>,
         code: Vec<Bytecode>,
-        condition: Option<F>,
+        condition: Value<F>,
     ) -> Self {
         Self::Block(Block::new(pc, start, end, locals, code, condition))
     }
 
     pub fn new_conditional_block(
-        true_branch: Option<Block<F>>,
-        false_branch: Option<Block<F>>,
+        true_branch: Block<F>,
+        false_branch: Block<F>,
+        merge: Option<u16>,
+        stack_height: usize,
     ) -> Self {
-        Self::ConditionalBlock(ConditionalBlock::new(true_branch, false_branch))
+        Self::ConditionalBlock(ConditionalBlock::new(
+            true_branch,
+            false_branch,
+            merge,
+            stack_height,
+        ))
     }
 
-    pub fn pc(&self) -> u16 {
+    /// The block which is executing instructions.
+    pub fn running(&mut self) -> &mut Block<F> {
         match self {
-            Self::Block(block) => block.pc,
-            Self::ConditionalBlock(_conditional) => unimplemented!(),
+            Self::Block(block) => block,
+            Self::ConditionalBlock(conditional) => conditional.current_running(),
+            Self::LoopBlock(lp) => &mut lp.running,
         }
     }
 
+    pub fn pc(&mut self) -> u16 {
+        self.running().pc
+    }
+
     pub fn add_pc(&mut self) {
-        match self {
-            Self::Block(block) => block.pc += 1,
-            Self::ConditionalBlock(_conditional) => unimplemented!(),
-        }
+        self.running().pc += 1
     }
 
     pub fn set_pc(&mut self, next: u16) {
-        match self {
-            Self::Block(block) => block.pc = next,
-            Self::ConditionalBlock(_conditional) => unimplemented!(),
-        }
+        self.running().pc = next
+    }
+
+    pub fn condition(&mut self) -> Value<F> {
+        self.running().condition.clone()
     }
 
     pub fn locals(&mut self) -> &mut Locals<F> {
-        match self {
-            Self::Block(block) => &mut block.locals,
-            Self::ConditionalBlock(_conditional) => unimplemented!(),
-        }
+        &mut self.running().locals
     }
 
     pub fn set_locals(&mut self, locals: Locals<F>) {
-        match self {
-            Self::Block(block) => block.locals = locals,
-            Self::ConditionalBlock(_conditional) => unimplemented!(),
-        }
+        self.running().locals = locals
     }
 
     pub fn merge_locals(
         &mut self,
         evaluation_chip: &EvaluationChip<F>,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         t_locals: &Locals<F>,
         f_locals: &Locals<F>,
-        condition: Option<F>,
+        condition: Value<F>,
     ) -> VmResult<()> {
-        debug_assert!(t_locals.len() == f_locals.len());
-        for i in 0..t_locals.len() {
-            if let (Some(t), Some(f)) = (t_locals.get(i), f_locals.get(i)) {
-                if !t.equals(&f) {
-                    let local = evaluation_chip
-                        .conditional_select(
-                            layouter.namespace(|| format!("merge_locals {}", i)),
-                            t.clone(),
-                            f.clone(),
-                            condition,
-                        )
-                        .map_err(|e| {
-                            error!("merge locals failed: {:?}", e);
-                            RuntimeError::from(e)
-                        })?;
-                    self.locals().store(i, local)?;
-                }
-            }
-        }
-        Ok(())
+        merge_locals(
+            evaluation_chip,
+            layouter,
+            self.locals(),
+            t_locals,
+            f_locals,
+            condition,
+        )
     }
 
     pub fn execute(
@@ -422,18 +994,64 @@ impl<F: FieldExt> ProgramBlock<F> {
         evaluation_chip: &EvaluationChip<F>,
         mut layouter: impl Layouter<F>,
         interp: &mut Interpreter<F>,
+        ctx: &ExecutionContext,
     ) -> VmResult<ExitStatus<F>> {
         match self {
             Self::Block(block) => block.execute(
                 evaluation_chip,
                 layouter.namespace(|| format!("into block in step#{}", interp.step)),
                 interp,
+                ctx,
             ),
             Self::ConditionalBlock(conditional) => conditional.execute(
                 evaluation_chip,
                 layouter.namespace(|| format!("into conditional block in step#{}", interp.step)),
                 interp,
+                ctx,
+            ),
+            Self::LoopBlock(lp) => lp.execute(
+                evaluation_chip,
+                layouter.namespace(|| format!("into loop block in step#{}", interp.step)),
+                interp,
+                ctx,
             ),
         }
     }
 }
+
+/// Store `condition ? t_locals[i] : f_locals[i]` into `target` for every local,
+/// a select is only laid out if the two differ. A local which is only valid on
+/// one side is taken from that side, the bytecode verifier guarantees it is not
+/// read on the other path.
+pub fn merge_locals<F: FieldExt>(
+    evaluation_chip: &EvaluationChip<F>,
+    mut layouter: impl Layouter<F>,
+    target: &mut Locals<F>,
+    t_locals: &Locals<F>,
+    f_locals: &Locals<F>,
+    condition: Value<F>,
+) -> VmResult<()> {
+    debug_assert!(t_locals.len() == f_locals.len());
+    for i in 0..t_locals.len() {
+        if let (Some(t), Some(f)) = (t_locals.get(i), f_locals.get(i)) {
+            let local = match (&t, &f) {
+                _ if t.equals(&f) => t,
+                (Value::Invalid, _) => f,
+                (_, Value::Invalid) => t,
+                _ => evaluation_chip
+                    .conditional_select(
+                        layouter.namespace(|| format!("merge_locals {}", i)),
+                        t,
+                        f,
+                        condition.clone(),
+                    )
+                    .map_err(|e| {
+                        error!("merge locals failed: {:?}", e);
+                        RuntimeError::from(e)
+                    })?,
+            };
+            target.store(i, local)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use blake2b_simd::Params as Blake2bParams;
+use error::{RuntimeError, StatusCode, VmResult};
+use halo2_proofs::pasta::group::ff::PrimeField;
+use halo2_proofs::pasta::{EqAffine, Fp};
+use halo2_proofs::plonk::VerifyingKey;
+use halo2_proofs::poly::commitment::Params;
+use move_binary_format::file_format::CompiledScript;
+use move_binary_format::CompiledModule;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Bumped whenever the layout of a proof file changes.
+pub const PROOF_FORMAT_VERSION: u32 = 1;
+const PROOF_MAGIC: &[u8; 4] = b"ZKMP";
+
+/// A proof of a script execution with what a verifier needs to check it on
+/// another machine. A proof file holds, little endian:
+///
+/// | magic | version | k | vk fingerprint | script hash | public inputs | proof bytes |
+///
+/// where the public inputs are a u32 count followed by 32 byte field
+/// elements, and the proof bytes are a u32 length followed by the bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveProof {
+    pub k: u32,
+    pub vk_fingerprint: [u8; 32],
+    pub script_hash: [u8; 32],
+    pub public_inputs: Vec<Fp>,
+    pub proof: Vec<u8>,
+}
+
+impl MoveProof {
+    pub fn write<W: Write>(&self, writer: &mut W) -> VmResult<()> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(PROOF_MAGIC);
+        bytes.extend_from_slice(&PROOF_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.k.to_le_bytes());
+        bytes.extend_from_slice(&self.vk_fingerprint);
+        bytes.extend_from_slice(&self.script_hash);
+        bytes.extend_from_slice(&(self.public_inputs.len() as u32).to_le_bytes());
+        for input in self.public_inputs.iter() {
+            bytes.extend_from_slice(&input.to_repr());
+        }
+        bytes.extend_from_slice(&(self.proof.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.proof);
+        writer.write_all(&bytes).map_err(io_error)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> VmResult<Self> {
+        let magic: [u8; 4] = read_bytes(reader)?;
+        if &magic != PROOF_MAGIC {
+            return Err(RuntimeError::new(StatusCode::ProofFormatError)
+                .with_message("not a proof file".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != PROOF_FORMAT_VERSION {
+            return Err(
+                RuntimeError::new(StatusCode::ProofFormatError).with_message(format!(
+                    "proof format version {} is not supported, expected {}",
+                    version, PROOF_FORMAT_VERSION
+                )),
+            );
+        }
+        let k = read_u32(reader)?;
+        let vk_fingerprint = read_bytes(reader)?;
+        let script_hash = read_bytes(reader)?;
+        let count = read_u32(reader)?;
+        let mut public_inputs = vec![];
+        for i in 0..count {
+            let repr: [u8; 32] = read_bytes(reader)?;
+            let input = Option::from(Fp::from_repr(repr)).ok_or_else(|| {
+                RuntimeError::new(StatusCode::ProofFormatError)
+                    .with_message(format!("public input #{} is not a field element", i))
+            })?;
+            public_inputs.push(input);
+        }
+        let len = read_u32(reader)?;
+        let mut proof = vec![0u8; len as usize];
+        reader.read_exact(&mut proof).map_err(io_error)?;
+        Ok(Self {
+            k,
+            vk_fingerprint,
+            script_hash,
+            public_inputs,
+            proof,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> VmResult<()> {
+        let mut file = File::create(path).map_err(io_error)?;
+        self.write(&mut file)
+    }
+
+    pub fn load(path: &Path) -> VmResult<Self> {
+        let mut file = File::open(path).map_err(io_error)?;
+        Self::read(&mut file)
+    }
+}
+
+/// Identifies a verifying key, a hash of the same representation halo2
+/// hashes into the transcript.
+pub fn vk_fingerprint(vk: &VerifyingKey<EqAffine>) -> [u8; 32] {
+    hash(format!("{:?}", vk.pinned()).as_bytes())
+}
+
+pub fn script_hash(script: &CompiledScript) -> VmResult<[u8; 32]> {
+    let mut bytes = vec![];
+    script.serialize(&mut bytes).map_err(|e| {
+        RuntimeError::new(StatusCode::ScriptLoadingError)
+            .with_message(format!("serialize script failed: {:?}", e))
+    })?;
+    Ok(hash(&bytes))
+}
+
+/// A hash of the modules in order, the order decides the module table of the
+/// circuit.
+pub fn modules_hash(modules: &[CompiledModule]) -> VmResult<[u8; 32]> {
+    let mut bytes = vec![];
+    for module in modules.iter() {
+        let mut module_bytes = vec![];
+        module.serialize(&mut module_bytes).map_err(|e| {
+            RuntimeError::new(StatusCode::ScriptLoadingError)
+                .with_message(format!("serialize module failed: {:?}", e))
+        })?;
+        bytes.extend_from_slice(&(module_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&module_bytes);
+    }
+    Ok(hash(&bytes))
+}
+
+/// The k the params were generated with, there are 2^k generators.
+pub fn params_k(params: &Params<EqAffine>) -> u32 {
+    params.get_g().len().trailing_zeros()
+}
+
+pub(crate) fn hash(bytes: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(
+        Blake2bParams::new()
+            .hash_length(32)
+            .personal(b"zkMove-Proof-Fmt")
+            .hash(bytes)
+            .as_bytes(),
+    );
+    out
+}
+
+pub(crate) fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> VmResult<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).map_err(io_error)?;
+    Ok(bytes)
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> VmResult<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+pub(crate) fn io_error(e: std::io::Error) -> RuntimeError {
+    RuntimeError::from(anyhow::Error::from(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_file_roundtrip() {
+        let proof = MoveProof {
+            k: 10,
+            vk_fingerprint: [1u8; 32],
+            script_hash: [2u8; 32],
+            public_inputs: vec![Fp::zero(), Fp::from(7), -Fp::one()],
+            proof: vec![3, 4, 5],
+        };
+        let mut bytes = vec![];
+        proof.write(&mut bytes).unwrap();
+        assert_eq!(MoveProof::read(&mut bytes.as_slice()).unwrap(), proof);
+
+        // unknown versions are refused
+        bytes[4] = 0xff;
+        assert!(MoveProof::read(&mut bytes.as_slice()).is_err());
+        // so are truncated files
+        let mut truncated = vec![];
+        proof.write(&mut truncated).unwrap();
+        truncated.pop();
+        assert!(MoveProof::read(&mut truncated.as_slice()).is_err());
+    }
+}
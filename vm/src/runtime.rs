@@ -2,10 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::circuit::MoveCircuit;
+use crate::execution::{execute, ExecutionResult};
+use crate::keys::KeyInfo;
+use crate::proof::{io_error, params_k, script_hash, vk_fingerprint, MoveProof};
+use crate::sizing::{circuit_size, CircuitSize};
+use crate::with_lanes;
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::arithmetic::FieldExt;
 use halo2_proofs::plonk::{
     create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, Error, ProvingKey, SingleVerifier,
+    VerifyingKey,
 };
 use halo2_proofs::poly::commitment::Params;
 use halo2_proofs::transcript::{Blake2bRead, Blake2bWrite, Challenge255};
@@ -18,7 +24,10 @@ use movelang::loader::MoveLoader;
 use movelang::state::StateStore;
 use plotters::prelude::*;
 use rand_core::OsRng;
+use std::cmp;
+use std::fs::File;
 use std::marker::PhantomData;
+use std::path::Path;
 
 // number of circuit rows cannot exceed 2^MAX_K
 pub const MAX_K: u32 = 18;
@@ -53,58 +62,84 @@ impl<F: FieldExt> Runtime<F> {
         modules: Vec<CompiledModule>,
         args: Option<ScriptArguments>,
         data_store: StateStore,
-    ) -> MoveCircuit {
+    ) -> MoveCircuit<F> {
         MoveCircuit::new(script, modules, args, data_store, self.loader())
     }
 
+    /// Runs the script on the native interpreter, see `execution::execute`.
+    /// Meant to check a script and its arguments before paying for a proof.
+    pub fn dry_run(&self, circuit: &MoveCircuit<F>) -> VmResult<ExecutionResult<F>> {
+        execute(circuit)
+    }
+
+    /// Counts the rows the circuit uses and the k it needs, with the rows,
+    /// regions, advice cells and lookups spent by opcode, function and code
+    /// location, see `sizing::circuit_size`.
+    pub fn circuit_size(&self, circuit: &MoveCircuit<F>) -> VmResult<CircuitSize> {
+        circuit_size(circuit)
+    }
+
     // find the minimum k that satisfies the circuit row number less than 2^k
-    pub fn find_best_k<ConcreteCircuit: Circuit<F>>(
-        &self,
-        circuit: &ConcreteCircuit,
-        instance: Vec<Vec<F>>,
-    ) -> VmResult<u32> {
-        let mut k = MIN_K;
-        while k <= MAX_K {
-            trace!("Try k={}...", k);
-            let not_enough_rows_error = Error::NotEnoughRowsAvailable { current_k: k };
-            let result = MockProver::run(k, circuit, instance.clone());
-            match result {
-                Ok(_) => {
-                    break;
-                }
-                Err(e) => {
-                    if e.to_string() == not_enough_rows_error.to_string() {
-                        k += 1;
-                    } else {
-                        debug!("Prover Error: {:?}", e);
-                        return Err(RuntimeError::new(StatusCode::ProofSystemError(e)));
-                    }
-                }
-            }
+    pub fn find_best_k(&self, circuit: &MoveCircuit<F>) -> VmResult<u32> {
+        let size = self.circuit_size(circuit)?;
+        debug!("circuit size: {}", size);
+        if size.k > MAX_K {
+            return Err(
+                RuntimeError::new(StatusCode::CircuitTooLarge).with_message(format!(
+                    "the circuit needs {} rows, more than 2^{}",
+                    size.rows + size.blinding_rows,
+                    MAX_K
+                )),
+            );
+        }
+        Ok(cmp::max(size.k, MIN_K))
+    }
+
+    /// The public inputs of a proof of the circuit, the state roots before
+    /// and after the script, the public arguments and the outputs, computed
+    /// by a dry run. Nothing is laid out for them. Fails with `MoveAbort` if the script aborts, such a run
+    /// has no proof.
+    pub fn public_inputs(&self, circuit: &MoveCircuit<F>) -> VmResult<Vec<F>> {
+        let result = self.dry_run(circuit)?;
+        match result.abort {
+            Some(abort) => Err(
+                RuntimeError::new(StatusCode::MoveAbort).with_message(format!(
+                    "aborted with error code {} at pc {} of {}",
+                    abort.code, abort.pc, abort.function
+                )),
+            ),
+            None => Ok(result.public_inputs),
         }
-        Ok(k)
     }
 
-    pub fn mock_prove_circuit<ConcreteCircuit: Circuit<F>>(
+    pub fn mock_prove_circuit(
         &self,
-        circuit: &ConcreteCircuit,
+        circuit: &MoveCircuit<F>,
         instance: Vec<Vec<F>>,
         k: u32,
     ) -> VmResult<()> {
-        let prover = MockProver::run(k, circuit, instance).map_err(|e| {
-            debug!("Prover Error: {:?}", e);
-            RuntimeError::new(StatusCode::ProofSystemError(e))
+        let prover = with_lanes!(circuit.lanes(), LANES => {
+            MockProver::run(k, &circuit.on_lanes::<LANES>(), instance).map_err(|e| {
+                debug!("Prover Error: {:?}", e);
+                RuntimeError::new(StatusCode::ProofSystemError(e))
+            })
         })?;
-        assert_eq!(prover.verify(), Ok(()));
+        prover.verify().map_err(|failures| {
+            let failures: Vec<String> = failures.iter().map(|f| f.to_string()).collect();
+            debug!("MockProver failures: {:?}", failures);
+            RuntimeError::new(StatusCode::ProofSystemError(Error::ConstraintSystemFailure))
+                .with_message(failures.join("; "))
+        })
+    }
 
-        Ok(())
+    pub fn print_circuit_layout(&self, k: u32, circuit: &MoveCircuit<F>) -> VmResult<()> {
+        with_lanes!(circuit.lanes(), LANES => {
+            self.render_layout(k, &circuit.on_lanes::<LANES>());
+            Ok(())
+        })
     }
 
-    pub fn print_circuit_layout<ConcreteCircuit: Circuit<F>>(
-        &self,
-        k: u32,
-        circuit: &ConcreteCircuit,
-    ) {
+    fn render_layout<ConcreteCircuit: Circuit<F>>(&self, k: u32, circuit: &ConcreteCircuit) {
         let root = SVGBackend::new("layout.svg", (3840, 2160)).into_drawing_area();
         root.fill(&WHITE).unwrap();
         let root = root.titled("Circuit Layout", ("sans-serif", 60)).unwrap();
@@ -118,46 +153,180 @@ impl<F: FieldExt> Runtime<F> {
 
     pub fn setup_move_circuit(
         &self,
-        circuit: &MoveCircuit,
+        circuit: &MoveCircuit<Fp>,
         params: &Params<EqAffine>,
     ) -> VmResult<ProvingKey<EqAffine>> {
+        let vk = self.setup_verifying_key(circuit, params)?;
+        self.setup_proving_key(circuit, params, vk)
+    }
+
+    pub fn setup_verifying_key(
+        &self,
+        circuit: &MoveCircuit<Fp>,
+        params: &Params<EqAffine>,
+    ) -> VmResult<VerifyingKey<EqAffine>> {
         debug!("Generate vk");
-        let vk = keygen_vk(params, circuit).map_err(|e| {
-            RuntimeError::new(StatusCode::ProofSystemError(e))
-                .with_message("keygen_vk should not fail".to_string())
-        })?;
+        with_lanes!(circuit.lanes(), LANES => {
+            keygen_vk(params, &circuit.on_lanes::<LANES>()).map_err(|e| {
+                RuntimeError::new(StatusCode::ProofSystemError(e))
+                    .with_message("keygen_vk should not fail".to_string())
+            })
+        })
+    }
+
+    fn setup_proving_key(
+        &self,
+        circuit: &MoveCircuit<Fp>,
+        params: &Params<EqAffine>,
+        vk: VerifyingKey<EqAffine>,
+    ) -> VmResult<ProvingKey<EqAffine>> {
         debug!("Generate pk");
-        let pk = keygen_pk(params, vk, circuit).map_err(|e| {
-            RuntimeError::new(StatusCode::ProofSystemError(e))
-                .with_message("keygen_pk should not fail".to_string())
-        })?;
-        Ok(pk)
+        with_lanes!(circuit.lanes(), LANES => {
+            keygen_pk(params, vk, &circuit.on_lanes::<LANES>()).map_err(|e| {
+                RuntimeError::new(StatusCode::ProofSystemError(e))
+                    .with_message("keygen_pk should not fail".to_string())
+            })
+        })
     }
 
-    pub fn prove_move_circuit(
+    pub fn save_params(&self, params: &Params<EqAffine>, path: &Path) -> VmResult<()> {
+        let mut file = File::create(path).map_err(io_error)?;
+        params.write(&mut file).map_err(io_error)
+    }
+
+    /// Refuses params generated for another k.
+    pub fn load_params(&self, path: &Path, k: u32) -> VmResult<Params<EqAffine>> {
+        let mut file = File::open(path).map_err(io_error)?;
+        let params = Params::read(&mut file).map_err(io_error)?;
+        if params_k(&params) != k {
+            return Err(
+                RuntimeError::new(StatusCode::IncompatibleKey).with_message(format!(
+                    "params are for k = {}, expected {}",
+                    params_k(&params),
+                    k
+                )),
+            );
+        }
+        Ok(params)
+    }
+
+    /// Records what the keys are generated for, see `KeyInfo`. The keys
+    /// themselves are not saved, halo2 0.1 can't write them.
+    pub fn save_key_info(
         &self,
-        circuit: MoveCircuit,
-        instance: &[&[Fp]],
+        circuit: &MoveCircuit<Fp>,
         params: &Params<EqAffine>,
-        pk: ProvingKey<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        path: &Path,
     ) -> VmResult<()> {
+        let mut info = KeyInfo::for_circuit(circuit, params_k(params))?;
+        info.vk_fingerprint = vk_fingerprint(vk);
+        info.save(path)
+    }
+
+    /// Runs `keygen_vk` again for the keys recorded with `save_key_info`.
+    /// Fails with `IncompatibleKey` before the key generation if they are
+    /// for another script, module set, k or circuit shape, see `KeyInfo`,
+    /// and after it if the public values have changed.
+    pub fn regenerate_verifying_key(
+        &self,
+        circuit: &MoveCircuit<Fp>,
+        params: &Params<EqAffine>,
+        path: &Path,
+    ) -> VmResult<VerifyingKey<EqAffine>> {
+        let info = KeyInfo::load(path)?;
+        info.check_compatible(&KeyInfo::for_circuit(circuit, params_k(params))?)?;
+        let vk = self.setup_verifying_key(circuit, params)?;
+        if vk_fingerprint(&vk) != info.vk_fingerprint {
+            return Err(RuntimeError::new(StatusCode::IncompatibleKey).with_message(
+                "keys are generated for other public arguments or outputs".to_string(),
+            ));
+        }
+        Ok(vk)
+    }
+
+    /// Runs `keygen_pk` on the key of `regenerate_verifying_key`.
+    pub fn regenerate_proving_key(
+        &self,
+        circuit: &MoveCircuit<Fp>,
+        params: &Params<EqAffine>,
+        path: &Path,
+    ) -> VmResult<ProvingKey<EqAffine>> {
+        let vk = self.regenerate_verifying_key(circuit, params, path)?;
+        self.setup_proving_key(circuit, params, vk)
+    }
+
+    /// Proves the circuit with these public inputs, the proof is not checked.
+    pub fn prove_move_circuit(
+        &self,
+        circuit: MoveCircuit<Fp>,
+        public_inputs: &[Fp],
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+    ) -> VmResult<MoveProof> {
+        let script_hash = script_hash(circuit.script())?;
         let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-        // Create a proof
         let prove_start = std::time::Instant::now();
-        create_proof(params, &pk, &[circuit], &[instance], OsRng, &mut transcript)
-            .expect("proof generation should not fail");
+        with_lanes!(circuit.lanes(), LANES => {
+            create_proof(
+                params,
+                pk,
+                &[circuit.on_lanes::<LANES>()],
+                &[&[public_inputs]],
+                OsRng,
+                &mut transcript,
+            )
+            .map_err(|e| {
+                RuntimeError::new(StatusCode::ProofSystemError(e))
+                    .with_message("proof generation failed".to_string())
+            })
+        })?;
         let proof: Vec<u8> = transcript.finalize();
         info!("proof size {} bytes", proof.len());
         let prove_time = std::time::Instant::now().duration_since(prove_start);
         info!("proving time: {} ms", prove_time.as_millis());
 
+        Ok(MoveProof {
+            k: params_k(params),
+            vk_fingerprint: vk_fingerprint(pk.get_vk()),
+            script_hash,
+            public_inputs: public_inputs.to_vec(),
+            proof,
+        })
+    }
+
+    /// Verifies a proof against the public inputs the verifier expects, which
+    /// need not be the ones recorded in the proof. Fails with
+    /// `IncompatibleKey` if the proof was made with another key or k.
+    pub fn verify_move_proof(
+        &self,
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        public_inputs: &[Fp],
+        proof: &MoveProof,
+    ) -> VmResult<()> {
+        if proof.k != params_k(params) {
+            return Err(
+                RuntimeError::new(StatusCode::IncompatibleKey).with_message(format!(
+                    "proof is made with k = {}, params are for k = {}",
+                    proof.k,
+                    params_k(params)
+                )),
+            );
+        }
+        if proof.vk_fingerprint != vk_fingerprint(vk) {
+            return Err(RuntimeError::new(StatusCode::IncompatibleKey)
+                .with_message("proof is made with another verifying key".to_string()));
+        }
         let strategy = SingleVerifier::new(params);
-        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof.proof[..]);
         let verify_start = std::time::Instant::now();
-        let result = verify_proof(params, pk.get_vk(), strategy, &[instance], &mut transcript);
+        let result = verify_proof(params, vk, strategy, &[&[public_inputs]], &mut transcript);
         let verify_time = std::time::Instant::now().duration_since(verify_start);
         info!("verification time: {} ms", verify_time.as_millis());
-        assert!(result.is_ok());
-        Ok(())
+        result.map_err(|e| {
+            RuntimeError::new(StatusCode::ProofSystemError(e))
+                .with_message("proof verification failed".to_string())
+        })
     }
 }
@@ -0,0 +1,457 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::evaluation_chip::{EvaluationChip, EvaluationConfig};
+use crate::circuit::MoveCircuit;
+use crate::interpreter::Interpreter;
+use crate::proof::io_error;
+use crate::with_lanes;
+use error::{RuntimeError, VmResult};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_proofs::plonk::{
+    Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed,
+    FloorPlanner, Instance, Selector,
+};
+use move_binary_format::file_format::Bytecode;
+use movelang::compiler::SourceLines;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::AddAssign;
+use std::path::Path;
+use std::rc::Rc;
+
+/// What the cells being laid out are spent on. The interpreter updates it as
+/// it goes, the cost counter charges every region to it. Cells laid out
+/// outside the script, to commit the state and expose the public inputs,
+/// have no function, and those laid out between instructions, to merge
+/// branches or run loops, have no pc.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CostOwner {
+    pub function: Option<String>,
+    pub pc: Option<u16>,
+    pub opcode: String,
+}
+
+impl CostOwner {
+    /// Where in the code the cells are spent, `file:line` if the source
+    /// line of the pc is known and else `function@pc`.
+    pub fn location(&self, source_lines: &SourceLines) -> String {
+        match (&self.function, self.pc) {
+            (Some(function), Some(pc)) => match source_lines.get(function, pc) {
+                Some(line) => format!("{}:{}", line.file, line.line),
+                None => format!("{}@{}", function, pc),
+            },
+            (Some(function), None) => format!("{} ({})", function, self.opcode),
+            (None, _) => format!("- ({})", self.opcode),
+        }
+    }
+}
+
+/// What a part of the circuit costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Cost {
+    /// The rows of the regions, regions using other columns can be laid out
+    /// side by side, so these may add up to more than the circuit rows.
+    pub rows: usize,
+    pub regions: usize,
+    pub advice_cells: usize,
+    /// The rows looked up in lookup tables.
+    pub lookups: usize,
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, other: Self) {
+        self.rows += other.rows;
+        self.regions += other.regions;
+        self.advice_cells += other.advice_cells;
+        self.lookups += other.lookups;
+    }
+}
+
+/// The rows a circuit uses and the k it needs, with what each instruction
+/// costs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CircuitSize {
+    /// Rows used by any column, the constants and the public inputs
+    /// included.
+    pub rows: usize,
+    /// Rows halo2 reserves at the end of every column for blinding.
+    pub blinding_rows: usize,
+    /// The smallest k with `rows + blinding_rows <= 2^k`.
+    pub k: u32,
+    pub costs: BTreeMap<CostOwner, Cost>,
+    /// The source lines the locations are reported by.
+    pub source_lines: SourceLines,
+}
+
+impl CircuitSize {
+    /// Reports the locations by the source lines of the instructions.
+    pub fn with_source_lines(mut self, source_lines: SourceLines) -> Self {
+        self.source_lines = source_lines;
+        self
+    }
+
+    pub fn total(&self) -> Cost {
+        let mut total = Cost::default();
+        for cost in self.costs.values() {
+            total += *cost;
+        }
+        total
+    }
+
+    pub fn by_opcode(&self) -> BTreeMap<String, Cost> {
+        self.sum_by(|owner| owner.opcode.clone())
+    }
+
+    pub fn by_function(&self) -> BTreeMap<String, Cost> {
+        self.sum_by(|owner| owner.function.clone().unwrap_or_else(|| "-".to_string()))
+    }
+
+    pub fn by_location(&self) -> BTreeMap<String, Cost> {
+        self.sum_by(|owner| owner.location(&self.source_lines))
+    }
+
+    fn sum_by(&self, key: impl Fn(&CostOwner) -> String) -> BTreeMap<String, Cost> {
+        let mut costs = BTreeMap::new();
+        for (owner, cost) in self.costs.iter() {
+            *costs.entry(key(owner)).or_default() += *cost;
+        }
+        costs
+    }
+
+    /// The report as JSON, the costs keyed by opcode, function and location.
+    pub fn to_json(&self) -> VmResult<String> {
+        #[derive(Serialize)]
+        struct Report {
+            rows: usize,
+            blinding_rows: usize,
+            k: u32,
+            total: Cost,
+            by_opcode: BTreeMap<String, Cost>,
+            by_function: BTreeMap<String, Cost>,
+            by_location: BTreeMap<String, Cost>,
+        }
+        let report = Report {
+            rows: self.rows,
+            blinding_rows: self.blinding_rows,
+            k: self.k,
+            total: self.total(),
+            by_opcode: self.by_opcode(),
+            by_function: self.by_function(),
+            by_location: self.by_location(),
+        };
+        serde_json::to_string_pretty(&report)
+            .map_err(|e| RuntimeError::from(anyhow::Error::from(e)))
+    }
+
+    pub fn save_json(&self, path: &Path) -> VmResult<()> {
+        fs::write(path, self.to_json()?).map_err(io_error)
+    }
+}
+
+impl fmt::Display for CircuitSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} rows and {} blinding rows, k = {}",
+            self.rows, self.blinding_rows, self.k
+        )?;
+        for (title, costs) in [
+            ("opcode", self.by_opcode()),
+            ("function", self.by_function()),
+            ("location", self.by_location()),
+        ] {
+            writeln!(
+                f,
+                "{:<32} {:>8} {:>8} {:>8} {:>8}",
+                title, "rows", "regions", "advice", "lookups"
+            )?;
+            let mut costs: Vec<_> = costs.into_iter().collect();
+            costs.sort_by(|a, b| b.1.rows.cmp(&a.1.rows).then(a.0.cmp(&b.0)));
+            for (name, cost) in costs {
+                writeln!(
+                    f,
+                    "{:<32} {:>8} {:>8} {:>8} {:>8}",
+                    name, cost.rows, cost.regions, cost.advice_cells, cost.lookups
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of an instruction without its operands.
+pub(crate) fn opcode_name(instruction: &Bytecode) -> String {
+    let name = format!("{:?}", instruction);
+    match name.find('(') {
+        Some(end) => name[..end].to_string(),
+        None => name,
+    }
+}
+
+/// Lays the circuit out without assigning anything, counting the rows the
+/// floor planner uses and what each instruction costs, to size the circuit
+/// without running the prover.
+pub fn circuit_size<F: FieldExt>(circuit: &MoveCircuit<F>) -> VmResult<CircuitSize> {
+    with_lanes!(circuit.lanes(), LANES => circuit_size_on::<F, LANES>(circuit))
+}
+
+fn circuit_size_on<F: FieldExt, const LANES: usize>(
+    circuit: &MoveCircuit<F>,
+) -> VmResult<CircuitSize> {
+    let owner = Rc::new(RefCell::new(CostOwner::default()));
+    let sizing = Sizing::<F, LANES> {
+        circuit,
+        owner: owner.clone(),
+    };
+    let mut meta = ConstraintSystem::default();
+    let config = MoveCircuit::<F>::configure(&mut meta, LANES);
+    let constants = vec![config.constant];
+    let mut counter = CostCounter {
+        owner,
+        lookup_selectors: config.lookup_selectors(),
+        rows: 0,
+        in_region: false,
+        region: Cost::default(),
+        region_rows: None,
+        costs: BTreeMap::new(),
+    };
+    SimpleFloorPlanner::synthesize(&mut counter, &sizing, config, constants)?;
+
+    // the last usable row is followed by the blinding rows
+    let blinding_rows = meta.blinding_factors() + 1;
+    let needed = cmp::max(counter.rows + blinding_rows, meta.minimum_rows());
+    let k = needed.next_power_of_two().trailing_zeros();
+
+    Ok(CircuitSize {
+        rows: counter.rows,
+        blinding_rows,
+        k,
+        costs: counter.costs,
+        source_lines: SourceLines::default(),
+    })
+}
+
+/// Synthesizes the circuit with an interpreter which tells the cost counter
+/// what it is laying out.
+struct Sizing<'c, 'l, F: FieldExt, const LANES: usize> {
+    circuit: &'c MoveCircuit<'l, F>,
+    owner: Rc<RefCell<CostOwner>>,
+}
+
+impl<'c, 'l, F: FieldExt, const LANES: usize> Circuit<F> for Sizing<'c, 'l, F, LANES> {
+    type Config = EvaluationConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            circuit: self.circuit,
+            owner: self.owner.clone(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        MoveCircuit::<F>::configure(meta, LANES)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        let evaluation_chip = EvaluationChip::<F>::construct(config, ());
+        let mut interp = Interpreter::new();
+        interp.cost_owner = Some(self.owner.clone());
+        self.circuit
+            .synthesize_with(&evaluation_chip, layouter, &mut interp)
+    }
+}
+
+/// A backend which keeps nothing but what the cells it is given cost.
+struct CostCounter {
+    owner: Rc<RefCell<CostOwner>>,
+    // each lookup argument is turned on by a selector
+    lookup_selectors: Vec<Selector>,
+    // one past the last row used by any column
+    rows: usize,
+    in_region: bool,
+    // the cost of the current region and the first and last row it uses
+    region: Cost,
+    region_rows: Option<(usize, usize)>,
+    costs: BTreeMap<CostOwner, Cost>,
+}
+
+impl CostCounter {
+    fn use_row(&mut self, row: usize) {
+        self.rows = cmp::max(self.rows, row + 1);
+    }
+
+    fn use_region_row(&mut self, row: usize) {
+        self.use_row(row);
+        self.region_rows = match self.region_rows {
+            Some((first, last)) => Some((cmp::min(first, row), cmp::max(last, row))),
+            None => Some((row, row)),
+        };
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for CostCounter {
+    fn enter_region<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.in_region = true;
+    }
+
+    fn exit_region(&mut self) {
+        self.in_region = false;
+        let mut cost = std::mem::take(&mut self.region);
+        if let Some((first, last)) = self.region_rows.take() {
+            cost.rows = last - first + 1;
+            cost.regions = 1;
+            let owner = self.owner.borrow().clone();
+            *self.costs.entry(owner).or_default() += cost;
+        }
+    }
+
+    fn enable_selector<A, AR>(
+        &mut self,
+        _annotation: A,
+        selector: &Selector,
+        row: usize,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_region_row(row);
+        self.region.lookups += self
+            .lookup_selectors
+            .iter()
+            .filter(|lookup_selector| *lookup_selector == selector)
+            .count();
+        Ok(())
+    }
+
+    fn query_instance(&self, _column: Column<Instance>, _row: usize) -> Result<Option<F>, Error> {
+        Ok(None)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _annotation: A,
+        _column: Column<Advice>,
+        row: usize,
+        _to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_region_row(row);
+        self.region.advice_cells += 1;
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _annotation: A,
+        _column: Column<Fixed>,
+        row: usize,
+        _to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // constants are assigned after their region is left, in a column of
+        // their own
+        if self.in_region {
+            self.use_region_row(row);
+        } else {
+            self.use_row(row);
+        }
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        _left_column: Column<Any>,
+        left_row: usize,
+        _right_column: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        // the public inputs are only used by copies
+        self.use_row(left_row);
+        self.use_row(right_row);
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _column: Column<Fixed>,
+        row: usize,
+        _to: Option<Assigned<F>>,
+    ) -> Result<(), Error> {
+        // the column is filled up to the blinding rows, `row` must be usable
+        self.use_row(row);
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _gadget_name: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_binary_format::file_format::FunctionHandleIndex;
+    use movelang::compiler::SourceLine;
+
+    #[test]
+    fn test_opcode_name() {
+        assert_eq!(opcode_name(&Bytecode::Add), "Add");
+        assert_eq!(opcode_name(&Bytecode::LdU64(7)), "LdU64");
+        assert_eq!(opcode_name(&Bytecode::Call(FunctionHandleIndex(0))), "Call");
+    }
+
+    #[test]
+    fn test_location() {
+        let line = |line| {
+            Some(SourceLine {
+                file: "main.move".to_string(),
+                line,
+            })
+        };
+        let mut source_lines = SourceLines::default();
+        source_lines
+            .functions
+            .insert("main".to_string(), vec![line(3), None, line(4)]);
+        let location = |function: Option<&str>, pc| {
+            let owner = CostOwner {
+                function: function.map(|function| function.to_string()),
+                pc,
+                opcode: "Add".to_string(),
+            };
+            owner.location(&source_lines)
+        };
+        assert_eq!(location(Some("main"), Some(2)), "main.move:4");
+        // no line for the pc or the function, or no pc at all
+        assert_eq!(location(Some("main"), Some(1)), "main@1");
+        assert_eq!(location(Some("f"), Some(0)), "f@0");
+        assert_eq!(location(Some("main"), None), "main (Add)");
+        assert_eq!(location(None, None), "- (Add)");
+    }
+}
@@ -39,6 +39,14 @@ impl<F: FieldExt> EvalStack<F> {
     pub fn top(&self) -> Option<&Value<F>> {
         self.0.last()
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<F: FieldExt> Default for EvalStack<F> {
@@ -74,6 +82,19 @@ impl<F: FieldExt> CallStack<F> {
     pub fn top(&mut self) -> Option<&mut Frame<F>> {
         self.0.last_mut()
     }
+
+    /// The frame at call depth `depth`, the entry function is at 0.
+    pub fn get(&mut self, depth: usize) -> Option<&mut Frame<F>> {
+        self.0.get_mut(depth)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<F: FieldExt> Default for CallStack<F> {
@@ -0,0 +1,204 @@
+// Copyright (c) zkMove Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::chips::poseidon::Poseidon;
+use error::{RuntimeError, StatusCode, VmResult};
+use halo2_proofs::arithmetic::FieldExt;
+use move_core_types::language_storage::StructTag;
+use move_core_types::value::MoveValue;
+use movelang::state::StateStore;
+use movelang::value::{address_to_field, convert_to_field};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// The low bits of the key of a resource, which index its leaf. The rest of the
+/// key must be below 2^128, so the bits are unique to the key; the keys of 2^254
+/// and above, a 2^-129 fraction of them, can't be committed.
+pub const TREE_DEPTH: usize = 126;
+
+/// A sparse Merkle tree of Poseidon hashes committing to the resources of a
+/// `StateStore`. The leaf of a resource of type `tag` at `address` has the key
+///
+///   key = hash2(address, type id)
+///
+/// it is at the index of the low `TREE_DEPTH` bits of the key and holds
+/// `hash2(key, hash of the value)`, or 0 if the resource doesn't exist. Two
+/// resources only share a leaf if their keys agree in all these bits.
+#[derive(Clone, Debug)]
+pub struct StateTree<F: FieldExt> {
+    poseidon: Poseidon<F>,
+    vector_capacity: usize,
+    empty: Vec<F>, // the root of an empty subtree at every level
+    nodes: HashMap<(usize, u128), F>,
+    keys: HashMap<u128, F>,
+}
+
+impl<F: FieldExt> StateTree<F> {
+    /// The tree of all resources in `state`. Vectors are hashed with
+    /// `vector_capacity` slots, like in the circuit.
+    pub fn new(state: &StateStore, vector_capacity: usize) -> VmResult<Self> {
+        let poseidon = Poseidon::new();
+        let mut empty = vec![F::zero()];
+        for level in 0..TREE_DEPTH {
+            empty.push(poseidon.hash2(empty[level], empty[level]));
+        }
+        let mut tree = Self {
+            poseidon,
+            vector_capacity,
+            empty,
+            nodes: HashMap::new(),
+            keys: HashMap::new(),
+        };
+        for (address, tag, value) in state.resources() {
+            let type_id = tree.type_id(&tag);
+            let key = tree.key(address_to_field(address), type_id);
+            let index = Self::index(key)?;
+            tree.claim(index, key, &tag)?;
+            let values = tree.flatten(&value)?;
+            let leaf = tree.leaf(key, true, &values);
+            tree.update(index, leaf);
+        }
+        Ok(tree)
+    }
+
+    pub fn poseidon(&self) -> &Poseidon<F> {
+        &self.poseidon
+    }
+
+    pub fn root(&self) -> F {
+        self.node(TREE_DEPTH, 0)
+    }
+
+    /// The id of a resource type, a hash of its name.
+    pub fn type_id(&self, tag: &StructTag) -> F {
+        let name = tag.to_string();
+        name.as_bytes()
+            .chunks(15)
+            .fold(F::from_u128(name.len() as u128), |hash, chunk| {
+                let chunk = chunk
+                    .iter()
+                    .fold(0u128, |acc, byte| acc << 8 | *byte as u128);
+                self.poseidon.hash2(hash, F::from_u128(chunk))
+            })
+    }
+
+    /// The index of the leaf of `key`, its low `TREE_DEPTH` bits.
+    pub fn index(key: F) -> VmResult<u128> {
+        Self::split_key(key).map(|(low, _)| low).ok_or_else(|| {
+            RuntimeError::new(StatusCode::GlobalStateError)
+                .with_message("the key of a resource is out of the range of the tree".to_string())
+        })
+    }
+
+    /// The low `TREE_DEPTH` bits of a key and the rest, None unless the rest
+    /// is below 2^128.
+    pub fn split_key(key: F) -> Option<(u128, u128)> {
+        let repr = key.to_repr();
+        let (low, high) = repr.as_ref().split_at(16);
+        let low = u128::from_le_bytes(low.try_into().expect("a key has 32 bytes"));
+        let high = u128::from_le_bytes(high[..16].try_into().expect("a key has 32 bytes"));
+        if high >> TREE_DEPTH != 0 {
+            return None;
+        }
+        let rest = low >> TREE_DEPTH | high << (128 - TREE_DEPTH);
+        Some((low & ((1 << TREE_DEPTH) - 1), rest))
+    }
+
+    pub fn key(&self, address: F, type_id: F) -> F {
+        self.poseidon.hash2(address, type_id)
+    }
+
+    pub fn leaf(&self, key: F, exists: bool, values: &[F]) -> F {
+        if !exists {
+            return F::zero();
+        }
+        let hash = values
+            .iter()
+            .fold(F::zero(), |hash, v| self.poseidon.hash2(hash, *v));
+        self.poseidon.hash2(key, hash)
+    }
+
+    /// Reserves the leaf at `index` for `key`, it fails if another resource
+    /// is already there.
+    pub fn claim(&mut self, index: u128, key: F, tag: &StructTag) -> VmResult<()> {
+        match self.keys.get(&index) {
+            Some(k) if *k != key => Err(RuntimeError::new(StatusCode::GlobalStateError)
+                .with_message(format!(
+                    "resource {} collides with another resource in the state tree",
+                    tag
+                ))),
+            _ => {
+                self.keys.insert(index, key);
+                Ok(())
+            }
+        }
+    }
+
+    /// The elements hashed for a value, see `EvaluationChip::flatten_value`.
+    pub fn flatten(&self, value: &MoveValue) -> VmResult<Vec<F>> {
+        match value {
+            MoveValue::Struct(s) => {
+                let mut out = vec![];
+                for field in s.fields() {
+                    out.extend(self.flatten(field)?);
+                }
+                Ok(out)
+            }
+            MoveValue::Vector(items) => {
+                if items.len() > self.vector_capacity {
+                    return Err(
+                        RuntimeError::new(StatusCode::VectorOperationError).with_message(format!(
+                            "a stored vector exceeds the vector capacity {}",
+                            self.vector_capacity
+                        )),
+                    );
+                }
+                // the slots of a vector are single cells
+                if items
+                    .iter()
+                    .any(|item| matches!(item, MoveValue::Vector(_) | MoveValue::Struct(_)))
+                {
+                    return Err(
+                        RuntimeError::new(StatusCode::UnsupportedMoveType).with_message(
+                            "a stored vector of vectors or structs is not supported".to_string(),
+                        ),
+                    );
+                }
+                let mut out = vec![F::from_u128(items.len() as u128)];
+                out.extend(items.iter().cloned().map(convert_to_field::<F>));
+                out.resize(self.vector_capacity + 1, F::zero());
+                Ok(out)
+            }
+            v => Ok(vec![convert_to_field(v.clone())]),
+        }
+    }
+
+    /// The siblings on the path of a leaf, from the leaf level up.
+    pub fn siblings(&self, index: u128) -> Vec<F> {
+        (0..TREE_DEPTH)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect()
+    }
+
+    pub fn update(&mut self, index: u128, leaf: F) {
+        self.nodes.insert((0, index), leaf);
+        let mut node = leaf;
+        for level in 0..TREE_DEPTH {
+            let i = index >> level;
+            let sibling = self.node(level, i ^ 1);
+            node = if i & 1 == 1 {
+                self.poseidon.hash2(sibling, node)
+            } else {
+                self.poseidon.hash2(node, sibling)
+            };
+            self.nodes.insert((level + 1, i >> 1), node);
+        }
+    }
+
+    fn node(&self, level: usize, index: u128) -> F {
+        self.nodes
+            .get(&(level, index))
+            .cloned()
+            .unwrap_or(self.empty[level])
+    }
+}
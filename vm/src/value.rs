@@ -3,7 +3,8 @@
 
 use error::{RuntimeError, StatusCode, VmResult};
 use halo2_proofs::{arithmetic::FieldExt, circuit::Cell};
-use movelang::value::{convert_to_field, move_div, move_rem};
+use move_core_types::value::MoveStruct;
+use movelang::value::{convert_to_field, field_to_address, move_div, move_rem};
 use movelang::value::{MoveValue, MoveValueType};
 
 #[derive(Clone, Debug)]
@@ -56,11 +57,92 @@ impl<F: FieldExt> FVariable<F> {
     }
 }
 
+/// Where a reference points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Root {
+    Local(
+        usize, /* call depth of the frame owning the local */
+        usize, /* local */
+    ),
+    Global(usize /* index of the resource in the global state */),
+}
+
+/// A reference to a local or a global resource, or to a field nested in it.
+/// `path` holds the field offsets from the root down to the referenced value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub(crate) root: Root,
+    pub(crate) path: Vec<usize>,
+}
+
+impl Reference {
+    pub fn new(frame: usize, local: usize) -> Self {
+        Reference {
+            root: Root::Local(frame, local),
+            path: vec![],
+        }
+    }
+
+    pub fn global(index: usize) -> Self {
+        Reference {
+            root: Root::Global(index),
+            path: vec![],
+        }
+    }
+
+    pub fn borrow_field(&self, offset: usize) -> Self {
+        let mut path = self.path.clone();
+        path.push(offset);
+        Reference {
+            root: self.root.clone(),
+            path,
+        }
+    }
+}
+
+/// A vector with a bounded capacity. The circuit has a slot for every element
+/// up to the capacity, only the first `len` slots hold elements. Slots which
+/// were never written are invalid, they are always past the end.
+#[derive(Clone, Debug)]
+pub struct FVector<F: FieldExt> {
+    pub(crate) slots: Vec<Value<F>>,
+    pub(crate) len: Box<Value<F>>, // u64
+    pub(crate) elem_ty: MoveValueType,
+}
+
+impl<F: FieldExt> FVector<F> {
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The length if it is known, it is unknown during keygen.
+    pub fn length(&self) -> Option<u64> {
+        self.len.value().map(|v| v.get_lower_128() as u64)
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.elem_ty == other.elem_ty
+            && self.len.equals(&other.len)
+            && self.slots.len() == other.slots.len()
+            && self
+                .slots
+                .iter()
+                .zip(&other.slots)
+                .all(|(a, b)| a.equals(b))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Value<F: FieldExt> {
     Invalid,
     Constant(FConstant<F>),
     Variable(FVariable<F>),
+    Struct(Vec<Value<F>>),
+    Vector(FVector<F>),
+    Reference(Reference),
+    // an immutable reference to a value computed when it was borrowed, the
+    // value can't change while it is borrowed
+    ImmRef(Box<Value<F>>),
 }
 
 impl<F: FieldExt> Value<F> {
@@ -102,23 +184,37 @@ impl<F: FieldExt> Value<F> {
             ty: MoveValueType::U128,
         }))
     }
+    pub fn new_struct(fields: Vec<Value<F>>) -> Self {
+        Self::Struct(fields)
+    }
+    pub fn new_vector(slots: Vec<Value<F>>, len: Value<F>, elem_ty: MoveValueType) -> Self {
+        Self::Vector(FVector {
+            slots,
+            len: Box::new(len),
+            elem_ty,
+        })
+    }
     pub fn value(&self) -> Option<F> {
         match self {
-            Self::Invalid => None,
             Self::Constant(c) => Some(c.value),
             Self::Variable(v) => v.value,
+            _ => None,
         }
     }
     pub fn cell(&self) -> Option<Cell> {
         match self {
-            Self::Invalid => None,
             Self::Constant(c) => c.cell,
             Self::Variable(v) => v.cell,
+            _ => None,
         }
     }
     pub fn ty(&self) -> MoveValueType {
         match self {
-            Self::Invalid => {
+            Self::Invalid
+            | Self::Struct(_)
+            | Self::Vector(_)
+            | Self::Reference(_)
+            | Self::ImmRef(_) => {
                 unreachable!()
             }
             Self::Constant(c) => c.ty.clone(),
@@ -126,11 +222,81 @@ impl<F: FieldExt> Value<F> {
         }
     }
 
+    /// Re-type the value without touching its field element or cell.
+    pub fn with_ty(self, ty: MoveValueType) -> Self {
+        match self {
+            Self::Invalid => Self::Invalid,
+            Self::Constant(c) => Self::Constant(FConstant { ty, ..c }),
+            Self::Variable(v) => Self::Variable(FVariable { ty, ..v }),
+            v => v,
+        }
+    }
+
+    pub fn into_reference(self) -> VmResult<Reference> {
+        match self {
+            Self::Reference(reference) => Ok(reference),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a reference, found {:?}", v))),
+        }
+    }
+
+    /// The value nested in a struct, `path` holds the field offsets.
+    pub fn field(&self, path: &[usize]) -> VmResult<&Value<F>> {
+        match path.split_first() {
+            None => Ok(self),
+            Some((offset, rest)) => match self {
+                Self::Struct(fields) => fields
+                    .get(*offset)
+                    .ok_or_else(|| RuntimeError::new(StatusCode::OutOfBounds))?
+                    .field(rest),
+                _ => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                    .with_message(format!("can not borrow field #{} of {:?}", offset, self))),
+            },
+        }
+    }
+
+    pub fn field_mut(&mut self, path: &[usize]) -> VmResult<&mut Value<F>> {
+        match path.split_first() {
+            None => Ok(self),
+            Some((offset, rest)) => match self {
+                Self::Struct(fields) => fields
+                    .get_mut(*offset)
+                    .ok_or_else(|| RuntimeError::new(StatusCode::OutOfBounds))?
+                    .field_mut(rest),
+                _ => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                    .with_message(format!("can not borrow field #{} of {:?}", offset, self))),
+            },
+        }
+    }
+
+    pub fn into_vector(self) -> VmResult<FVector<F>> {
+        match self {
+            Self::Vector(vector) => Ok(vector),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("expect a vector, found {:?}", v))),
+        }
+    }
+
+    /// The fields of a struct, in declaration order.
+    pub fn unpack(self) -> VmResult<Vec<Value<F>>> {
+        match self {
+            Self::Struct(fields) => Ok(fields),
+            v => Err(RuntimeError::new(StatusCode::TypeMissMatch)
+                .with_message(format!("can not unpack {:?}", v))),
+        }
+    }
+
     pub fn equals(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Invalid, Self::Invalid) => true,
             (Self::Constant(c1), Self::Constant(c2)) => c1.equals(c2),
             (Self::Variable(v1), Self::Variable(v2)) => v1.equals(v2),
+            (Self::Struct(f1), Self::Struct(f2)) => {
+                f1.len() == f2.len() && f1.iter().zip(f2).all(|(a, b)| a.equals(b))
+            }
+            (Self::Vector(v1), Self::Vector(v2)) => v1.equals(v2),
+            (Self::Reference(r1), Self::Reference(r2)) => r1 == r2,
+            (Self::ImmRef(v1), Self::ImmRef(v2)) => v1.equals(v2),
             _ => false,
         }
     }
@@ -141,6 +307,42 @@ impl<F: FieldExt> Value<F> {
             None => false,
         }
     }
+
+    /// The Move value this value holds, None if it is unknown or a mutable
+    /// reference. Only the first `len` slots of a vector are elements.
+    pub fn to_move_value(&self) -> Option<MoveValue> {
+        match self {
+            Self::Invalid | Self::Reference(_) => None,
+            Self::Constant(_) | Self::Variable(_) => {
+                let field = self.value()?;
+                let value = match self.ty() {
+                    MoveValueType::U8 => MoveValue::U8(field.get_lower_128() as u8),
+                    MoveValueType::U64 => MoveValue::U64(field.get_lower_128() as u64),
+                    MoveValueType::U128 => MoveValue::U128(field.get_lower_128()),
+                    MoveValueType::Bool => MoveValue::Bool(field == F::one()),
+                    MoveValueType::Address => MoveValue::Address(field_to_address(field).ok()?),
+                    MoveValueType::Signer => MoveValue::Signer(field_to_address(field).ok()?),
+                    _ => return None,
+                };
+                Some(value)
+            }
+            Self::Struct(fields) => Some(MoveValue::Struct(MoveStruct::new(
+                fields
+                    .iter()
+                    .map(|field| field.to_move_value())
+                    .collect::<Option<_>>()?,
+            ))),
+            Self::Vector(vector) => Some(MoveValue::Vector(
+                vector
+                    .slots
+                    .iter()
+                    .take(vector.length()? as usize)
+                    .map(|elem| elem.to_move_value())
+                    .collect::<Option<_>>()?,
+            )),
+            Self::ImmRef(value) => value.to_move_value(),
+        }
+    }
 }
 
 impl<F: FieldExt> PartialEq for Value<F> {